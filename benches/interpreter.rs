@@ -0,0 +1,84 @@
+//! Compares running a long loop from the decode cache against decoding every
+//! instruction as it is executed. Run with `cargo bench`
+
+use std::time::{Duration, Instant};
+
+use potassium::{
+    assembler::assemble_program,
+    vm::{ArithmeticMode, ExitStatus, VM},
+};
+
+/// Counts down from `#10000` a hundred times, adding into `$2` as it goes
+const SOURCE: &str = "
+    LOAD $4 #100
+    LOAD $1 #1
+outer:
+    LOAD $0 #10000
+inner:
+    ADD $2 $0 $2
+    XOR $3 $2 $3
+    SUB $0 $1 $0
+    JNZ @inner
+    SUB $4 $1 $4
+    JNZ @outer
+    HLT
+";
+
+const RUNS: u32 = 10;
+
+fn vm(use_decode_cache: bool) -> VM {
+    let mut vm = VM::new();
+    vm.arithmetic_mode = ArithmeticMode::Wrapping;
+    vm.use_decode_cache = use_decode_cache;
+    vm.load_program(assemble_program(SOURCE).expect("benchmark program assembles"));
+    vm
+}
+
+/// Counts the instructions the program executes by stepping through it
+fn count_steps() -> u64 {
+    let mut vm = vm(true);
+    let mut steps = 0;
+    loop {
+        steps += 1;
+        match vm.run_once() {
+            Ok(ExitStatus::Running) => {}
+            Ok(_) => return steps,
+            Err(e) => panic!("benchmark program failed: {e}"),
+        }
+    }
+}
+
+/// Runs the program `RUNS` times, returning the fastest run
+fn bench(use_decode_cache: bool) -> Duration {
+    let mut fastest = Duration::MAX;
+
+    for _ in 0..RUNS {
+        let mut vm = vm(use_decode_cache);
+
+        let start = Instant::now();
+        if let Err(e) = vm.run() {
+            panic!("benchmark program failed: {e}");
+        }
+        fastest = fastest.min(start.elapsed());
+    }
+
+    fastest
+}
+
+fn main() {
+    let steps = count_steps();
+    let uncached = bench(false);
+    let cached = bench(true);
+
+    for (name, time) in [("decode every step", uncached), ("decode cache", cached)] {
+        println!(
+            "{name:>18}: {time:?} for {steps} instructions ({:.2} ns each)",
+            time.as_nanos() as f64 / steps as f64
+        );
+    }
+    println!(
+        "{:>18}: {:.2}x",
+        "speedup",
+        uncached.as_secs_f64() / cached.as_secs_f64()
+    );
+}
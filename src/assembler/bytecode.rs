@@ -0,0 +1,196 @@
+//! The potassium bytecode file format.
+//!
+//! All integers are little-endian. A file starts with a 16 byte header:
+//!
+//! | offset | size | field                                      |
+//! |--------|------|--------------------------------------------|
+//! | 0      | 4    | [`MAGIC`]                                  |
+//! | 4      | 2    | format [`VERSION`]                         |
+//! | 6      | 2    | number of sections                         |
+//! | 8      | 4    | entry point, as an offset into the code    |
+//! | 12     | 4    | CRC-32 of every byte after the header      |
+//!
+//! The header is followed by the section table, one [`SECTION_ENTRY_SIZE`] byte entry per
+//! section holding its [`SectionKind`], three reserved bytes, and its offset from the start
+//! of the file and length as `u32`s. The section contents follow the table.
+
+use super::{LineInfo, Program, Section, Symbol};
+
+pub const MAGIC: [u8; 4] = *b"\x7FPBC";
+/// The current format version. Version 1 files, which predate the constant pool,
+/// can still be read
+pub const VERSION: u16 = 2;
+pub const HEADER_SIZE: usize = 16;
+pub const SECTION_ENTRY_SIZE: usize = 12;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SectionKind {
+    /// The instruction words
+    Code,
+    /// Initial contents of the heap
+    Data,
+    /// Each symbol as its section byte, `u32` offset, `u16` name length and UTF-8 name
+    Symbols,
+    /// Pairs of `u32` code offsets and the `u32` source lines they were assembled from
+    Debug,
+    /// The `f64` constants loaded by `LOADF`
+    Constants,
+}
+
+impl TryFrom<u8> for SectionKind {
+    type Error = u8;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(SectionKind::Code),
+            1 => Ok(SectionKind::Data),
+            2 => Ok(SectionKind::Symbols),
+            3 => Ok(SectionKind::Debug),
+            4 => Ok(SectionKind::Constants),
+            n => Err(n),
+        }
+    }
+}
+
+impl From<SectionKind> for u8 {
+    fn from(value: SectionKind) -> Self {
+        match value {
+            SectionKind::Code => 0,
+            SectionKind::Data => 1,
+            SectionKind::Symbols => 2,
+            SectionKind::Debug => 3,
+            SectionKind::Constants => 4,
+        }
+    }
+}
+
+impl From<Section> for u8 {
+    fn from(value: Section) -> Self {
+        match value {
+            Section::Code => 0,
+            Section::Data => 1,
+        }
+    }
+}
+
+impl TryFrom<u8> for Section {
+    type Error = u8;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(Section::Code),
+            1 => Ok(Section::Data),
+            n => Err(n),
+        }
+    }
+}
+
+/// Serializes a program into the bytecode file format
+pub fn write_bytecode(program: &Program) -> Vec<u8> {
+    let sections = [
+        (
+            SectionKind::Code,
+            program.code.iter().flatten().copied().collect(),
+        ),
+        (SectionKind::Data, program.data.clone()),
+        (SectionKind::Symbols, write_symbols(&program.symbols)),
+        (SectionKind::Debug, write_debug(&program.debug)),
+        (
+            SectionKind::Constants,
+            program
+                .constants
+                .iter()
+                .flat_map(|constant| constant.to_le_bytes())
+                .collect(),
+        ),
+    ];
+
+    let mut body = vec![];
+    let mut offset = HEADER_SIZE + sections.len() * SECTION_ENTRY_SIZE;
+
+    for (kind, contents) in &sections {
+        body.extend_from_slice(&[u8::from(*kind), 0, 0, 0]);
+        body.extend_from_slice(&(offset as u32).to_le_bytes());
+        body.extend_from_slice(&(contents.len() as u32).to_le_bytes());
+        offset += contents.len();
+    }
+
+    for (_, contents) in &sections {
+        body.extend_from_slice(contents);
+    }
+
+    let mut output = Vec::with_capacity(HEADER_SIZE + body.len());
+    output.extend_from_slice(&MAGIC);
+    output.extend_from_slice(&VERSION.to_le_bytes());
+    output.extend_from_slice(&(sections.len() as u16).to_le_bytes());
+    output.extend_from_slice(&program.entry_point.to_le_bytes());
+    output.extend_from_slice(&checksum(&body).to_le_bytes());
+    output.extend_from_slice(&body);
+    output
+}
+
+fn write_symbols(symbols: &[Symbol]) -> Vec<u8> {
+    let mut output = vec![];
+
+    for symbol in symbols {
+        output.push(u8::from(symbol.section));
+        output.extend_from_slice(&symbol.offset.to_le_bytes());
+        output.extend_from_slice(&(symbol.name.len() as u16).to_le_bytes());
+        output.extend_from_slice(symbol.name.as_bytes());
+    }
+
+    output
+}
+
+fn write_debug(debug: &[LineInfo]) -> Vec<u8> {
+    debug
+        .iter()
+        .flat_map(|info| [info.offset.to_le_bytes(), info.line.to_le_bytes()])
+        .flatten()
+        .collect()
+}
+
+/// The CRC-32 (IEEE) checksum of `bytes`
+pub fn checksum(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+
+    for &byte in bytes {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_checksum() {
+        assert_eq!(checksum(b"123456789"), 0xCBF4_3926);
+    }
+
+    #[test]
+    fn test_write_header() {
+        let program = Program {
+            code: vec![[0, 0, 0, 0]],
+            entry_point: 4,
+            ..Default::default()
+        };
+        let output = write_bytecode(&program);
+
+        assert_eq!(output[0..4], MAGIC);
+        assert_eq!(output[4..6], VERSION.to_le_bytes());
+        assert_eq!(output[6..8], 5u16.to_le_bytes());
+        assert_eq!(output[8..12], 4u32.to_le_bytes());
+        assert_eq!(output[12..16], checksum(&output[16..]).to_le_bytes());
+        assert_eq!(output.len(), HEADER_SIZE + 5 * SECTION_ENTRY_SIZE + 4);
+    }
+}
@@ -0,0 +1,211 @@
+use super::{
+    diagnostic::{Diagnostic, Span},
+    directive::Directive,
+    token::{ParseError, SpannedToken, Token},
+};
+
+/// Splits the input into its `.code` and `.data` sections, in that order.
+/// Tokens before the first section directive belong to the code section
+pub fn split_sections(input: Vec<SpannedToken>) -> (Vec<SpannedToken>, Vec<SpannedToken>) {
+    let mut code = vec![];
+    let mut data = vec![];
+    let mut in_data = false;
+
+    for (token, span) in input {
+        match token {
+            Token::Directive(Directive::Code) => in_data = false,
+            Token::Directive(Directive::Data) => in_data = true,
+            token if in_data => data.push((token, span)),
+            token => code.push((token, span)),
+        }
+    }
+
+    (code, data)
+}
+
+/// Calls `on_label` with the data offset of every label in the data section.
+/// Invalid operands are skipped here and reported by [`parse_data`]
+pub fn data_layout(input: &[SpannedToken], mut on_label: impl FnMut(&str, Span, u32)) {
+    let mut directive = None;
+    let mut offset = 0;
+
+    for (token, span) in input {
+        match (directive, token) {
+            (_, Token::LabelDeclaration(label)) => on_label(label, *span, offset),
+            (_, Token::Directive(d)) => directive = Some(*d),
+            (Some(Directive::Asciiz), Token::StringLiteral(s)) => offset += s.len() as u32 + 1,
+            (Some(Directive::Word), Token::IntegerOperand(_) | Token::LabelUsage(_)) => offset += 4,
+            (Some(Directive::Byte), Token::IntegerOperand(_)) => offset += 1,
+            (Some(Directive::Space), Token::IntegerOperand(n)) => offset += (*n).max(0) as u32,
+            _ => {}
+        }
+    }
+}
+
+/// Converts the data section into the bytes that are loaded into the VM's heap.
+/// Label usages must already have been resolved
+pub fn parse_data(input: Vec<SpannedToken>) -> Result<Vec<u8>, Vec<Diagnostic>> {
+    let mut directive = None;
+    let mut output = vec![];
+    let mut errors = vec![];
+
+    for (token, span) in input {
+        let result = match (directive, token) {
+            (_, Token::Directive(d)) => {
+                directive = Some(d);
+                Ok(())
+            }
+            (Some(Directive::Asciiz), Token::StringLiteral(s)) => {
+                output.extend_from_slice(s.as_bytes());
+                output.push(0);
+                Ok(())
+            }
+            (Some(Directive::Word), Token::IntegerOperand(n)) => {
+                output.extend_from_slice(&n.to_le_bytes());
+                Ok(())
+            }
+            (Some(Directive::Byte), Token::IntegerOperand(n)) => {
+                byte_operand(n).map(|byte| output.push(byte))
+            }
+            (Some(Directive::Space), Token::IntegerOperand(n)) => usize::try_from(n)
+                .map(|size| output.resize(output.len() + size, 0))
+                .map_err(|_| ParseError::IntegerOutOfRangeError(n)),
+            (Some(d), _) => Err(ParseError::InvalidDirectiveOperandError(d)),
+            (None, _) => Err(ParseError::InvalidDataError),
+        };
+
+        if let Err(error) = result {
+            errors.push(Diagnostic::new(error, span));
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(output)
+    } else {
+        Err(errors)
+    }
+}
+
+/// Checks that a `.byte` operand fits in a byte, either signed or unsigned
+pub fn byte_operand(n: i32) -> Result<u8, ParseError> {
+    i8::try_from(n)
+        .map(|b| b as u8)
+        .or_else(|_| u8::try_from(n))
+        .map_err(|_| ParseError::IntegerOutOfRangeError(n))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::opcode::Opcode;
+
+    fn spanned(tokens: Vec<Token>) -> Vec<SpannedToken> {
+        tokens
+            .into_iter()
+            .map(|token| (token, Span::default()))
+            .collect()
+    }
+
+    fn errors(result: Result<Vec<u8>, Vec<Diagnostic>>) -> Vec<ParseError> {
+        result
+            .unwrap_err()
+            .into_iter()
+            .map(|diagnostic| diagnostic.error)
+            .collect()
+    }
+
+    #[test]
+    fn test_split_sections() {
+        let input = spanned(vec![
+            Token::Op(Opcode::HLT),
+            Token::Directive(Directive::Data),
+            Token::Directive(Directive::Word),
+            Token::Directive(Directive::Code),
+            Token::Op(Opcode::RET),
+        ]);
+        let expected_code = spanned(vec![Token::Op(Opcode::HLT), Token::Op(Opcode::RET)]);
+        let expected_data = spanned(vec![Token::Directive(Directive::Word)]);
+
+        assert_eq!(split_sections(input), (expected_code, expected_data));
+    }
+
+    #[test]
+    fn test_data_layout() {
+        let input = spanned(vec![
+            Token::LabelDeclaration("greeting".to_owned()),
+            Token::Directive(Directive::Asciiz),
+            Token::StringLiteral("hi".to_owned()),
+            Token::LabelDeclaration("table".to_owned()),
+            Token::Directive(Directive::Word),
+            Token::LabelUsage("greeting".to_owned()),
+        ]);
+        let mut labels = vec![];
+
+        data_layout(&input, |label, _, offset| {
+            labels.push((label.to_owned(), offset))
+        });
+        assert_eq!(
+            labels,
+            vec![("greeting".to_owned(), 0), ("table".to_owned(), 3)]
+        );
+    }
+
+    #[test]
+    fn test_parse_data() {
+        let input = spanned(vec![
+            Token::Directive(Directive::Asciiz),
+            Token::StringLiteral("hi".to_owned()),
+            Token::Directive(Directive::Word),
+            Token::IntegerOperand(-2),
+            Token::Directive(Directive::Byte),
+            Token::IntegerOperand(255),
+            Token::IntegerOperand(-1),
+            Token::Directive(Directive::Space),
+            Token::IntegerOperand(2),
+        ]);
+        let expected_output = vec![b'h', b'i', 0, 0xFE, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0, 0];
+
+        assert_eq!(parse_data(input), Ok(expected_output));
+    }
+
+    #[test]
+    fn test_parse_data_byte_out_of_range() {
+        let input = spanned(vec![
+            Token::Directive(Directive::Byte),
+            Token::IntegerOperand(256),
+        ]);
+
+        assert_eq!(
+            errors(parse_data(input)),
+            vec![ParseError::IntegerOutOfRangeError(256)]
+        );
+    }
+
+    #[test]
+    fn test_parse_data_invalid_operand() {
+        let input = spanned(vec![
+            Token::Directive(Directive::Word),
+            Token::StringLiteral("hi".to_owned()),
+            Token::Directive(Directive::Byte),
+            Token::IntegerOperand(-129),
+        ]);
+
+        assert_eq!(
+            errors(parse_data(input)),
+            vec![
+                ParseError::InvalidDirectiveOperandError(Directive::Word),
+                ParseError::IntegerOutOfRangeError(-129)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_data_without_directive() {
+        let input = spanned(vec![Token::Op(Opcode::HLT)]);
+
+        assert_eq!(
+            errors(parse_data(input)),
+            vec![ParseError::InvalidDataError]
+        );
+    }
+}
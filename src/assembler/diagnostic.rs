@@ -0,0 +1,116 @@
+use std::fmt::Write;
+
+use super::token::ParseError;
+
+/// The location of a token in the source, with 1-based line and column numbers
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Span {
+    pub line: usize,
+    pub column: usize,
+    pub len: usize,
+}
+
+/// An assembler error along with where in the source it happened
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub error: ParseError,
+    pub span: Span,
+}
+
+impl Diagnostic {
+    pub fn new(error: ParseError, span: Span) -> Self {
+        Diagnostic { error, span }
+    }
+
+    /// Renders the diagnostic in the style of rustc, quoting the offending line
+    /// and pointing a caret at the span
+    pub fn render(&self, source: &str, filename: &str) -> String {
+        let Span { line, column, len } = self.span;
+        let text = source.lines().nth(line.saturating_sub(1)).unwrap_or("");
+        let gutter = " ".repeat(line.to_string().len());
+
+        let mut output = String::new();
+        writeln!(output, "error: {}", self.error).unwrap();
+        writeln!(output, "{gutter}--> {filename}:{line}:{column}").unwrap();
+        writeln!(output, "{gutter} |").unwrap();
+        writeln!(output, "{line} | {text}").unwrap();
+        write!(
+            output,
+            "{gutter} | {}{}",
+            " ".repeat(column.saturating_sub(1)),
+            "^".repeat(len.max(1))
+        )
+        .unwrap();
+
+        if let ParseError::InvalidOperandsError(opcode) = &self.error {
+            write!(
+                output,
+                "\n{gutter} = expected: {:?} {}",
+                opcode,
+                super::parser::expected_operands(*opcode)
+            )
+            .unwrap();
+        }
+
+        output
+    }
+}
+
+/// Renders every diagnostic, separated by blank lines
+pub fn render_all(diagnostics: &[Diagnostic], source: &str, filename: &str) -> String {
+    diagnostics
+        .iter()
+        .map(|diagnostic| diagnostic.render(source, filename))
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::opcode::Opcode;
+
+    #[test]
+    fn test_render() {
+        let diagnostic = Diagnostic::new(
+            ParseError::UndefinedLabelError("end".to_owned()),
+            Span {
+                line: 2,
+                column: 9,
+                len: 4,
+            },
+        );
+        let expected_output = "\
+error: The label 'end' is never declared
+ --> test.iasm:2:9
+  |
+2 | LOAD $0 @end
+  |         ^^^^";
+
+        assert_eq!(
+            diagnostic.render("HLT\nLOAD $0 @end\n", "test.iasm"),
+            expected_output
+        );
+    }
+
+    #[test]
+    fn test_render_expected_operands() {
+        let diagnostic = Diagnostic::new(
+            ParseError::InvalidOperandsError(Opcode::LOAD),
+            Span {
+                line: 1,
+                column: 1,
+                len: 4,
+            },
+        );
+        let expected_output = "\
+error: Invalid operands for LOAD
+ --> test.iasm:1:1
+  |
+1 | LOAD #5
+  | ^^^^
+  = expected: LOAD $register #integer";
+
+        assert_eq!(diagnostic.render("LOAD #5", "test.iasm"), expected_output);
+    }
+}
@@ -0,0 +1,58 @@
+use std::fmt::Display;
+
+use super::token::ParseError;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Directive {
+    Data,
+    Code,
+    Asciiz,
+    Word,
+    Byte,
+    Space,
+}
+
+impl TryFrom<&str> for Directive {
+    type Error = ParseError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value.to_ascii_lowercase().as_str() {
+            ".data" => Ok(Directive::Data),
+            ".code" => Ok(Directive::Code),
+            ".asciiz" => Ok(Directive::Asciiz),
+            ".word" => Ok(Directive::Word),
+            ".byte" => Ok(Directive::Byte),
+            ".space" => Ok(Directive::Space),
+            _ => Err(ParseError::InvalidDirectiveError(value.to_owned())),
+        }
+    }
+}
+
+impl Display for Directive {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Directive::Data => ".data",
+            Directive::Code => ".code",
+            Directive::Asciiz => ".asciiz",
+            Directive::Word => ".word",
+            Directive::Byte => ".byte",
+            Directive::Space => ".space",
+        };
+
+        write!(f, "{name}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_directive_from_str() {
+        assert_eq!(Directive::try_from(".ASCIIZ"), Ok(Directive::Asciiz));
+        assert_eq!(
+            Directive::try_from(".text"),
+            Err(ParseError::InvalidDirectiveError(".text".to_owned()))
+        );
+    }
+}
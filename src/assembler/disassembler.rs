@@ -0,0 +1,159 @@
+use super::instruction::Instruction;
+
+/// Converts program bytes back into assembly, one line per instruction word.
+/// Words that are not valid instructions come out as `.byte` lines, so that
+/// assembling the output of a word-aligned program reproduces it exactly. A
+/// trailing partial word comes out as a `.byte` line of just the bytes that are
+/// there, which the assembler rejects as the code section only holds whole words.
+/// `LOADF` is shown with its value from `constants`
+pub fn disassemble(program: &[u8], constants: &[f64]) -> Vec<String> {
+    program
+        .chunks(4)
+        .map(|chunk| {
+            let mut word = [0; 4];
+            word[..chunk.len()].copy_from_slice(chunk);
+
+            match Instruction::try_from(word) {
+                Ok(Instruction::LOADF(reg, index)) if chunk.len() == 4 => {
+                    match constants.get(index as usize) {
+                        Some(value) => format!("LOADF $f{reg} #{value:?}"),
+                        None => bytes_line(&word),
+                    }
+                }
+                Ok(instruction) if chunk.len() == 4 => instruction.to_string(),
+                _ => bytes_line(chunk),
+            }
+        })
+        .collect()
+}
+
+/// Converts a data section into `.byte` lines of up to 16 bytes each
+pub fn disassemble_data(data: &[u8]) -> Vec<String> {
+    data.chunks(16).map(bytes_line).collect()
+}
+
+fn bytes_line(bytes: &[u8]) -> String {
+    let operands: Vec<String> = bytes.iter().map(|byte| format!("#{byte}")).collect();
+    format!(".byte {}", operands.join(" "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assembler::assemble_program;
+
+    #[test]
+    fn test_disassemble() {
+        let program = [1, 0, 1, 244, 2, 0, 1, 2, 0, 0, 0, 0];
+        let expected_output = vec!["LOAD $0 #500", "ADD $0 $1 $2", "HLT"];
+
+        assert_eq!(disassemble(&program, &[]), expected_output);
+    }
+
+    #[test]
+    fn test_disassemble_unknown_bytes() {
+        let program = [255, 0, 0, 0, 6, 0, 5, 0, 1];
+        let expected_output = vec![".byte #255 #0 #0 #0", ".byte #6 #0 #5 #0", ".byte #1"];
+
+        assert_eq!(disassemble(&program, &[]), expected_output);
+    }
+
+    #[test]
+    fn test_disassemble_constants() {
+        let program = [44, 0, 0, 1, 44, 1, 0, 2];
+        let expected_output = vec!["LOADF $f0 #2.5", ".byte #44 #1 #0 #2"];
+
+        assert_eq!(disassemble(&program, &[1.0, 2.5]), expected_output);
+    }
+
+    #[test]
+    fn test_disassemble_data() {
+        let expected_output = vec![".byte #104 #105 #0"];
+
+        assert_eq!(disassemble_data(b"hi\0"), expected_output);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let source = "
+            LOAD $0 #500
+            LOAD $1 #65535
+            LOAD $2 #-1
+            ADD $0 $1 $2
+            SUB $0 $1 $2
+            MUL $0 $1 $2
+            DIV $0 $1 $2
+            JMP $0
+            JMPF $1
+            JMPB $2
+            EQ $0 $1
+            NEQ $0 $1
+            GT $0 $1
+            LT $0 $1
+            GTQ $0 $1
+            LTQ $0 $1
+            JEQ $0
+            JNEQ $0
+            ALOC $0
+            LOADB $0 $1
+            LOADH $0 $1
+            LOADW $0 $1
+            STOREB $0 $1
+            STOREH $0 $1
+            STOREW $0 $1
+            PUSH $0
+            POP $0
+            CALL $0
+            RET
+            AND $0 $1 $2
+            OR $0 $1 $2
+            XOR $0 $1 $2
+            NOT $0 $1
+            SHL $0 $1 $2
+            SHR $0 $1 $2
+            SAR $0 $1 $2
+            SHLI $0 #1 $2
+            SHRI $0 #31 $2
+            SARI $0 #0 $2
+            JC $0
+            JNC $0
+            JO $0
+            JNO $0
+            LOADF $f0 #1.5
+            LOADF $f1 #-2
+            LOADF $f2 #1.5
+            ADDF $f0 $f1 $f2
+            SUBF $f0 $f1 $f2
+            MULF $f0 $f1 $f2
+            DIVF $f0 $f1 $f2
+            EQF $f0 $f1
+            NEQF $f0 $f1
+            GTF $f0 $f1
+            LTF $f0 $f1
+            GTQF $f0 $f1
+            LTQF $f0 $f1
+            ITOF $0 $f1
+            FTOI $f0 $1
+            CMP $0 $1
+            JMPR #-8
+            JEQI #1024
+            JNOR #4
+            JZ $0
+            JNZ $0
+            JLT $0
+            JGE $0
+            JGT $0
+            JLE $0
+            SYSCALL #2
+            HLT
+            .byte #255 #1 #2 #3
+        ";
+        let program = assemble_program(source).unwrap();
+        let code: Vec<u8> = program.code.iter().flatten().copied().collect();
+        let disassembly = disassemble(&code, &program.constants).join("\n");
+        let reassembled = assemble_program(&disassembly).unwrap();
+
+        assert_eq!(reassembled.code, program.code);
+        assert_eq!(reassembled.constants, program.constants);
+    }
+}
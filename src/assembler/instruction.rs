@@ -1,6 +1,9 @@
-use crate::opcode::Opcode;
+use std::fmt::Display;
 
-#[derive(Debug, PartialEq)]
+use crate::opcode::{JumpTarget, Opcode};
+
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Instruction {
     HLT,
     LOAD(u8, i32),
@@ -19,6 +22,81 @@ pub enum Instruction {
     LTQ(u8, u8),
     JEQ(u8),
     JNEQ(u8),
+    ALOC(u8),
+    LOADB(u8, u8),
+    LOADH(u8, u8),
+    LOADW(u8, u8),
+    STOREB(u8, u8),
+    STOREH(u8, u8),
+    STOREW(u8, u8),
+    PUSH(u8),
+    POP(u8),
+    CALL(u8),
+    RET,
+    LOADHI(u8, u16),
+    LOADLO(u8, u16),
+    AND(u8, u8, u8),
+    OR(u8, u8, u8),
+    XOR(u8, u8, u8),
+    NOT(u8, u8),
+    SHL(u8, u8, u8),
+    SHR(u8, u8, u8),
+    SAR(u8, u8, u8),
+    SHLI(u8, u8, u8),
+    SHRI(u8, u8, u8),
+    SARI(u8, u8, u8),
+    JC(u8),
+    JNC(u8),
+    JO(u8),
+    JNO(u8),
+    /// Loads a float register from the constant pool entry at the given index
+    LOADF(u8, u16),
+    ADDF(u8, u8, u8),
+    SUBF(u8, u8, u8),
+    MULF(u8, u8, u8),
+    DIVF(u8, u8, u8),
+    EQF(u8, u8),
+    NEQF(u8, u8),
+    GTF(u8, u8),
+    LTF(u8, u8),
+    GTQF(u8, u8),
+    LTQF(u8, u8),
+    ITOF(u8, u8),
+    FTOI(u8, u8),
+    CMP(u8, u8),
+    JZ(u8),
+    JNZ(u8),
+    JLT(u8),
+    JGE(u8),
+    JGT(u8),
+    JLE(u8),
+    JMPR(i16),
+    JEQR(i16),
+    JNEQR(i16),
+    JZR(i16),
+    JNZR(i16),
+    JLTR(i16),
+    JGER(i16),
+    JGTR(i16),
+    JLER(i16),
+    JCR(i16),
+    JNCR(i16),
+    JOR(i16),
+    JNOR(i16),
+    JMPI(u32),
+    JEQI(u32),
+    JNEQI(u32),
+    JZI(u32),
+    JNZI(u32),
+    JLTI(u32),
+    JGEI(u32),
+    JGTI(u32),
+    JLEI(u32),
+    JCI(u32),
+    JNCI(u32),
+    JOI(u32),
+    JNOI(u32),
+    SYSCALL(u16),
 }
 
 impl From<Instruction> for Opcode {
@@ -42,6 +120,445 @@ impl From<Instruction> for Opcode {
             I::LTQ(_, _) => Opcode::LTQ,
             I::JEQ(_) => Opcode::JEQ,
             I::JNEQ(_) => Opcode::JNEQ,
+            I::ALOC(_) => Opcode::ALOC,
+            I::LOADB(_, _) => Opcode::LOADB,
+            I::LOADH(_, _) => Opcode::LOADH,
+            I::LOADW(_, _) => Opcode::LOADW,
+            I::STOREB(_, _) => Opcode::STOREB,
+            I::STOREH(_, _) => Opcode::STOREH,
+            I::STOREW(_, _) => Opcode::STOREW,
+            I::PUSH(_) => Opcode::PUSH,
+            I::POP(_) => Opcode::POP,
+            I::CALL(_) => Opcode::CALL,
+            I::RET => Opcode::RET,
+            I::LOADHI(_, _) => Opcode::LOADHI,
+            I::LOADLO(_, _) => Opcode::LOADLO,
+            I::AND(_, _, _) => Opcode::AND,
+            I::OR(_, _, _) => Opcode::OR,
+            I::XOR(_, _, _) => Opcode::XOR,
+            I::NOT(_, _) => Opcode::NOT,
+            I::SHL(_, _, _) => Opcode::SHL,
+            I::SHR(_, _, _) => Opcode::SHR,
+            I::SAR(_, _, _) => Opcode::SAR,
+            I::SHLI(_, _, _) => Opcode::SHLI,
+            I::SHRI(_, _, _) => Opcode::SHRI,
+            I::SARI(_, _, _) => Opcode::SARI,
+            I::JC(_) => Opcode::JC,
+            I::JNC(_) => Opcode::JNC,
+            I::JO(_) => Opcode::JO,
+            I::JNO(_) => Opcode::JNO,
+            I::LOADF(_, _) => Opcode::LOADF,
+            I::ADDF(_, _, _) => Opcode::ADDF,
+            I::SUBF(_, _, _) => Opcode::SUBF,
+            I::MULF(_, _, _) => Opcode::MULF,
+            I::DIVF(_, _, _) => Opcode::DIVF,
+            I::EQF(_, _) => Opcode::EQF,
+            I::NEQF(_, _) => Opcode::NEQF,
+            I::GTF(_, _) => Opcode::GTF,
+            I::LTF(_, _) => Opcode::LTF,
+            I::GTQF(_, _) => Opcode::GTQF,
+            I::LTQF(_, _) => Opcode::LTQF,
+            I::ITOF(_, _) => Opcode::ITOF,
+            I::FTOI(_, _) => Opcode::FTOI,
+            I::CMP(_, _) => Opcode::CMP,
+            I::JZ(_) => Opcode::JZ,
+            I::JNZ(_) => Opcode::JNZ,
+            I::JLT(_) => Opcode::JLT,
+            I::JGE(_) => Opcode::JGE,
+            I::JGT(_) => Opcode::JGT,
+            I::JLE(_) => Opcode::JLE,
+            I::JMPR(_) => Opcode::JMPR,
+            I::JEQR(_) => Opcode::JEQR,
+            I::JNEQR(_) => Opcode::JNEQR,
+            I::JZR(_) => Opcode::JZR,
+            I::JNZR(_) => Opcode::JNZR,
+            I::JLTR(_) => Opcode::JLTR,
+            I::JGER(_) => Opcode::JGER,
+            I::JGTR(_) => Opcode::JGTR,
+            I::JLER(_) => Opcode::JLER,
+            I::JCR(_) => Opcode::JCR,
+            I::JNCR(_) => Opcode::JNCR,
+            I::JOR(_) => Opcode::JOR,
+            I::JNOR(_) => Opcode::JNOR,
+            I::JMPI(_) => Opcode::JMPI,
+            I::JEQI(_) => Opcode::JEQI,
+            I::JNEQI(_) => Opcode::JNEQI,
+            I::JZI(_) => Opcode::JZI,
+            I::JNZI(_) => Opcode::JNZI,
+            I::JLTI(_) => Opcode::JLTI,
+            I::JGEI(_) => Opcode::JGEI,
+            I::JGTI(_) => Opcode::JGTI,
+            I::JLEI(_) => Opcode::JLEI,
+            I::JCI(_) => Opcode::JCI,
+            I::JNCI(_) => Opcode::JNCI,
+            I::JOI(_) => Opcode::JOI,
+            I::JNOI(_) => Opcode::JNOI,
+            I::SYSCALL(_) => Opcode::SYSCALL,
+        }
+    }
+}
+
+impl From<Instruction> for [u8; 4] {
+    fn from(value: Instruction) -> Self {
+        use Instruction as I;
+        let opcode = u8::from(Opcode::from(value));
+        match value {
+            I::HLT | I::RET => [opcode, 0, 0, 0],
+            I::LOAD(reg, int) => [opcode, reg, (int >> 8) as u8, int as u8],
+            I::LOADHI(reg, half) | I::LOADLO(reg, half) | I::LOADF(reg, half) => {
+                let [high, low] = half.to_be_bytes();
+                [opcode, reg, high, low]
+            }
+            I::ADD(reg1, reg2, reg3)
+            | I::SUB(reg1, reg2, reg3)
+            | I::MUL(reg1, reg2, reg3)
+            | I::DIV(reg1, reg2, reg3)
+            | I::AND(reg1, reg2, reg3)
+            | I::OR(reg1, reg2, reg3)
+            | I::XOR(reg1, reg2, reg3)
+            | I::SHL(reg1, reg2, reg3)
+            | I::SHR(reg1, reg2, reg3)
+            | I::SAR(reg1, reg2, reg3)
+            | I::ADDF(reg1, reg2, reg3)
+            | I::SUBF(reg1, reg2, reg3)
+            | I::MULF(reg1, reg2, reg3)
+            | I::DIVF(reg1, reg2, reg3) => [opcode, reg1, reg2, reg3],
+            I::SHLI(reg1, amount, reg2)
+            | I::SHRI(reg1, amount, reg2)
+            | I::SARI(reg1, amount, reg2) => [opcode, reg1, amount, reg2],
+            I::JMP(reg)
+            | I::JMPF(reg)
+            | I::JMPB(reg)
+            | I::JEQ(reg)
+            | I::JNEQ(reg)
+            | I::ALOC(reg)
+            | I::PUSH(reg)
+            | I::POP(reg)
+            | I::CALL(reg)
+            | I::JC(reg)
+            | I::JNC(reg)
+            | I::JO(reg)
+            | I::JNO(reg)
+            | I::JZ(reg)
+            | I::JNZ(reg)
+            | I::JLT(reg)
+            | I::JGE(reg)
+            | I::JGT(reg)
+            | I::JLE(reg) => [opcode, reg, 0, 0],
+            I::EQ(reg1, reg2)
+            | I::NEQ(reg1, reg2)
+            | I::GT(reg1, reg2)
+            | I::LT(reg1, reg2)
+            | I::GTQ(reg1, reg2)
+            | I::LTQ(reg1, reg2)
+            | I::CMP(reg1, reg2)
+            | I::LOADB(reg1, reg2)
+            | I::LOADH(reg1, reg2)
+            | I::LOADW(reg1, reg2)
+            | I::STOREB(reg1, reg2)
+            | I::STOREH(reg1, reg2)
+            | I::STOREW(reg1, reg2)
+            | I::NOT(reg1, reg2)
+            | I::EQF(reg1, reg2)
+            | I::NEQF(reg1, reg2)
+            | I::GTF(reg1, reg2)
+            | I::LTF(reg1, reg2)
+            | I::GTQF(reg1, reg2)
+            | I::LTQF(reg1, reg2)
+            | I::ITOF(reg1, reg2)
+            | I::FTOI(reg1, reg2) => [opcode, reg1, reg2, 0],
+            I::JMPR(offset)
+            | I::JEQR(offset)
+            | I::JNEQR(offset)
+            | I::JZR(offset)
+            | I::JNZR(offset)
+            | I::JLTR(offset)
+            | I::JGER(offset)
+            | I::JGTR(offset)
+            | I::JLER(offset)
+            | I::JCR(offset)
+            | I::JNCR(offset)
+            | I::JOR(offset)
+            | I::JNOR(offset) => {
+                let [hi, lo] = offset.to_be_bytes();
+                [opcode, hi, lo, 0]
+            }
+            I::SYSCALL(number) => {
+                let [hi, lo] = number.to_be_bytes();
+                [opcode, hi, lo, 0]
+            }
+            I::JMPI(address)
+            | I::JEQI(address)
+            | I::JNEQI(address)
+            | I::JZI(address)
+            | I::JNZI(address)
+            | I::JLTI(address)
+            | I::JGEI(address)
+            | I::JGTI(address)
+            | I::JLEI(address)
+            | I::JCI(address)
+            | I::JNCI(address)
+            | I::JOI(address)
+            | I::JNOI(address) => {
+                let [_, hi, mid, lo] = address.to_be_bytes();
+                [opcode, hi, mid, lo]
+            }
+        }
+    }
+}
+
+impl TryFrom<[u8; 4]> for Instruction {
+    type Error = [u8; 4];
+
+    /// Decodes an instruction word, rejecting words that would not be re-encoded
+    /// to the same bytes, such as ones with non-zero padding
+    fn try_from(value: [u8; 4]) -> Result<Self, Self::Error> {
+        use Instruction as I;
+        use Opcode as O;
+        let [opcode, a, b, c] = value;
+        let instruction = match Opcode::try_from(opcode).map_err(|_| value)? {
+            O::HLT => I::HLT,
+            O::LOAD => I::LOAD(a, i16::from_be_bytes([b, c]) as i32),
+            O::ADD => I::ADD(a, b, c),
+            O::SUB => I::SUB(a, b, c),
+            O::MUL => I::MUL(a, b, c),
+            O::DIV => I::DIV(a, b, c),
+            O::JMP => I::JMP(a),
+            O::JMPF => I::JMPF(a),
+            O::JMPB => I::JMPB(a),
+            O::EQ => I::EQ(a, b),
+            O::NEQ => I::NEQ(a, b),
+            O::GT => I::GT(a, b),
+            O::LT => I::LT(a, b),
+            O::GTQ => I::GTQ(a, b),
+            O::LTQ => I::LTQ(a, b),
+            O::JEQ => I::JEQ(a),
+            O::JNEQ => I::JNEQ(a),
+            O::ALOC => I::ALOC(a),
+            O::LOADB => I::LOADB(a, b),
+            O::LOADH => I::LOADH(a, b),
+            O::LOADW => I::LOADW(a, b),
+            O::STOREB => I::STOREB(a, b),
+            O::STOREH => I::STOREH(a, b),
+            O::STOREW => I::STOREW(a, b),
+            O::PUSH => I::PUSH(a),
+            O::POP => I::POP(a),
+            O::CALL => I::CALL(a),
+            O::RET => I::RET,
+            O::LOADHI => I::LOADHI(a, u16::from_be_bytes([b, c])),
+            O::LOADLO => I::LOADLO(a, u16::from_be_bytes([b, c])),
+            O::AND => I::AND(a, b, c),
+            O::OR => I::OR(a, b, c),
+            O::XOR => I::XOR(a, b, c),
+            O::NOT => I::NOT(a, b),
+            O::SHL => I::SHL(a, b, c),
+            O::SHR => I::SHR(a, b, c),
+            O::SAR => I::SAR(a, b, c),
+            O::SHLI => I::SHLI(a, b, c),
+            O::SHRI => I::SHRI(a, b, c),
+            O::SARI => I::SARI(a, b, c),
+            O::JC => I::JC(a),
+            O::JNC => I::JNC(a),
+            O::JO => I::JO(a),
+            O::JNO => I::JNO(a),
+            O::LOADF => I::LOADF(a, u16::from_be_bytes([b, c])),
+            O::ADDF => I::ADDF(a, b, c),
+            O::SUBF => I::SUBF(a, b, c),
+            O::MULF => I::MULF(a, b, c),
+            O::DIVF => I::DIVF(a, b, c),
+            O::EQF => I::EQF(a, b),
+            O::NEQF => I::NEQF(a, b),
+            O::GTF => I::GTF(a, b),
+            O::LTF => I::LTF(a, b),
+            O::GTQF => I::GTQF(a, b),
+            O::LTQF => I::LTQF(a, b),
+            O::ITOF => I::ITOF(a, b),
+            O::FTOI => I::FTOI(a, b),
+            O::CMP => I::CMP(a, b),
+            O::JZ => I::JZ(a),
+            O::JNZ => I::JNZ(a),
+            O::JLT => I::JLT(a),
+            O::JGE => I::JGE(a),
+            O::JGT => I::JGT(a),
+            O::JLE => I::JLE(a),
+            O::JMPR => I::JMPR(i16::from_be_bytes([a, b])),
+            O::JEQR => I::JEQR(i16::from_be_bytes([a, b])),
+            O::JNEQR => I::JNEQR(i16::from_be_bytes([a, b])),
+            O::JZR => I::JZR(i16::from_be_bytes([a, b])),
+            O::JNZR => I::JNZR(i16::from_be_bytes([a, b])),
+            O::JLTR => I::JLTR(i16::from_be_bytes([a, b])),
+            O::JGER => I::JGER(i16::from_be_bytes([a, b])),
+            O::JGTR => I::JGTR(i16::from_be_bytes([a, b])),
+            O::JLER => I::JLER(i16::from_be_bytes([a, b])),
+            O::JCR => I::JCR(i16::from_be_bytes([a, b])),
+            O::JNCR => I::JNCR(i16::from_be_bytes([a, b])),
+            O::JOR => I::JOR(i16::from_be_bytes([a, b])),
+            O::JNOR => I::JNOR(i16::from_be_bytes([a, b])),
+            O::JMPI => I::JMPI(u32::from_be_bytes([0, a, b, c])),
+            O::JEQI => I::JEQI(u32::from_be_bytes([0, a, b, c])),
+            O::JNEQI => I::JNEQI(u32::from_be_bytes([0, a, b, c])),
+            O::JZI => I::JZI(u32::from_be_bytes([0, a, b, c])),
+            O::JNZI => I::JNZI(u32::from_be_bytes([0, a, b, c])),
+            O::JLTI => I::JLTI(u32::from_be_bytes([0, a, b, c])),
+            O::JGEI => I::JGEI(u32::from_be_bytes([0, a, b, c])),
+            O::JGTI => I::JGTI(u32::from_be_bytes([0, a, b, c])),
+            O::JLEI => I::JLEI(u32::from_be_bytes([0, a, b, c])),
+            O::JCI => I::JCI(u32::from_be_bytes([0, a, b, c])),
+            O::JNCI => I::JNCI(u32::from_be_bytes([0, a, b, c])),
+            O::JOI => I::JOI(u32::from_be_bytes([0, a, b, c])),
+            O::JNOI => I::JNOI(u32::from_be_bytes([0, a, b, c])),
+            O::SYSCALL => I::SYSCALL(u16::from_be_bytes([a, b])),
+        };
+
+        if <[u8; 4]>::from(instruction) == value {
+            Ok(instruction)
+        } else {
+            Err(value)
+        }
+    }
+}
+
+impl Instruction {
+    /// Builds any form of a jump with an immediate operand, for the jump at `offset`.
+    /// The relative and absolute forms take `value` as their offset or address. The
+    /// register form instead takes it as the address to jump to, and uses a relative
+    /// offset if one fits, or an absolute address otherwise. Returns `None` if the
+    /// value does not fit in the instruction
+    pub fn jump_immediate(opcode: Opcode, offset: u32, value: i32) -> Option<Instruction> {
+        let (_, target) = opcode.jump()?;
+        let [_, high, middle, low] = value.to_be_bytes();
+        let word = match target {
+            JumpTarget::Register => {
+                let relative = opcode.jump_form(JumpTarget::Relative)?;
+                let absolute = opcode.jump_form(JumpTarget::Absolute)?;
+                return i32::try_from(value as i64 - offset as i64)
+                    .ok()
+                    .and_then(|relative_offset| {
+                        Self::jump_immediate(relative, offset, relative_offset)
+                    })
+                    .or_else(|| Self::jump_immediate(absolute, offset, value));
+            }
+            JumpTarget::Relative if i16::try_from(value).is_ok() => [middle, low, 0],
+            JumpTarget::Absolute if (0..1 << 24).contains(&value) => [high, middle, low],
+            _ => return None,
+        };
+
+        Instruction::try_from([u8::from(opcode), word[0], word[1], word[2]]).ok()
+    }
+
+    /// Loads any `i32` into a register. `LOAD` sign-extends a 16 bit immediate, so
+    /// values outside of the `i16` range are split into a `LOADHI` and a `LOADLO`
+    pub fn load_immediate(register: u8, int: i32) -> Vec<Instruction> {
+        if i16::try_from(int).is_ok() {
+            vec![Instruction::LOAD(register, int)]
+        } else {
+            vec![
+                Instruction::LOADHI(register, (int >> 16) as u16),
+                Instruction::LOADLO(register, int as u16),
+            ]
+        }
+    }
+}
+
+/// Formats the instruction in the syntax accepted by the assembler, except for
+/// `LOADF`, which only knows the index of its constant and shows it as `[index]`.
+/// The disassembler replaces it with the constant's value
+impl Display for Instruction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        use Instruction as I;
+        let name = format!("{:?}", Opcode::from(*self));
+        match *self {
+            I::HLT | I::RET => write!(f, "{name}"),
+            I::LOAD(reg, int) => write!(f, "{name} ${reg} #{int}"),
+            I::JMPR(offset)
+            | I::JEQR(offset)
+            | I::JNEQR(offset)
+            | I::JZR(offset)
+            | I::JNZR(offset)
+            | I::JLTR(offset)
+            | I::JGER(offset)
+            | I::JGTR(offset)
+            | I::JLER(offset)
+            | I::JCR(offset)
+            | I::JNCR(offset)
+            | I::JOR(offset)
+            | I::JNOR(offset) => write!(f, "{name} #{offset}"),
+            I::JMPI(address)
+            | I::JEQI(address)
+            | I::JNEQI(address)
+            | I::JZI(address)
+            | I::JNZI(address)
+            | I::JLTI(address)
+            | I::JGEI(address)
+            | I::JGTI(address)
+            | I::JLEI(address)
+            | I::JCI(address)
+            | I::JNCI(address)
+            | I::JOI(address)
+            | I::JNOI(address) => write!(f, "{name} #{address}"),
+            I::SYSCALL(number) => write!(f, "{name} #{number}"),
+            I::LOADHI(reg, half) | I::LOADLO(reg, half) => write!(f, "{name} ${reg} #{half}"),
+            I::LOADF(reg, index) => write!(f, "{name} $f{reg} [{index}]"),
+            I::ADDF(reg1, reg2, reg3)
+            | I::SUBF(reg1, reg2, reg3)
+            | I::MULF(reg1, reg2, reg3)
+            | I::DIVF(reg1, reg2, reg3) => write!(f, "{name} $f{reg1} $f{reg2} $f{reg3}"),
+            I::EQF(reg1, reg2)
+            | I::NEQF(reg1, reg2)
+            | I::GTF(reg1, reg2)
+            | I::LTF(reg1, reg2)
+            | I::GTQF(reg1, reg2)
+            | I::LTQF(reg1, reg2) => write!(f, "{name} $f{reg1} $f{reg2}"),
+            I::ITOF(reg1, reg2) => write!(f, "{name} ${reg1} $f{reg2}"),
+            I::FTOI(reg1, reg2) => write!(f, "{name} $f{reg1} ${reg2}"),
+            I::ADD(reg1, reg2, reg3)
+            | I::SUB(reg1, reg2, reg3)
+            | I::MUL(reg1, reg2, reg3)
+            | I::DIV(reg1, reg2, reg3)
+            | I::AND(reg1, reg2, reg3)
+            | I::OR(reg1, reg2, reg3)
+            | I::XOR(reg1, reg2, reg3)
+            | I::SHL(reg1, reg2, reg3)
+            | I::SHR(reg1, reg2, reg3)
+            | I::SAR(reg1, reg2, reg3) => write!(f, "{name} ${reg1} ${reg2} ${reg3}"),
+            I::SHLI(reg1, amount, reg2)
+            | I::SHRI(reg1, amount, reg2)
+            | I::SARI(reg1, amount, reg2) => {
+                write!(f, "{name} ${reg1} #{amount} ${reg2}")
+            }
+            I::JMP(reg)
+            | I::JMPF(reg)
+            | I::JMPB(reg)
+            | I::JEQ(reg)
+            | I::JNEQ(reg)
+            | I::ALOC(reg)
+            | I::PUSH(reg)
+            | I::POP(reg)
+            | I::CALL(reg)
+            | I::JC(reg)
+            | I::JNC(reg)
+            | I::JO(reg)
+            | I::JNO(reg)
+            | I::JZ(reg)
+            | I::JNZ(reg)
+            | I::JLT(reg)
+            | I::JGE(reg)
+            | I::JGT(reg)
+            | I::JLE(reg) => write!(f, "{name} ${reg}"),
+            I::EQ(reg1, reg2)
+            | I::NEQ(reg1, reg2)
+            | I::GT(reg1, reg2)
+            | I::LT(reg1, reg2)
+            | I::GTQ(reg1, reg2)
+            | I::LTQ(reg1, reg2)
+            | I::CMP(reg1, reg2)
+            | I::LOADB(reg1, reg2)
+            | I::LOADH(reg1, reg2)
+            | I::LOADW(reg1, reg2)
+            | I::STOREB(reg1, reg2)
+            | I::STOREH(reg1, reg2)
+            | I::STOREW(reg1, reg2)
+            | I::NOT(reg1, reg2) => write!(f, "{name} ${reg1} ${reg2}"),
         }
     }
 }
@@ -55,4 +572,76 @@ mod tests {
         let test_instruction = Instruction::HLT;
         assert_eq!(Opcode::from(test_instruction), Opcode::HLT);
     }
+
+    #[test]
+    fn test_encode_instruction() {
+        assert_eq!(<[u8; 4]>::from(Instruction::LOAD(0, 500)), [1, 0, 1, 244]);
+        assert_eq!(<[u8; 4]>::from(Instruction::EQ(0, 1)), [9, 0, 1, 0]);
+        assert_eq!(<[u8; 4]>::from(Instruction::JMPR(-4)), [64, 255, 252, 0]);
+        assert_eq!(<[u8; 4]>::from(Instruction::JMPI(0x10203)), [65, 1, 2, 3]);
+    }
+
+    #[test]
+    fn test_decode_instruction() {
+        assert_eq!(
+            Instruction::try_from([1, 0, 1, 244]),
+            Ok(Instruction::LOAD(0, 500))
+        );
+        assert_eq!(
+            Instruction::try_from([1, 0, 255, 255]),
+            Ok(Instruction::LOAD(0, -1))
+        );
+        assert_eq!(
+            Instruction::try_from([28, 0, 255, 254]),
+            Ok(Instruction::LOADHI(0, 0xFFFE))
+        );
+        assert_eq!(Instruction::try_from([255, 0, 0, 0]), Err([255, 0, 0, 0]));
+        assert_eq!(Instruction::try_from([0, 0, 0, 1]), Err([0, 0, 0, 1]));
+    }
+
+    #[test]
+    fn test_display_instruction() {
+        assert_eq!(Instruction::LOAD(0, 500).to_string(), "LOAD $0 #500");
+        assert_eq!(Instruction::ADD(0, 1, 2).to_string(), "ADD $0 $1 $2");
+        assert_eq!(Instruction::SHLI(0, 3, 1).to_string(), "SHLI $0 #3 $1");
+        assert_eq!(Instruction::ADDF(0, 1, 2).to_string(), "ADDF $f0 $f1 $f2");
+        assert_eq!(Instruction::FTOI(0, 1).to_string(), "FTOI $f0 $1");
+        assert_eq!(Instruction::RET.to_string(), "RET");
+        assert_eq!(Instruction::JLTR(-8).to_string(), "JLTR #-8");
+        assert_eq!(Instruction::JMPI(65536).to_string(), "JMPI #65536");
+    }
+
+    #[test]
+    fn test_jump_immediate() {
+        assert_eq!(
+            Instruction::jump_immediate(Opcode::JEQ, 8, 0),
+            Some(Instruction::JEQR(-8))
+        );
+        assert_eq!(
+            Instruction::jump_immediate(Opcode::JEQ, 0, 40000),
+            Some(Instruction::JEQI(40000))
+        );
+        assert_eq!(Instruction::jump_immediate(Opcode::JMPR, 0, 40000), None);
+        assert_eq!(Instruction::jump_immediate(Opcode::JMPI, 0, 1 << 24), None);
+        assert_eq!(Instruction::jump_immediate(Opcode::JMPF, 0, 4), None);
+    }
+
+    #[test]
+    fn test_load_immediate() {
+        assert_eq!(
+            Instruction::load_immediate(0, -32768),
+            vec![Instruction::LOAD(0, -32768)]
+        );
+        assert_eq!(
+            Instruction::load_immediate(0, 65535),
+            vec![Instruction::LOADHI(0, 0), Instruction::LOADLO(0, 0xFFFF)]
+        );
+        assert_eq!(
+            Instruction::load_immediate(1, -70000),
+            vec![
+                Instruction::LOADHI(1, 0xFFFE),
+                Instruction::LOADLO(1, 0xEE90)
+            ]
+        );
+    }
 }
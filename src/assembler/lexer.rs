@@ -1,38 +1,153 @@
-use super::token::{ParseError, Token};
-
-pub fn lex(input: &str) -> Result<Vec<Token>, ParseError> {
-    input
-        .split_ascii_whitespace()
-        .into_iter()
-        .map(|s| Token::try_from(s))
-        .collect()
+use std::{
+    iter::{Enumerate, Peekable},
+    str::Chars,
+};
+
+use super::{
+    diagnostic::{Diagnostic, Span},
+    token::{unescape, ParseError, SpannedToken, Token},
+};
+
+type LineChars<'a> = Peekable<Enumerate<Chars<'a>>>;
+
+/// Splits the input into tokens, reporting every token that could not be lexed.
+/// Comments start with `;` or `//` and run to the end of the line
+pub fn lex(input: &str) -> Result<Vec<SpannedToken>, Vec<Diagnostic>> {
+    let mut output = vec![];
+    let mut errors = vec![];
+
+    for (line_number, line) in input.lines().enumerate() {
+        let mut chars = line.chars().enumerate().peekable();
+
+        while let Some(&(start, c)) = chars.peek() {
+            if c.is_ascii_whitespace() {
+                chars.next();
+                continue;
+            }
+            if starts_comment(&mut chars) {
+                break;
+            }
+
+            let token = if c == '"' {
+                chars.next();
+                lex_string(&mut chars).map(Token::StringLiteral)
+            } else {
+                Token::try_from(lex_word(&mut chars).as_str())
+            };
+
+            let end = chars
+                .peek()
+                .map_or_else(|| line.chars().count(), |&(end, _)| end);
+            let span = Span {
+                line: line_number + 1,
+                column: start + 1,
+                len: end - start,
+            };
+
+            match token {
+                Ok(token) => output.push((token, span)),
+                Err(e) => errors.push(Diagnostic::new(e, span)),
+            }
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(output)
+    } else {
+        Err(errors)
+    }
+}
+
+/// Whether the next characters begin a comment
+fn starts_comment(chars: &mut LineChars) -> bool {
+    match chars.peek() {
+        Some((_, ';')) => true,
+        Some((_, '/')) => matches!(chars.clone().nth(1), Some((_, '/'))),
+        _ => false,
+    }
+}
+
+/// Reads up to the next whitespace or comment. Whitespace is allowed between single
+/// quotes so that character literals like `#' '` make up a single word
+fn lex_word(chars: &mut LineChars) -> String {
+    let mut word = String::new();
+    let mut quoted = false;
+    let mut escaped = false;
+
+    while let Some(&(_, c)) = chars.peek() {
+        if !quoted && (c.is_ascii_whitespace() || starts_comment(chars)) {
+            break;
+        }
+        chars.next();
+
+        if quoted && c == '\\' && !escaped {
+            escaped = true;
+        } else {
+            if c == '\'' && !escaped {
+                quoted = !quoted;
+            }
+            escaped = false;
+        }
+        word.push(c);
+    }
+
+    word
+}
+
+/// Reads the rest of a string literal whose opening quote has already been consumed.
+/// A bad escape sequence is reported once the whole literal has been read
+fn lex_string(chars: &mut LineChars) -> Result<String, ParseError> {
+    let mut output = String::new();
+    let mut error = None;
+
+    loop {
+        let (_, c) = chars.next().ok_or(ParseError::UnterminatedStringError)?;
+        match c {
+            '"' => return error.map_or(Ok(output), Err),
+            '\\' => {
+                let (_, c) = chars.next().ok_or(ParseError::UnterminatedStringError)?;
+                match unescape(c) {
+                    Some(c) => output.push(c),
+                    None => error = error.or(Some(ParseError::InvalidEscapeError(c))),
+                }
+            }
+            c => output.push(c),
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::opcode::Opcode;
+    use crate::{assembler::directive::Directive, opcode::Opcode};
 
     use super::*;
 
+    /// Lexes the input, dropping spans
+    fn tokens(input: &str) -> Result<Vec<Token>, Vec<ParseError>> {
+        lex(input)
+            .map(|tokens| tokens.into_iter().map(|(token, _)| token).collect())
+            .map_err(|errors| errors.into_iter().map(|e| e.error).collect())
+    }
+
     #[test]
     fn test_lex_opcode() {
         let expected_output = vec![Token::Op(Opcode::HLT)];
 
-        assert_eq!(lex("HLT"), Ok(expected_output));
+        assert_eq!(tokens("HLT"), Ok(expected_output));
     }
 
     #[test]
     fn test_lex_register() {
         let expected_output = vec![Token::Register(0)];
 
-        assert_eq!(lex("$0"), Ok(expected_output));
+        assert_eq!(tokens("$0"), Ok(expected_output));
     }
 
     #[test]
     fn test_lex_integer_operand() {
         let expected_output = vec![Token::IntegerOperand(500)];
 
-        assert_eq!(lex("#500"), Ok(expected_output));
+        assert_eq!(tokens("#500"), Ok(expected_output));
     }
 
     #[test]
@@ -43,6 +158,165 @@ mod tests {
             Token::IntegerOperand(500),
         ];
 
-        assert_eq!(lex("LOAD $0 #500"), Ok(expected_output));
+        assert_eq!(tokens("LOAD $0 #500"), Ok(expected_output));
+    }
+
+    #[test]
+    fn test_lex_label_declaration() {
+        let expected_output = vec![Token::LabelDeclaration("loop".to_owned())];
+
+        assert_eq!(tokens("loop:"), Ok(expected_output));
+    }
+
+    #[test]
+    fn test_lex_label_usage() {
+        let expected_output = vec![Token::LabelUsage("loop".to_owned())];
+
+        assert_eq!(tokens("@loop"), Ok(expected_output));
+    }
+
+    #[test]
+    fn test_lex_invalid_label() {
+        assert_eq!(
+            tokens("@"),
+            Err(vec![ParseError::InvalidLabelError("".to_owned())])
+        );
+    }
+
+    #[test]
+    fn test_lex_directive() {
+        let expected_output = vec![Token::Directive(Directive::Word), Token::IntegerOperand(5)];
+
+        assert_eq!(tokens(".word #5"), Ok(expected_output));
+    }
+
+    #[test]
+    fn test_lex_string_literal() {
+        let expected_output = vec![
+            Token::Directive(Directive::Asciiz),
+            Token::StringLiteral("hello, \"world\"\n".to_owned()),
+        ];
+
+        assert_eq!(
+            tokens(r#".asciiz "hello, \"world\"\n""#),
+            Ok(expected_output)
+        );
+    }
+
+    #[test]
+    fn test_lex_unterminated_string() {
+        assert_eq!(
+            tokens(r#".asciiz "hello"#),
+            Err(vec![ParseError::UnterminatedStringError])
+        );
+    }
+
+    #[test]
+    fn test_lex_invalid_escape() {
+        assert_eq!(
+            tokens(r#".asciiz "\q""#),
+            Err(vec![ParseError::InvalidEscapeError('q')])
+        );
+    }
+
+    #[test]
+    fn test_lex_spans() {
+        let expected_output = vec![
+            (
+                Token::Op(Opcode::HLT),
+                Span {
+                    line: 1,
+                    column: 1,
+                    len: 3,
+                },
+            ),
+            (
+                Token::Register(10),
+                Span {
+                    line: 2,
+                    column: 3,
+                    len: 3,
+                },
+            ),
+        ];
+
+        assert_eq!(lex("HLT\n  $10"), Ok(expected_output));
+    }
+
+    #[test]
+    fn test_lex_multiple_errors() {
+        let expected_output = vec![
+            Diagnostic::new(
+                ParseError::InvalidOpcodeError("FOO".to_owned()),
+                Span {
+                    line: 1,
+                    column: 1,
+                    len: 3,
+                },
+            ),
+            Diagnostic::new(
+                ParseError::InvalidLabelError("".to_owned()),
+                Span {
+                    line: 2,
+                    column: 9,
+                    len: 1,
+                },
+            ),
+        ];
+
+        assert_eq!(lex("FOO $0\nLOAD $0 @"), Err(expected_output));
+    }
+
+    #[test]
+    fn test_lex_comments() {
+        let expected_output = vec![
+            Token::Op(Opcode::HLT),
+            Token::Op(Opcode::LOAD),
+            Token::Register(0),
+            Token::IntegerOperand(1),
+        ];
+
+        assert_eq!(
+            tokens("; a comment\nHLT // another\nLOAD $0 #1;trailing"),
+            Ok(expected_output)
+        );
+    }
+
+    #[test]
+    fn test_lex_comment_in_string() {
+        let expected_output = vec![Token::StringLiteral("a; b // c".to_owned())];
+
+        assert_eq!(tokens(r#""a; b // c" ; comment"#), Ok(expected_output));
+    }
+
+    #[test]
+    fn test_lex_integer_formats() {
+        let expected_output = vec![
+            Token::IntegerOperand(500),
+            Token::IntegerOperand(5),
+            Token::IntegerOperand(-5),
+            Token::IntegerOperand(-16),
+            Token::IntegerOperand(97),
+            Token::IntegerOperand(32),
+            Token::IntegerOperand(59),
+            Token::IntegerOperand(39),
+        ];
+
+        assert_eq!(
+            tokens(r"#0x1F4 #0b101 #-5 #-0x10 #'a' #' ' #';' #'''"),
+            Ok(expected_output)
+        );
+    }
+
+    #[test]
+    fn test_lex_invalid_integers() {
+        assert_eq!(
+            tokens("#'ab' #0x-5 #''"),
+            Err(vec![
+                ParseError::InvalidIntegerError("'ab'".to_owned()),
+                ParseError::InvalidIntegerError("0x-5".to_owned()),
+                ParseError::InvalidIntegerError("''".to_owned()),
+            ])
+        );
     }
 }
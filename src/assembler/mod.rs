@@ -1,77 +1,200 @@
+use data::{byte_operand, parse_data, split_sections};
+use directive::Directive;
 use lexer::lex;
 use parser::parse;
-use token::ParseError;
-
+use symbols::{collect_labels, resolve_labels};
+use token::{SpannedToken, Token};
+
+pub use bytecode::write_bytecode;
+pub use diagnostic::{render_all, Diagnostic, Span};
+pub use disassembler::{disassemble, disassemble_data};
+pub use instruction::Instruction;
+pub use symbols::{Section, Symbol};
+pub use token::ParseError;
+
+pub mod bytecode;
+mod data;
+mod diagnostic;
+mod directive;
+pub mod disassembler;
 mod instruction;
 mod lexer;
 mod parser;
+mod symbols;
 mod token;
 
-pub fn assemble(input: &str) -> Result<Vec<[u8; 4]>, ParseError> {
-    let tokens = lex(input)?;
-    let instructions = parse(tokens)?;
+/// The label the VM starts executing at, if the program declares it
+pub const ENTRY_LABEL: &str = "main";
+
+/// An assembled program: the instruction words, the initial contents of the heap
+/// and the metadata that is written alongside them in a bytecode file
+#[derive(Debug, Default, PartialEq)]
+pub struct Program {
+    pub code: Vec<[u8; 4]>,
+    pub data: Vec<u8>,
+    /// The float constant pool that `LOADF` indexes into
+    pub constants: Vec<f64>,
+    pub symbols: Vec<Symbol>,
+    pub debug: Vec<LineInfo>,
+    pub entry_point: u32,
+}
+
+/// Maps the instruction at a code offset back to the source line it came from
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LineInfo {
+    pub offset: u32,
+    pub line: u32,
+}
+
+/// Assembles a program, discarding its `.data` section and constant pool
+pub fn assemble(input: &str) -> Result<Vec<[u8; 4]>, Vec<Diagnostic>> {
+    assemble_program(input).map(|program| program.code)
+}
+
+/// Assembles code that will be appended at `origin` to a program that is already
+/// loaded, adding any new float constants to the end of that program's pool
+pub fn assemble_line(
+    input: &str,
+    origin: u32,
+    constants: &mut Vec<f64>,
+) -> Result<Vec<[u8; 4]>, Vec<Diagnostic>> {
+    let program = assemble_with_constants(input, origin, constants.clone())?;
+    *constants = program.constants;
+    Ok(program.code)
+}
+
+/// Assembles a program made up of `.code` and `.data` sections. Every error found
+/// is reported, ordered by where it appears in the source
+pub fn assemble_program(input: &str) -> Result<Program, Vec<Diagnostic>> {
+    assemble_with_constants(input, 0, vec![])
+}
 
+fn assemble_with_constants(
+    input: &str,
+    origin: u32,
+    mut constants: Vec<f64>,
+) -> Result<Program, Vec<Diagnostic>> {
+    let mut errors = vec![];
+    let (code, data) = split_sections(lex(input)?);
+    let symbols = collect_labels(&code, &data, origin)?;
+    let code = resolve_labels(code, &symbols, &mut errors);
+    let data = resolve_labels(data, &symbols, &mut errors);
+
+    let ((code, debug), data) = match both(
+        assemble_code(code, origin, &mut constants),
+        parse_data(data),
+    ) {
+        Ok(output) if errors.is_empty() => output,
+        result => {
+            errors.extend(result.err().unwrap_or_default());
+            errors.sort_by_key(|diagnostic| (diagnostic.span.line, diagnostic.span.column));
+            return Err(errors);
+        }
+    };
+    let symbols = symbols.into_symbols();
+
+    Ok(Program {
+        code,
+        data,
+        constants,
+        entry_point: symbols
+            .iter()
+            .find(|symbol| symbol.name == ENTRY_LABEL && symbol.section == Section::Code)
+            .map_or(0, |symbol| symbol.offset),
+        symbols,
+        debug,
+    })
+}
+
+/// Combines the results of two independent stages, keeping the errors from both
+fn both<A, B>(
+    a: Result<A, Vec<Diagnostic>>,
+    b: Result<B, Vec<Diagnostic>>,
+) -> Result<(A, B), Vec<Diagnostic>> {
+    match (a, b) {
+        (Ok(a), Ok(b)) => Ok((a, b)),
+        (Err(mut a), Err(b)) => {
+            a.extend(b);
+            Err(a)
+        }
+        (Err(e), _) | (_, Err(e)) => Err(e),
+    }
+}
+
+/// The encoded code section and the line info of each instruction in it
+type CodeSection = (Vec<[u8; 4]>, Vec<LineInfo>);
+
+/// Encodes the code section along with the source line of each instruction. Besides
+/// instructions, it may contain `.byte` directives whose operands make up whole
+/// instruction words
+fn assemble_code(
+    input: Vec<SpannedToken>,
+    origin: u32,
+    constants: &mut Vec<f64>,
+) -> Result<CodeSection, Vec<Diagnostic>> {
     let mut output = vec![];
+    let mut debug = vec![];
+    let mut errors = vec![];
+    let mut tokens = input.into_iter().peekable();
+
+    while tokens.peek().is_some() {
+        let mut instructions = vec![];
+        while let Some(token) =
+            tokens.next_if(|(token, _)| !matches!(token, Token::Directive(Directive::Byte)))
+        {
+            instructions.push(token);
+        }
 
-    for instruction in instructions {
-        match instruction {
-            instruction::Instruction::HLT => output.push([0, 0, 0, 0]),
-            instruction::Instruction::LOAD(reg, int) => {
-                output.push([1, reg, (int >> 8) as u8, int as u8]);
-            }
-            instruction::Instruction::ADD(reg1, reg2, reg3) => {
-                output.push([2, reg1, reg2, reg3]);
+        let offset = origin + output.len() as u32 * 4;
+        match parse(instructions, offset, constants) {
+            Ok(instructions) => {
+                for (instruction, span) in instructions {
+                    debug.push(LineInfo {
+                        offset: origin + output.len() as u32 * 4,
+                        line: span.line as u32,
+                    });
+                    output.push(<[u8; 4]>::from(instruction));
+                }
             }
-            instruction::Instruction::SUB(reg1, reg2, reg3) => {
-                output.push([3, reg1, reg2, reg3]);
-            }
-            instruction::Instruction::MUL(reg1, reg2, reg3) => {
-                output.push([4, reg1, reg2, reg3]);
-            }
-            instruction::Instruction::DIV(reg1, reg2, reg3) => {
-                output.push([5, reg1, reg2, reg3]);
-            }
-            instruction::Instruction::JMP(reg) => {
-                output.push([6, reg, 0, 0]);
-            }
-            instruction::Instruction::JMPF(reg) => {
-                output.push([7, reg, 0, 0]);
-            }
-            instruction::Instruction::JMPB(reg) => {
-                output.push([8, reg, 0, 0]);
-            }
-            instruction::Instruction::EQ(reg1, reg2) => {
-                output.push([9, reg1, reg2, 0]);
-            }
-            instruction::Instruction::NEQ(reg1, reg2) => {
-                output.push([10, reg1, reg2, 0]);
-            }
-            instruction::Instruction::GT(reg1, reg2) => {
-                output.push([11, reg1, reg2, 0]);
-            }
-            instruction::Instruction::LT(reg1, reg2) => {
-                output.push([12, reg1, reg2, 0]);
-            }
-            instruction::Instruction::GTQ(reg1, reg2) => {
-                output.push([13, reg1, reg2, 0]);
-            }
-            instruction::Instruction::LTQ(reg1, reg2) => {
-                output.push([14, reg1, reg2, 0]);
-            }
-            instruction::Instruction::JEQ(reg) => {
-                output.push([15, reg, 0, 0]);
+            Err(e) => errors.extend(e),
+        }
+
+        if let Some((_, directive_span)) = tokens.next() {
+            let mut bytes = vec![];
+            while let Some((Token::IntegerOperand(n), span)) =
+                tokens.next_if(|(token, _)| matches!(token, Token::IntegerOperand(_)))
+            {
+                match byte_operand(n) {
+                    Ok(byte) => bytes.push(byte),
+                    Err(error) => errors.push(Diagnostic::new(error, span)),
+                }
             }
-            instruction::Instruction::JNEQ(reg) => {
-                output.push([16, reg, 0, 0]);
+
+            if !bytes.len().is_multiple_of(4) {
+                errors.push(Diagnostic::new(
+                    ParseError::UnalignedBytesError(bytes.len()),
+                    directive_span,
+                ));
             }
+            output.extend(
+                bytes
+                    .chunks_exact(4)
+                    .map(|word| [word[0], word[1], word[2], word[3]]),
+            );
         }
     }
-    Ok(output)
+
+    if errors.is_empty() {
+        Ok((output, debug))
+    } else {
+        Err(errors)
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::opcode::Opcode;
 
     #[test]
     fn test_assemble_hlt() {
@@ -191,4 +314,598 @@ mod tests {
 
         assert_eq!(assemble("JNEQ $0"), Ok(expected_output));
     }
+
+    #[test]
+    fn test_assemble_aloc() {
+        let expected_output = vec![[17, 0, 0, 0]];
+
+        assert_eq!(assemble("ALOC $0"), Ok(expected_output));
+    }
+
+    #[test]
+    fn test_assemble_loadb() {
+        let expected_output = vec![[18, 0, 1, 0]];
+
+        assert_eq!(assemble("LOADB $0 $1"), Ok(expected_output));
+    }
+
+    #[test]
+    fn test_assemble_loadh() {
+        let expected_output = vec![[19, 0, 1, 0]];
+
+        assert_eq!(assemble("LOADH $0 $1"), Ok(expected_output));
+    }
+
+    #[test]
+    fn test_assemble_loadw() {
+        let expected_output = vec![[20, 0, 1, 0]];
+
+        assert_eq!(assemble("LOADW $0 $1"), Ok(expected_output));
+    }
+
+    #[test]
+    fn test_assemble_storeb() {
+        let expected_output = vec![[21, 0, 1, 0]];
+
+        assert_eq!(assemble("STOREB $0 $1"), Ok(expected_output));
+    }
+
+    #[test]
+    fn test_assemble_storeh() {
+        let expected_output = vec![[22, 0, 1, 0]];
+
+        assert_eq!(assemble("STOREH $0 $1"), Ok(expected_output));
+    }
+
+    #[test]
+    fn test_assemble_storew() {
+        let expected_output = vec![[23, 0, 1, 0]];
+
+        assert_eq!(assemble("STOREW $0 $1"), Ok(expected_output));
+    }
+
+    #[test]
+    fn test_assemble_push() {
+        let expected_output = vec![[24, 0, 0, 0]];
+
+        assert_eq!(assemble("PUSH $0"), Ok(expected_output));
+    }
+
+    #[test]
+    fn test_assemble_pop() {
+        let expected_output = vec![[25, 0, 0, 0]];
+
+        assert_eq!(assemble("POP $0"), Ok(expected_output));
+    }
+
+    #[test]
+    fn test_assemble_call() {
+        let expected_output = vec![[26, 0, 0, 0]];
+
+        assert_eq!(assemble("CALL $0"), Ok(expected_output));
+    }
+
+    #[test]
+    fn test_assemble_ret() {
+        let expected_output = vec![[27, 0, 0, 0]];
+
+        assert_eq!(assemble("RET"), Ok(expected_output));
+    }
+
+    #[test]
+    fn test_assemble_loadhi() {
+        let expected_output = vec![[28, 0, 0, 1]];
+
+        assert_eq!(assemble("LOADHI $0 #1"), Ok(expected_output));
+    }
+
+    #[test]
+    fn test_assemble_loadlo() {
+        let expected_output = vec![[29, 0, 255, 255]];
+
+        assert_eq!(assemble("LOADLO $0 #0xFFFF"), Ok(expected_output));
+    }
+
+    #[test]
+    fn test_assemble_wide_load() {
+        let expected_output = vec![[28, 0, 0, 1], [29, 0, 17, 112], [1, 1, 255, 255]];
+
+        assert_eq!(assemble("LOAD $0 #70000\nLOAD $1 #-1"), Ok(expected_output));
+    }
+
+    #[test]
+    fn test_assemble_and() {
+        let expected_output = vec![[30, 0, 1, 2]];
+
+        assert_eq!(assemble("AND $0 $1 $2"), Ok(expected_output));
+    }
+
+    #[test]
+    fn test_assemble_or() {
+        let expected_output = vec![[31, 0, 1, 2]];
+
+        assert_eq!(assemble("OR $0 $1 $2"), Ok(expected_output));
+    }
+
+    #[test]
+    fn test_assemble_xor() {
+        let expected_output = vec![[32, 0, 1, 2]];
+
+        assert_eq!(assemble("XOR $0 $1 $2"), Ok(expected_output));
+    }
+
+    #[test]
+    fn test_assemble_not() {
+        let expected_output = vec![[33, 0, 1, 0]];
+
+        assert_eq!(assemble("NOT $0 $1"), Ok(expected_output));
+    }
+
+    #[test]
+    fn test_assemble_shl() {
+        let expected_output = vec![[34, 0, 1, 2]];
+
+        assert_eq!(assemble("SHL $0 $1 $2"), Ok(expected_output));
+    }
+
+    #[test]
+    fn test_assemble_shr() {
+        let expected_output = vec![[35, 0, 1, 2]];
+
+        assert_eq!(assemble("SHR $0 $1 $2"), Ok(expected_output));
+    }
+
+    #[test]
+    fn test_assemble_sar() {
+        let expected_output = vec![[36, 0, 1, 2]];
+
+        assert_eq!(assemble("SAR $0 $1 $2"), Ok(expected_output));
+    }
+
+    #[test]
+    fn test_assemble_shli() {
+        let expected_output = vec![[37, 0, 3, 1]];
+
+        assert_eq!(assemble("SHLI $0 #3 $1"), Ok(expected_output));
+    }
+
+    #[test]
+    fn test_assemble_shri() {
+        let expected_output = vec![[38, 0, 3, 1]];
+
+        assert_eq!(assemble("SHRI $0 #3 $1"), Ok(expected_output));
+    }
+
+    #[test]
+    fn test_assemble_sari() {
+        let expected_output = vec![[39, 0, 31, 1]];
+
+        assert_eq!(assemble("SARI $0 #31 $1"), Ok(expected_output));
+    }
+
+    #[test]
+    fn test_assemble_jc() {
+        let expected_output = vec![[40, 0, 0, 0]];
+
+        assert_eq!(assemble("JC $0"), Ok(expected_output));
+    }
+
+    #[test]
+    fn test_assemble_jnc() {
+        let expected_output = vec![[41, 0, 0, 0]];
+
+        assert_eq!(assemble("JNC $0"), Ok(expected_output));
+    }
+
+    #[test]
+    fn test_assemble_jo() {
+        let expected_output = vec![[42, 0, 0, 0]];
+
+        assert_eq!(assemble("JO $0"), Ok(expected_output));
+    }
+
+    #[test]
+    fn test_assemble_jno() {
+        let expected_output = vec![[43, 0, 0, 0]];
+
+        assert_eq!(assemble("JNO $0"), Ok(expected_output));
+    }
+
+    #[test]
+    fn test_assemble_loadf() {
+        let expected_output = vec![[44, 1, 0, 0]];
+
+        assert_eq!(assemble("LOADF $f1 #2.5"), Ok(expected_output));
+    }
+
+    #[test]
+    fn test_assemble_addf() {
+        let expected_output = vec![[45, 0, 1, 2]];
+
+        assert_eq!(assemble("ADDF $f0 $f1 $f2"), Ok(expected_output));
+    }
+
+    #[test]
+    fn test_assemble_subf() {
+        let expected_output = vec![[46, 0, 1, 2]];
+
+        assert_eq!(assemble("SUBF $f0 $f1 $f2"), Ok(expected_output));
+    }
+
+    #[test]
+    fn test_assemble_mulf() {
+        let expected_output = vec![[47, 0, 1, 2]];
+
+        assert_eq!(assemble("MULF $f0 $f1 $f2"), Ok(expected_output));
+    }
+
+    #[test]
+    fn test_assemble_divf() {
+        let expected_output = vec![[48, 0, 1, 2]];
+
+        assert_eq!(assemble("DIVF $f0 $f1 $f2"), Ok(expected_output));
+    }
+
+    #[test]
+    fn test_assemble_eqf() {
+        let expected_output = vec![[49, 0, 1, 0]];
+
+        assert_eq!(assemble("EQF $f0 $f1"), Ok(expected_output));
+    }
+
+    #[test]
+    fn test_assemble_neqf() {
+        let expected_output = vec![[50, 0, 1, 0]];
+
+        assert_eq!(assemble("NEQF $f0 $f1"), Ok(expected_output));
+    }
+
+    #[test]
+    fn test_assemble_gtf() {
+        let expected_output = vec![[51, 0, 1, 0]];
+
+        assert_eq!(assemble("GTF $f0 $f1"), Ok(expected_output));
+    }
+
+    #[test]
+    fn test_assemble_ltf() {
+        let expected_output = vec![[52, 0, 1, 0]];
+
+        assert_eq!(assemble("LTF $f0 $f1"), Ok(expected_output));
+    }
+
+    #[test]
+    fn test_assemble_gtqf() {
+        let expected_output = vec![[53, 0, 1, 0]];
+
+        assert_eq!(assemble("GTQF $f0 $f1"), Ok(expected_output));
+    }
+
+    #[test]
+    fn test_assemble_ltqf() {
+        let expected_output = vec![[54, 0, 1, 0]];
+
+        assert_eq!(assemble("LTQF $f0 $f1"), Ok(expected_output));
+    }
+
+    #[test]
+    fn test_assemble_itof() {
+        let expected_output = vec![[55, 0, 1, 0]];
+
+        assert_eq!(assemble("ITOF $0 $f1"), Ok(expected_output));
+    }
+
+    #[test]
+    fn test_assemble_ftoi() {
+        let expected_output = vec![[56, 0, 1, 0]];
+
+        assert_eq!(assemble("FTOI $f0 $1"), Ok(expected_output));
+    }
+
+    #[test]
+    fn test_assemble_cmp() {
+        let expected_output = vec![[57, 0, 1, 0]];
+
+        assert_eq!(assemble("CMP $0 $1"), Ok(expected_output));
+    }
+
+    #[test]
+    fn test_assemble_jz() {
+        let expected_output = vec![[58, 0, 0, 0]];
+
+        assert_eq!(assemble("JZ $0"), Ok(expected_output));
+    }
+
+    #[test]
+    fn test_assemble_jnz() {
+        let expected_output = vec![[59, 0, 0, 0]];
+
+        assert_eq!(assemble("JNZ $0"), Ok(expected_output));
+    }
+
+    #[test]
+    fn test_assemble_jlt() {
+        let expected_output = vec![[60, 0, 0, 0]];
+
+        assert_eq!(assemble("JLT $0"), Ok(expected_output));
+    }
+
+    #[test]
+    fn test_assemble_jge() {
+        let expected_output = vec![[61, 0, 0, 0]];
+
+        assert_eq!(assemble("JGE $0"), Ok(expected_output));
+    }
+
+    #[test]
+    fn test_assemble_jgt() {
+        let expected_output = vec![[62, 0, 0, 0]];
+
+        assert_eq!(assemble("JGT $0"), Ok(expected_output));
+    }
+
+    #[test]
+    fn test_assemble_jle() {
+        let expected_output = vec![[63, 0, 0, 0]];
+
+        assert_eq!(assemble("JLE $0"), Ok(expected_output));
+    }
+
+    #[test]
+    fn test_assemble_constants() {
+        let program = assemble_program("LOADF $f0 #-1.5\nLOADF $f1 #2\nLOADF $f2 #-1.5").unwrap();
+
+        assert_eq!(
+            program.code,
+            vec![[44, 0, 0, 0], [44, 1, 0, 1], [44, 2, 0, 0]]
+        );
+        assert_eq!(program.constants, vec![-1.5, 2.0]);
+    }
+
+    #[test]
+    fn test_assemble_line() {
+        let mut constants = vec![1.0];
+
+        assert_eq!(
+            assemble_line("LOADF $f0 #3.0", 0, &mut constants),
+            Ok(vec![[44, 0, 0, 1]])
+        );
+        assert_eq!(constants, vec![1.0, 3.0]);
+    }
+
+    #[test]
+    fn test_assemble_register_out_of_range() {
+        assert_eq!(
+            assemble("LOAD $40 #1"),
+            Err(vec![Diagnostic::new(
+                ParseError::RegisterOutOfRangeError(40),
+                Span {
+                    line: 1,
+                    column: 6,
+                    len: 3
+                }
+            )])
+        );
+        assert_eq!(
+            assemble("ITOF $31 $f32"),
+            Err(vec![Diagnostic::new(
+                ParseError::RegisterOutOfRangeError(32),
+                Span {
+                    line: 1,
+                    column: 10,
+                    len: 4
+                }
+            )])
+        );
+    }
+
+    #[test]
+    fn test_assemble_shift_out_of_range() {
+        assert_eq!(
+            assemble("SHLI $0 #32 $1"),
+            Err(vec![Diagnostic::new(
+                ParseError::IntegerOutOfRangeError(32),
+                Span {
+                    line: 1,
+                    column: 9,
+                    len: 3
+                }
+            )])
+        );
+    }
+
+    #[test]
+    fn test_assemble_labels() {
+        let expected_output = vec![[1, 0, 0, 8], [6, 0, 0, 0], [0, 0, 0, 0]];
+
+        assert_eq!(
+            assemble("start: LOAD $0 @end\nJMP $0\nend: HLT"),
+            Ok(expected_output)
+        );
+    }
+
+    #[test]
+    fn test_assemble_jmpr() {
+        let expected_output = vec![[64, 255, 248, 0]];
+
+        assert_eq!(assemble("JMPR #-8"), Ok(expected_output));
+    }
+
+    #[test]
+    fn test_assemble_jmpi() {
+        let expected_output = vec![[65, 1, 0, 0]];
+
+        assert_eq!(assemble("JMPI #65536"), Ok(expected_output));
+    }
+
+    #[test]
+    fn test_assemble_syscall() {
+        let expected_output = vec![[90, 1, 2, 0]];
+
+        assert_eq!(assemble("SYSCALL #258"), Ok(expected_output));
+        assert!(assemble("SYSCALL #-1").is_err());
+    }
+
+    #[test]
+    fn test_assemble_jump_labels() {
+        let expected_output = vec![
+            [0, 0, 0, 0],
+            [72, 255, 252, 0],
+            [66, 0, 8, 0],
+            [0, 0, 0, 0],
+            [0, 0, 0, 0],
+        ];
+
+        assert_eq!(
+            assemble("loop: HLT\nJNZ @loop\nJEQ @end\nHLT\nend: HLT"),
+            Ok(expected_output)
+        );
+    }
+
+    #[test]
+    fn test_assemble_far_jump() {
+        let expected_output = vec![[0, 0, 0, 0], [65, 0, 0x9C, 0x40]];
+
+        assert_eq!(assemble("HLT\nJMP #40000"), Ok(expected_output));
+    }
+
+    #[test]
+    fn test_assemble_jump_out_of_range() {
+        assert_eq!(
+            assemble("JMPR #40000"),
+            Err(vec![Diagnostic::new(
+                ParseError::IntegerOutOfRangeError(40000),
+                Span {
+                    line: 1,
+                    column: 6,
+                    len: 6
+                }
+            )])
+        );
+    }
+
+    #[test]
+    fn test_assemble_line_jump() {
+        assert_eq!(
+            assemble_line("JMP #0", 8, &mut vec![]),
+            Ok(vec![[64, 255, 248, 0]])
+        );
+    }
+
+    #[test]
+    fn test_assemble_line_label() {
+        assert_eq!(
+            assemble_line("l: JMP @l", 8, &mut vec![]),
+            Ok(vec![[64, 0, 0, 0]])
+        );
+    }
+
+    #[test]
+    fn test_assemble_undefined_label() {
+        assert_eq!(
+            assemble("LOAD $0 @end"),
+            Err(vec![Diagnostic::new(
+                ParseError::UndefinedLabelError("end".to_owned()),
+                Span {
+                    line: 1,
+                    column: 9,
+                    len: 4
+                }
+            )])
+        );
+    }
+
+    #[test]
+    fn test_assemble_program() {
+        let input = r#"
+            .data
+            greeting: .asciiz "hi"
+            numbers: .word #1 #-1
+            .code
+            LOAD $0 @numbers
+            HLT
+        "#;
+        let expected_output = Program {
+            code: vec![[1, 0, 0, 3], [0, 0, 0, 0]],
+            data: vec![b'h', b'i', 0, 1, 0, 0, 0, 0xFF, 0xFF, 0xFF, 0xFF],
+            symbols: vec![
+                Symbol {
+                    name: "greeting".to_owned(),
+                    section: Section::Data,
+                    offset: 0,
+                },
+                Symbol {
+                    name: "numbers".to_owned(),
+                    section: Section::Data,
+                    offset: 3,
+                },
+            ],
+            debug: vec![
+                LineInfo { offset: 0, line: 6 },
+                LineInfo { offset: 4, line: 7 },
+            ],
+            ..Default::default()
+        };
+
+        assert_eq!(assemble_program(input), Ok(expected_output));
+    }
+
+    #[test]
+    fn test_assemble_multiple_errors() {
+        let errors: Vec<ParseError> = assemble("LOAD #1\nADD $0 $1\nLOAD $2 @nowhere\nHLT")
+            .unwrap_err()
+            .into_iter()
+            .map(|diagnostic| diagnostic.error)
+            .collect();
+
+        assert_eq!(
+            errors,
+            vec![
+                ParseError::InvalidOperandsError(Opcode::LOAD),
+                ParseError::InvalidOperandsError(Opcode::ADD),
+                ParseError::UndefinedLabelError("nowhere".to_owned()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_assemble_comments() {
+        let input = "
+            ; Count down from 5
+            LOAD $0 #0x5 // hex
+            LOAD $1 #-1
+            HLT
+        ";
+        let expected_output = vec![[1, 0, 0, 5], [1, 1, 255, 255], [0, 0, 0, 0]];
+
+        assert_eq!(assemble(input), Ok(expected_output));
+    }
+
+    #[test]
+    fn test_assemble_entry_point() {
+        let program = assemble_program("HLT\nmain: HLT").unwrap();
+
+        assert_eq!(program.entry_point, 4);
+    }
+
+    #[test]
+    fn test_assemble_code_bytes() {
+        let expected_output = vec![[0, 0, 0, 0], [255, 1, 2, 3], [27, 0, 0, 0]];
+
+        assert_eq!(
+            assemble("HLT\n.byte #255 #1 #2 #3\nRET"),
+            Ok(expected_output)
+        );
+        assert_eq!(
+            assemble(".byte #1 #2"),
+            Err(vec![Diagnostic::new(
+                ParseError::UnalignedBytesError(2),
+                Span {
+                    line: 1,
+                    column: 1,
+                    len: 5
+                }
+            )])
+        );
+    }
 }
@@ -1,126 +1,569 @@
-use crate::assembler::instruction::Instruction;
+use crate::{assembler::instruction::Instruction, opcode::Opcode};
 
-use super::token::{ParseError, Token};
+use super::{
+    diagnostic::{Diagnostic, Span},
+    token::{ParseError, SpannedToken, Token},
+};
 
-pub fn parse(input: Vec<Token>) -> Result<Vec<Instruction>, ParseError> {
+/// Parses the tokens into instructions, each with the span of its opcode. An
+/// instruction's operands must be on the same line as its opcode, and the line must
+/// end after them, so there is at most one instruction per line. After an invalid
+/// instruction, parsing resumes at the next opcode
+///
+/// `origin` is the offset of the first instruction, from which jumps to an immediate
+/// address work out their relative offset. Float constants used by `LOADF` are added
+/// to `constants`, reusing any entry that already holds the same value
+pub fn parse(
+    input: Vec<SpannedToken>,
+    origin: u32,
+    constants: &mut Vec<f64>,
+) -> Result<Vec<(Instruction, Span)>, Vec<Diagnostic>> {
     use crate::assembler::token::Token as T;
     use crate::opcode::Opcode as O;
     let mut pos = 0;
     let len = input.len();
 
     let mut output = vec![];
+    let mut errors = vec![];
 
     while pos < len {
-        match &input[pos] {
-            Token::Op(opcode) => match (
-                opcode,
-                input.get(pos + 1),
-                input.get(pos + 2),
-                input.get(pos + 3),
-            ) {
-                (O::HLT, _, _, _) => {
-                    output.push(Instruction::HLT);
-                    pos += 1;
-                }
-                (O::LOAD, Some(T::Register(reg)), Some(T::IntegerOperand(int)), _) => {
-                    output.push(Instruction::LOAD(*reg, *int));
-                    pos += 3;
-                }
-                (
-                    O::ADD,
-                    Some(T::Register(reg1)),
-                    Some(T::Register(reg2)),
-                    Some(T::Register(reg3)),
-                ) => {
-                    output.push(Instruction::ADD(*reg1, *reg2, *reg3));
-                    pos += 4;
-                }
-                (
-                    O::SUB,
-                    Some(T::Register(reg1)),
-                    Some(T::Register(reg2)),
-                    Some(T::Register(reg3)),
-                ) => {
-                    output.push(Instruction::SUB(*reg1, *reg2, *reg3));
-                    pos += 4;
-                }
-                (
-                    O::MUL,
-                    Some(T::Register(reg1)),
-                    Some(T::Register(reg2)),
-                    Some(T::Register(reg3)),
-                ) => {
-                    output.push(Instruction::MUL(*reg1, *reg2, *reg3));
-                    pos += 4;
-                }
-                (
-                    O::DIV,
-                    Some(T::Register(reg1)),
-                    Some(T::Register(reg2)),
-                    Some(T::Register(reg3)),
-                ) => {
-                    output.push(Instruction::DIV(*reg1, *reg2, *reg3));
-                    pos += 4;
-                }
-                (O::JMP, Some(T::Register(reg)), _, _) => {
-                    output.push(Instruction::JMP(*reg));
-                    pos += 2;
-                }
-                (O::JMPF, Some(T::Register(reg)), _, _) => {
-                    output.push(Instruction::JMPF(*reg));
-                    pos += 2;
-                }
-                (O::JMPB, Some(T::Register(reg)), _, _) => {
-                    output.push(Instruction::JMPB(*reg));
-                    pos += 2;
-                }
-                (O::EQ, Some(T::Register(reg1)), Some(T::Register(reg2)), _) => {
-                    output.push(Instruction::EQ(*reg1, *reg2));
-                    pos += 3;
-                }
-                (O::NEQ, Some(T::Register(reg1)), Some(T::Register(reg2)), _) => {
-                    output.push(Instruction::NEQ(*reg1, *reg2));
-                    pos += 3;
-                }
-                (O::GT, Some(T::Register(reg1)), Some(T::Register(reg2)), _) => {
-                    output.push(Instruction::GT(*reg1, *reg2));
-                    pos += 3;
-                }
-                (O::LT, Some(T::Register(reg1)), Some(T::Register(reg2)), _) => {
-                    output.push(Instruction::LT(*reg1, *reg2));
-                    pos += 3;
-                }
-                (O::GTQ, Some(T::Register(reg1)), Some(T::Register(reg2)), _) => {
-                    output.push(Instruction::GTQ(*reg1, *reg2));
-                    pos += 3;
-                }
-                (O::LTQ, Some(T::Register(reg1)), Some(T::Register(reg2)), _) => {
-                    output.push(Instruction::LTQ(*reg1, *reg2));
-                    pos += 3;
-                }
-                (O::JEQ, Some(T::Register(reg)), _, _) => {
-                    output.push(Instruction::JEQ(*reg));
-                    pos += 2;
-                }
-                (O::JNEQ, Some(T::Register(reg)), _, _) => {
-                    output.push(Instruction::JNEQ(*reg));
-                    pos += 2;
-                }
-                _ => {
-                    return Err(ParseError::InvalidOpcodeError(
-                        "sequence of opcodes could not be parsed to instruction".to_owned(),
-                    ))
+        let (first, span) = &input[pos];
+        let parsed = output.len();
+        let operand = |n: usize| {
+            input
+                .get(n)
+                .filter(|(_, operand_span)| operand_span.line == span.line)
+                .map(|(token, _)| token)
+        };
+
+        match first {
+            Token::Op(opcode) => {
+                match (opcode, operand(pos + 1), operand(pos + 2), operand(pos + 3)) {
+                    (O::HLT, _, _, _) => {
+                        output.push((Instruction::HLT, *span));
+                        pos += 1;
+                    }
+                    (O::LOAD, Some(T::Register(reg)), Some(T::IntegerOperand(int)), _) => {
+                        for instruction in Instruction::load_immediate(*reg, *int) {
+                            output.push((instruction, *span));
+                        }
+                        pos += 3;
+                    }
+                    (
+                        O::LOADHI | O::LOADLO,
+                        Some(T::Register(reg)),
+                        Some(T::IntegerOperand(int)),
+                        _,
+                    ) => {
+                        // Each half is 16 bits, written either signed or unsigned
+                        if (i16::MIN as i32..=u16::MAX as i32).contains(int) {
+                            let instruction = match opcode {
+                                O::LOADHI => Instruction::LOADHI(*reg, *int as u16),
+                                _ => Instruction::LOADLO(*reg, *int as u16),
+                            };
+                            output.push((instruction, *span));
+                        } else {
+                            errors.push(Diagnostic::new(
+                                ParseError::IntegerOutOfRangeError(*int),
+                                input[pos + 2].1,
+                            ));
+                        }
+                        pos += 3;
+                    }
+                    (
+                        O::ADD,
+                        Some(T::Register(reg1)),
+                        Some(T::Register(reg2)),
+                        Some(T::Register(reg3)),
+                    ) => {
+                        output.push((Instruction::ADD(*reg1, *reg2, *reg3), *span));
+                        pos += 4;
+                    }
+                    (
+                        O::SUB,
+                        Some(T::Register(reg1)),
+                        Some(T::Register(reg2)),
+                        Some(T::Register(reg3)),
+                    ) => {
+                        output.push((Instruction::SUB(*reg1, *reg2, *reg3), *span));
+                        pos += 4;
+                    }
+                    (
+                        O::MUL,
+                        Some(T::Register(reg1)),
+                        Some(T::Register(reg2)),
+                        Some(T::Register(reg3)),
+                    ) => {
+                        output.push((Instruction::MUL(*reg1, *reg2, *reg3), *span));
+                        pos += 4;
+                    }
+                    (
+                        O::DIV,
+                        Some(T::Register(reg1)),
+                        Some(T::Register(reg2)),
+                        Some(T::Register(reg3)),
+                    ) => {
+                        output.push((Instruction::DIV(*reg1, *reg2, *reg3), *span));
+                        pos += 4;
+                    }
+                    (_, Some(T::IntegerOperand(int)), _, _) if opcode.jump().is_some() => {
+                        let offset = origin + output.len() as u32 * 4;
+                        match Instruction::jump_immediate(*opcode, offset, *int) {
+                            Some(instruction) => output.push((instruction, *span)),
+                            None => errors.push(Diagnostic::new(
+                                ParseError::IntegerOutOfRangeError(*int),
+                                input[pos + 1].1,
+                            )),
+                        }
+                        pos += 2;
+                    }
+                    (O::SYSCALL, Some(T::IntegerOperand(int)), _, _) => {
+                        match u16::try_from(*int) {
+                            Ok(number) => output.push((Instruction::SYSCALL(number), *span)),
+                            Err(_) => errors.push(Diagnostic::new(
+                                ParseError::IntegerOutOfRangeError(*int),
+                                input[pos + 1].1,
+                            )),
+                        }
+                        pos += 2;
+                    }
+                    (O::JMP, Some(T::Register(reg)), _, _) => {
+                        output.push((Instruction::JMP(*reg), *span));
+                        pos += 2;
+                    }
+                    (O::JMPF, Some(T::Register(reg)), _, _) => {
+                        output.push((Instruction::JMPF(*reg), *span));
+                        pos += 2;
+                    }
+                    (O::JMPB, Some(T::Register(reg)), _, _) => {
+                        output.push((Instruction::JMPB(*reg), *span));
+                        pos += 2;
+                    }
+                    (O::CMP, Some(T::Register(reg1)), Some(T::Register(reg2)), _) => {
+                        output.push((Instruction::CMP(*reg1, *reg2), *span));
+                        pos += 3;
+                    }
+                    (O::JZ, Some(T::Register(reg)), _, _) => {
+                        output.push((Instruction::JZ(*reg), *span));
+                        pos += 2;
+                    }
+                    (O::JNZ, Some(T::Register(reg)), _, _) => {
+                        output.push((Instruction::JNZ(*reg), *span));
+                        pos += 2;
+                    }
+                    (O::JLT, Some(T::Register(reg)), _, _) => {
+                        output.push((Instruction::JLT(*reg), *span));
+                        pos += 2;
+                    }
+                    (O::JGE, Some(T::Register(reg)), _, _) => {
+                        output.push((Instruction::JGE(*reg), *span));
+                        pos += 2;
+                    }
+                    (O::JGT, Some(T::Register(reg)), _, _) => {
+                        output.push((Instruction::JGT(*reg), *span));
+                        pos += 2;
+                    }
+                    (O::JLE, Some(T::Register(reg)), _, _) => {
+                        output.push((Instruction::JLE(*reg), *span));
+                        pos += 2;
+                    }
+                    (O::EQ, Some(T::Register(reg1)), Some(T::Register(reg2)), _) => {
+                        output.push((Instruction::EQ(*reg1, *reg2), *span));
+                        pos += 3;
+                    }
+                    (O::NEQ, Some(T::Register(reg1)), Some(T::Register(reg2)), _) => {
+                        output.push((Instruction::NEQ(*reg1, *reg2), *span));
+                        pos += 3;
+                    }
+                    (O::GT, Some(T::Register(reg1)), Some(T::Register(reg2)), _) => {
+                        output.push((Instruction::GT(*reg1, *reg2), *span));
+                        pos += 3;
+                    }
+                    (O::LT, Some(T::Register(reg1)), Some(T::Register(reg2)), _) => {
+                        output.push((Instruction::LT(*reg1, *reg2), *span));
+                        pos += 3;
+                    }
+                    (O::GTQ, Some(T::Register(reg1)), Some(T::Register(reg2)), _) => {
+                        output.push((Instruction::GTQ(*reg1, *reg2), *span));
+                        pos += 3;
+                    }
+                    (O::LTQ, Some(T::Register(reg1)), Some(T::Register(reg2)), _) => {
+                        output.push((Instruction::LTQ(*reg1, *reg2), *span));
+                        pos += 3;
+                    }
+                    (O::JEQ, Some(T::Register(reg)), _, _) => {
+                        output.push((Instruction::JEQ(*reg), *span));
+                        pos += 2;
+                    }
+                    (O::JNEQ, Some(T::Register(reg)), _, _) => {
+                        output.push((Instruction::JNEQ(*reg), *span));
+                        pos += 2;
+                    }
+                    (O::JC, Some(T::Register(reg)), _, _) => {
+                        output.push((Instruction::JC(*reg), *span));
+                        pos += 2;
+                    }
+                    (O::JNC, Some(T::Register(reg)), _, _) => {
+                        output.push((Instruction::JNC(*reg), *span));
+                        pos += 2;
+                    }
+                    (O::JO, Some(T::Register(reg)), _, _) => {
+                        output.push((Instruction::JO(*reg), *span));
+                        pos += 2;
+                    }
+                    (O::JNO, Some(T::Register(reg)), _, _) => {
+                        output.push((Instruction::JNO(*reg), *span));
+                        pos += 2;
+                    }
+                    (O::ALOC, Some(T::Register(reg)), _, _) => {
+                        output.push((Instruction::ALOC(*reg), *span));
+                        pos += 2;
+                    }
+                    (O::LOADB, Some(T::Register(reg1)), Some(T::Register(reg2)), _) => {
+                        output.push((Instruction::LOADB(*reg1, *reg2), *span));
+                        pos += 3;
+                    }
+                    (O::LOADH, Some(T::Register(reg1)), Some(T::Register(reg2)), _) => {
+                        output.push((Instruction::LOADH(*reg1, *reg2), *span));
+                        pos += 3;
+                    }
+                    (O::LOADW, Some(T::Register(reg1)), Some(T::Register(reg2)), _) => {
+                        output.push((Instruction::LOADW(*reg1, *reg2), *span));
+                        pos += 3;
+                    }
+                    (O::STOREB, Some(T::Register(reg1)), Some(T::Register(reg2)), _) => {
+                        output.push((Instruction::STOREB(*reg1, *reg2), *span));
+                        pos += 3;
+                    }
+                    (O::STOREH, Some(T::Register(reg1)), Some(T::Register(reg2)), _) => {
+                        output.push((Instruction::STOREH(*reg1, *reg2), *span));
+                        pos += 3;
+                    }
+                    (O::STOREW, Some(T::Register(reg1)), Some(T::Register(reg2)), _) => {
+                        output.push((Instruction::STOREW(*reg1, *reg2), *span));
+                        pos += 3;
+                    }
+                    (O::PUSH, Some(T::Register(reg)), _, _) => {
+                        output.push((Instruction::PUSH(*reg), *span));
+                        pos += 2;
+                    }
+                    (O::POP, Some(T::Register(reg)), _, _) => {
+                        output.push((Instruction::POP(*reg), *span));
+                        pos += 2;
+                    }
+                    (O::CALL, Some(T::Register(reg)), _, _) => {
+                        output.push((Instruction::CALL(*reg), *span));
+                        pos += 2;
+                    }
+                    (O::RET, _, _, _) => {
+                        output.push((Instruction::RET, *span));
+                        pos += 1;
+                    }
+                    (
+                        O::LOADF,
+                        Some(T::FloatRegister(reg)),
+                        Some(T::FloatOperand(_) | T::IntegerOperand(_)),
+                        _,
+                    ) => {
+                        let value = match &input[pos + 2].0 {
+                            T::IntegerOperand(int) => *int as f64,
+                            T::FloatOperand(float) => *float,
+                            _ => unreachable!(),
+                        };
+                        match constant_index(constants, value) {
+                            Some(index) => output.push((Instruction::LOADF(*reg, index), *span)),
+                            None => errors.push(Diagnostic::new(
+                                ParseError::ConstantPoolFullError,
+                                input[pos + 2].1,
+                            )),
+                        }
+                        pos += 3;
+                    }
+                    (
+                        O::ADDF,
+                        Some(T::FloatRegister(reg1)),
+                        Some(T::FloatRegister(reg2)),
+                        Some(T::FloatRegister(reg3)),
+                    ) => {
+                        output.push((Instruction::ADDF(*reg1, *reg2, *reg3), *span));
+                        pos += 4;
+                    }
+                    (
+                        O::SUBF,
+                        Some(T::FloatRegister(reg1)),
+                        Some(T::FloatRegister(reg2)),
+                        Some(T::FloatRegister(reg3)),
+                    ) => {
+                        output.push((Instruction::SUBF(*reg1, *reg2, *reg3), *span));
+                        pos += 4;
+                    }
+                    (
+                        O::MULF,
+                        Some(T::FloatRegister(reg1)),
+                        Some(T::FloatRegister(reg2)),
+                        Some(T::FloatRegister(reg3)),
+                    ) => {
+                        output.push((Instruction::MULF(*reg1, *reg2, *reg3), *span));
+                        pos += 4;
+                    }
+                    (
+                        O::DIVF,
+                        Some(T::FloatRegister(reg1)),
+                        Some(T::FloatRegister(reg2)),
+                        Some(T::FloatRegister(reg3)),
+                    ) => {
+                        output.push((Instruction::DIVF(*reg1, *reg2, *reg3), *span));
+                        pos += 4;
+                    }
+                    (O::EQF, Some(T::FloatRegister(reg1)), Some(T::FloatRegister(reg2)), _) => {
+                        output.push((Instruction::EQF(*reg1, *reg2), *span));
+                        pos += 3;
+                    }
+                    (O::NEQF, Some(T::FloatRegister(reg1)), Some(T::FloatRegister(reg2)), _) => {
+                        output.push((Instruction::NEQF(*reg1, *reg2), *span));
+                        pos += 3;
+                    }
+                    (O::GTF, Some(T::FloatRegister(reg1)), Some(T::FloatRegister(reg2)), _) => {
+                        output.push((Instruction::GTF(*reg1, *reg2), *span));
+                        pos += 3;
+                    }
+                    (O::LTF, Some(T::FloatRegister(reg1)), Some(T::FloatRegister(reg2)), _) => {
+                        output.push((Instruction::LTF(*reg1, *reg2), *span));
+                        pos += 3;
+                    }
+                    (O::GTQF, Some(T::FloatRegister(reg1)), Some(T::FloatRegister(reg2)), _) => {
+                        output.push((Instruction::GTQF(*reg1, *reg2), *span));
+                        pos += 3;
+                    }
+                    (O::LTQF, Some(T::FloatRegister(reg1)), Some(T::FloatRegister(reg2)), _) => {
+                        output.push((Instruction::LTQF(*reg1, *reg2), *span));
+                        pos += 3;
+                    }
+                    (O::ITOF, Some(T::Register(reg1)), Some(T::FloatRegister(reg2)), _) => {
+                        output.push((Instruction::ITOF(*reg1, *reg2), *span));
+                        pos += 3;
+                    }
+                    (O::FTOI, Some(T::FloatRegister(reg1)), Some(T::Register(reg2)), _) => {
+                        output.push((Instruction::FTOI(*reg1, *reg2), *span));
+                        pos += 3;
+                    }
+                    (
+                        O::AND,
+                        Some(T::Register(reg1)),
+                        Some(T::Register(reg2)),
+                        Some(T::Register(reg3)),
+                    ) => {
+                        output.push((Instruction::AND(*reg1, *reg2, *reg3), *span));
+                        pos += 4;
+                    }
+                    (
+                        O::OR,
+                        Some(T::Register(reg1)),
+                        Some(T::Register(reg2)),
+                        Some(T::Register(reg3)),
+                    ) => {
+                        output.push((Instruction::OR(*reg1, *reg2, *reg3), *span));
+                        pos += 4;
+                    }
+                    (
+                        O::XOR,
+                        Some(T::Register(reg1)),
+                        Some(T::Register(reg2)),
+                        Some(T::Register(reg3)),
+                    ) => {
+                        output.push((Instruction::XOR(*reg1, *reg2, *reg3), *span));
+                        pos += 4;
+                    }
+                    (O::NOT, Some(T::Register(reg1)), Some(T::Register(reg2)), _) => {
+                        output.push((Instruction::NOT(*reg1, *reg2), *span));
+                        pos += 3;
+                    }
+                    (
+                        O::SHL,
+                        Some(T::Register(reg1)),
+                        Some(T::Register(reg2)),
+                        Some(T::Register(reg3)),
+                    ) => {
+                        output.push((Instruction::SHL(*reg1, *reg2, *reg3), *span));
+                        pos += 4;
+                    }
+                    (
+                        O::SHR,
+                        Some(T::Register(reg1)),
+                        Some(T::Register(reg2)),
+                        Some(T::Register(reg3)),
+                    ) => {
+                        output.push((Instruction::SHR(*reg1, *reg2, *reg3), *span));
+                        pos += 4;
+                    }
+                    (
+                        O::SAR,
+                        Some(T::Register(reg1)),
+                        Some(T::Register(reg2)),
+                        Some(T::Register(reg3)),
+                    ) => {
+                        output.push((Instruction::SAR(*reg1, *reg2, *reg3), *span));
+                        pos += 4;
+                    }
+                    (
+                        O::SHLI | O::SHRI | O::SARI,
+                        Some(T::Register(reg1)),
+                        Some(T::IntegerOperand(amount)),
+                        Some(T::Register(reg2)),
+                    ) => {
+                        // Shifting an i32 by 32 or more bits is meaningless
+                        if (0..32).contains(amount) {
+                            let amount = *amount as u8;
+                            let instruction = match opcode {
+                                O::SHLI => Instruction::SHLI(*reg1, amount, *reg2),
+                                O::SHRI => Instruction::SHRI(*reg1, amount, *reg2),
+                                _ => Instruction::SARI(*reg1, amount, *reg2),
+                            };
+                            output.push((instruction, *span));
+                        } else {
+                            errors.push(Diagnostic::new(
+                                ParseError::IntegerOutOfRangeError(*amount),
+                                input[pos + 2].1,
+                            ));
+                        }
+                        pos += 4;
+                    }
+                    _ => {
+                        errors.push(Diagnostic::new(
+                            ParseError::InvalidOperandsError(*opcode),
+                            *span,
+                        ));
+                        pos = next_opcode(&input, pos + 1);
+                    }
                 }
-            },
+            }
             _ => {
-                return Err(ParseError::InvalidOpcodeError(
-                    "instruction must start with an opcode".to_owned(),
-                ))
+                errors.push(Diagnostic::new(
+                    ParseError::InvalidOpcodeError(
+                        "instruction must start with an opcode".to_owned(),
+                    ),
+                    *span,
+                ));
+                pos = next_opcode(&input, pos + 1);
+            }
+        }
+
+        if output.len() > parsed {
+            if let Some((_, next)) = input.get(pos).filter(|(_, next)| next.line == span.line) {
+                errors.push(Diagnostic::new(ParseError::TrailingTokenError, *next));
+                pos = next_line(&input, pos);
             }
         }
     }
 
-    return Ok(output);
+    if errors.is_empty() {
+        Ok(output)
+    } else {
+        Err(errors)
+    }
+}
+
+/// Finds or adds `value` in the constant pool, returning its index
+fn constant_index(constants: &mut Vec<f64>, value: f64) -> Option<u16> {
+    let index = constants
+        .iter()
+        .position(|constant| constant.to_bits() == value.to_bits())
+        .unwrap_or_else(|| {
+            constants.push(value);
+            constants.len() - 1
+        });
+
+    u16::try_from(index).ok()
+}
+
+/// Finds the position of the next opcode at or after `pos`
+fn next_opcode(input: &[SpannedToken], pos: usize) -> usize {
+    input[pos..]
+        .iter()
+        .position(|(token, _)| matches!(token, Token::Op(_)))
+        .map_or(input.len(), |n| pos + n)
+}
+
+/// Finds the position of the first token after the line that `pos` is on
+fn next_line(input: &[SpannedToken], pos: usize) -> usize {
+    let line = input[pos].1.line;
+    input[pos..]
+        .iter()
+        .position(|(_, span)| span.line != line)
+        .map_or(input.len(), |n| pos + n)
+}
+
+/// Describes the operands an opcode takes, for error messages
+pub fn expected_operands(opcode: Opcode) -> &'static str {
+    use crate::opcode::Opcode as O;
+    match opcode {
+        O::HLT | O::RET => "with no operands",
+        O::LOAD | O::LOADHI | O::LOADLO => "$register #integer",
+        O::ADD | O::SUB | O::MUL | O::DIV | O::AND | O::OR | O::XOR | O::SHL | O::SHR | O::SAR => {
+            "$register $register $register"
+        }
+        O::SHLI | O::SHRI | O::SARI => "$register #integer $register",
+        O::JMP
+        | O::JEQ
+        | O::JNEQ
+        | O::JZ
+        | O::JNZ
+        | O::JLT
+        | O::JGE
+        | O::JGT
+        | O::JLE
+        | O::JC
+        | O::JNC
+        | O::JO
+        | O::JNO => "$register, #address or @label",
+        O::JMPR
+        | O::JEQR
+        | O::JNEQR
+        | O::JZR
+        | O::JNZR
+        | O::JLTR
+        | O::JGER
+        | O::JGTR
+        | O::JLER
+        | O::JCR
+        | O::JNCR
+        | O::JOR
+        | O::JNOR => "#offset",
+        O::JMPI
+        | O::JEQI
+        | O::JNEQI
+        | O::JZI
+        | O::JNZI
+        | O::JLTI
+        | O::JGEI
+        | O::JGTI
+        | O::JLEI
+        | O::JCI
+        | O::JNCI
+        | O::JOI
+        | O::JNOI => "#address",
+        O::SYSCALL => "#number",
+        O::JMPF | O::JMPB | O::ALOC | O::PUSH | O::POP | O::CALL => "$register",
+        O::EQ
+        | O::NEQ
+        | O::GT
+        | O::LT
+        | O::GTQ
+        | O::LTQ
+        | O::CMP
+        | O::LOADB
+        | O::LOADH
+        | O::LOADW
+        | O::STOREB
+        | O::STOREH
+        | O::STOREW
+        | O::NOT => "$register $register",
+        O::LOADF => "$float #number",
+        O::ADDF | O::SUBF | O::MULF | O::DIVF => "$float $float $float",
+        O::EQF | O::NEQF | O::GTF | O::LTF | O::GTQF | O::LTQF => "$float $float",
+        O::ITOF => "$register $float",
+        O::FTOI => "$float $register",
+    }
 }
 
 #[cfg(test)]
@@ -131,37 +574,198 @@ mod tests {
         opcode::Opcode,
     };
 
+    fn spanned(tokens: Vec<Token>) -> Vec<SpannedToken> {
+        tokens
+            .into_iter()
+            .map(|token| (token, Span::default()))
+            .collect()
+    }
+
+    fn errors(result: Result<Vec<(Instruction, Span)>, Vec<Diagnostic>>) -> Vec<ParseError> {
+        result
+            .unwrap_err()
+            .into_iter()
+            .map(|diagnostic| diagnostic.error)
+            .collect()
+    }
+
     #[test]
     fn test_parse() {
-        let input = vec![
+        let input = spanned(vec![
             Token::Op(Opcode::LOAD),
             Token::Register(0),
             Token::IntegerOperand(500),
-        ];
-        let expected_output = vec![Instruction::LOAD(0, 500)];
+        ]);
+        let expected_output = vec![(Instruction::LOAD(0, 500), Span::default())];
 
-        assert_eq!(parse(input), Ok(expected_output))
+        assert_eq!(parse(input, 0, &mut vec![]), Ok(expected_output))
     }
     #[test]
     fn test_parse_failure_1() {
-        let input = vec![Token::Register(0), Token::IntegerOperand(500)];
+        let input = spanned(vec![Token::Register(0), Token::IntegerOperand(500)]);
 
         assert_eq!(
-            parse(input),
-            Err(ParseError::InvalidOpcodeError(
+            errors(parse(input, 0, &mut vec![])),
+            vec![ParseError::InvalidOpcodeError(
                 "instruction must start with an opcode".to_owned(),
-            ))
+            )]
         )
     }
     #[test]
     fn test_parse_failure_2() {
-        let input = vec![Token::Op(Opcode::LOAD), Token::IntegerOperand(500)];
+        let input = spanned(vec![Token::Op(Opcode::LOAD), Token::IntegerOperand(500)]);
+
+        assert_eq!(
+            errors(parse(input, 0, &mut vec![])),
+            vec![ParseError::InvalidOperandsError(Opcode::LOAD)]
+        )
+    }
+    /// Puts each group of tokens on its own line
+    fn lines(lines: Vec<Vec<Token>>) -> Vec<SpannedToken> {
+        lines
+            .into_iter()
+            .enumerate()
+            .flat_map(|(n, tokens)| {
+                tokens.into_iter().map(move |token| {
+                    (
+                        token,
+                        Span {
+                            line: n + 1,
+                            ..Default::default()
+                        },
+                    )
+                })
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_recovers() {
+        let input = lines(vec![
+            vec![Token::Op(Opcode::ADD), Token::Register(0)],
+            vec![Token::Op(Opcode::HLT)],
+            vec![Token::Op(Opcode::PUSH), Token::IntegerOperand(1)],
+        ]);
+
+        assert_eq!(
+            errors(parse(input, 0, &mut vec![])),
+            vec![
+                ParseError::InvalidOperandsError(Opcode::ADD),
+                ParseError::InvalidOperandsError(Opcode::PUSH)
+            ]
+        )
+    }
+
+    #[test]
+    fn test_parse_operands_on_next_line() {
+        let input = lines(vec![
+            vec![Token::Op(Opcode::LOAD), Token::Register(0)],
+            vec![Token::IntegerOperand(5)],
+        ]);
+
+        assert_eq!(
+            errors(parse(input, 0, &mut vec![])),
+            vec![ParseError::InvalidOperandsError(Opcode::LOAD)]
+        )
+    }
+    #[test]
+    fn test_parse_trailing_tokens() {
+        let input = lines(vec![
+            vec![
+                Token::Op(Opcode::HLT),
+                Token::Register(0),
+                Token::Register(1),
+            ],
+            vec![Token::Op(Opcode::HLT)],
+        ]);
 
         assert_eq!(
-            parse(input),
-            Err(ParseError::InvalidOpcodeError(
-                "sequence of opcodes could not be parsed to instruction".to_owned(),
-            ))
+            errors(parse(input, 0, &mut vec![])),
+            vec![ParseError::TrailingTokenError]
         )
     }
+    #[test]
+    fn test_parse_two_instructions_on_one_line() {
+        let input = lines(vec![vec![Token::Op(Opcode::HLT), Token::Op(Opcode::HLT)]]);
+
+        assert_eq!(
+            errors(parse(input, 0, &mut vec![])),
+            vec![ParseError::TrailingTokenError]
+        )
+    }
+    #[test]
+    fn test_parse_half_out_of_range() {
+        let input = lines(vec![
+            vec![
+                Token::Op(Opcode::LOADHI),
+                Token::Register(0),
+                Token::IntegerOperand(-32768),
+            ],
+            vec![
+                Token::Op(Opcode::LOADHI),
+                Token::Register(0),
+                Token::IntegerOperand(65535),
+            ],
+            vec![
+                Token::Op(Opcode::LOADLO),
+                Token::Register(0),
+                Token::IntegerOperand(65536),
+            ],
+        ]);
+
+        assert_eq!(
+            errors(parse(input, 0, &mut vec![])),
+            vec![ParseError::IntegerOutOfRangeError(65536)]
+        )
+    }
+    #[test]
+    fn test_parse_wide_load() {
+        let input = spanned(vec![
+            Token::Op(Opcode::LOAD),
+            Token::Register(0),
+            Token::IntegerOperand(70000),
+        ]);
+        let expected_output = vec![
+            (Instruction::LOADHI(0, 1), Span::default()),
+            (Instruction::LOADLO(0, 4464), Span::default()),
+        ];
+
+        assert_eq!(parse(input, 0, &mut vec![]), Ok(expected_output))
+    }
+    #[test]
+    fn test_parse_constants() {
+        let input = lines(vec![
+            vec![
+                Token::Op(Opcode::LOADF),
+                Token::FloatRegister(0),
+                Token::FloatOperand(1.5),
+            ],
+            vec![
+                Token::Op(Opcode::LOADF),
+                Token::FloatRegister(1),
+                Token::IntegerOperand(2),
+            ],
+            vec![
+                Token::Op(Opcode::LOADF),
+                Token::FloatRegister(2),
+                Token::FloatOperand(1.5),
+            ],
+        ]);
+        let mut constants = vec![];
+        let instructions: Vec<Instruction> = parse(input, 0, &mut constants)
+            .unwrap()
+            .into_iter()
+            .map(|(instruction, _)| instruction)
+            .collect();
+
+        assert_eq!(
+            instructions,
+            vec![
+                Instruction::LOADF(0, 0),
+                Instruction::LOADF(1, 1),
+                Instruction::LOADF(2, 0)
+            ]
+        );
+        assert_eq!(constants, vec![1.5, 2.0]);
+    }
 }
@@ -0,0 +1,320 @@
+use std::collections::HashMap;
+
+use crate::opcode::Opcode;
+
+use super::{
+    data::data_layout,
+    diagnostic::{Diagnostic, Span},
+    directive::Directive,
+    instruction::Instruction,
+    token::{ParseError, SpannedToken, Token},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Section {
+    Code,
+    Data,
+}
+
+/// A label along with the section it was declared in
+#[derive(Debug, Clone, PartialEq)]
+pub struct Symbol {
+    pub name: String,
+    pub section: Section,
+    pub offset: u32,
+}
+
+/// Maps label names to the byte offset of the instruction or data that follows them.
+/// Code labels are offsets into the program and data labels are addresses in the heap
+#[derive(Debug, Default, PartialEq)]
+pub struct SymbolTable {
+    symbols: HashMap<String, Symbol>,
+}
+
+impl SymbolTable {
+    pub fn new() -> Self {
+        SymbolTable {
+            symbols: HashMap::new(),
+        }
+    }
+
+    pub fn insert(&mut self, label: &str, section: Section, offset: u32) -> Result<(), ParseError> {
+        if self.symbols.contains_key(label) {
+            return Err(ParseError::DuplicateLabelError(label.to_owned()));
+        }
+
+        self.symbols.insert(
+            label.to_owned(),
+            Symbol {
+                name: label.to_owned(),
+                section,
+                offset,
+            },
+        );
+        Ok(())
+    }
+
+    pub fn get(&self, label: &str) -> Option<u32> {
+        self.symbols.get(label).map(|symbol| symbol.offset)
+    }
+
+    /// Returns every symbol, ordered by section and then by offset
+    pub fn into_symbols(self) -> Vec<Symbol> {
+        let mut symbols: Vec<Symbol> = self.symbols.into_values().collect();
+        symbols.sort_by(|a, b| (a.section, a.offset, &a.name).cmp(&(b.section, b.offset, &b.name)));
+        symbols
+    }
+}
+
+/// First pass: records the byte offset of every label declaration in both sections.
+/// A `LOAD` of a label takes two words once the label's offset no longer fits in 16
+/// bits, which can push later labels further out, so the layout is repeated until the
+/// offsets settle. Offsets only ever grow, so this always finishes.
+/// Code labels are counted from `origin`, the offset the code will be placed at
+pub fn collect_labels(
+    code: &[SpannedToken],
+    data: &[SpannedToken],
+    origin: u32,
+) -> Result<SymbolTable, Vec<Diagnostic>> {
+    let mut symbols = layout(code, data, origin, &SymbolTable::new())?;
+
+    loop {
+        let next = layout(code, data, origin, &symbols)?;
+        if next == symbols {
+            return Ok(symbols);
+        }
+        symbols = next;
+    }
+}
+
+/// Lays out both sections, sizing label operands with the offsets in `previous`
+fn layout(
+    code: &[SpannedToken],
+    data: &[SpannedToken],
+    origin: u32,
+    previous: &SymbolTable,
+) -> Result<SymbolTable, Vec<Diagnostic>> {
+    let mut symbols = SymbolTable::new();
+    let mut errors = vec![];
+    let mut offset = origin;
+    let mut in_bytes = false;
+
+    let mut insert = |label: &str, span: Span, section: Section, offset: u32| {
+        if let Err(error) = symbols.insert(label, section, offset) {
+            errors.push(Diagnostic::new(error, span));
+        }
+    };
+
+    for (n, (token, span)) in code.iter().enumerate() {
+        match token {
+            Token::Op(Opcode::LOAD) => {
+                let int = match code.get(n + 2) {
+                    Some((Token::IntegerOperand(int), _)) => *int,
+                    Some((Token::LabelUsage(label), _)) => previous.get(label).unwrap_or(0) as i32,
+                    _ => 0,
+                };
+                offset += 4 * Instruction::load_immediate(0, int).len() as u32;
+                in_bytes = false;
+            }
+            Token::Op(_) => {
+                offset += 4;
+                in_bytes = false;
+            }
+            Token::Directive(Directive::Byte) => in_bytes = true,
+            Token::IntegerOperand(_) | Token::LabelUsage(_) if in_bytes => offset += 1,
+            Token::LabelDeclaration(label) => insert(label, *span, Section::Code, offset),
+            _ => {}
+        }
+    }
+
+    data_layout(data, |label, span, offset| {
+        insert(label, span, Section::Data, offset)
+    });
+
+    if errors.is_empty() {
+        Ok(symbols)
+    } else {
+        Err(errors)
+    }
+}
+
+/// Second pass: drops label declarations and replaces label usages with their offsets.
+/// Undefined labels are reported in `errors` and replaced with 0 so that assembly can
+/// carry on and find any other errors
+pub fn resolve_labels(
+    input: Vec<SpannedToken>,
+    symbols: &SymbolTable,
+    errors: &mut Vec<Diagnostic>,
+) -> Vec<SpannedToken> {
+    let mut output = vec![];
+
+    for (token, span) in input {
+        match token {
+            Token::LabelDeclaration(_) => {}
+            Token::LabelUsage(label) => {
+                let offset = symbols.get(&label).unwrap_or_else(|| {
+                    errors.push(Diagnostic::new(
+                        ParseError::UndefinedLabelError(label),
+                        span,
+                    ));
+                    0
+                });
+                output.push((Token::IntegerOperand(offset as i32), span));
+            }
+            token => output.push((token, span)),
+        }
+    }
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spanned(tokens: Vec<Token>) -> Vec<SpannedToken> {
+        tokens
+            .into_iter()
+            .map(|token| (token, Span::default()))
+            .collect()
+    }
+
+    #[test]
+    fn test_collect_labels() {
+        let input = spanned(vec![
+            Token::Op(Opcode::HLT),
+            Token::LabelDeclaration("end".to_owned()),
+            Token::Op(Opcode::HLT),
+        ]);
+
+        assert_eq!(collect_labels(&input, &[], 0).unwrap().get("end"), Some(4));
+    }
+
+    #[test]
+    fn test_collect_duplicate_labels() {
+        let span = Span {
+            line: 2,
+            column: 1,
+            len: 4,
+        };
+        let input = vec![
+            (Token::LabelDeclaration("end".to_owned()), Span::default()),
+            (Token::LabelDeclaration("end".to_owned()), span),
+        ];
+
+        assert_eq!(
+            collect_labels(&input, &[], 0),
+            Err(vec![Diagnostic::new(
+                ParseError::DuplicateLabelError("end".to_owned()),
+                span
+            )])
+        );
+    }
+
+    #[test]
+    fn test_collect_data_labels() {
+        let code = spanned(vec![Token::LabelDeclaration("start".to_owned())]);
+        let data = spanned(vec![
+            Token::Directive(Directive::Space),
+            Token::IntegerOperand(8),
+            Token::LabelDeclaration("buffer".to_owned()),
+        ]);
+        let symbols = collect_labels(&code, &data, 0).unwrap();
+
+        assert_eq!(symbols.get("start"), Some(0));
+        assert_eq!(symbols.get("buffer"), Some(8));
+    }
+
+    #[test]
+    fn test_collect_labels_from_origin() {
+        let code = spanned(vec![
+            Token::Op(Opcode::HLT),
+            Token::LabelDeclaration("end".to_owned()),
+        ]);
+        let data = spanned(vec![Token::LabelDeclaration("buffer".to_owned())]);
+        let symbols = collect_labels(&code, &data, 8).unwrap();
+
+        assert_eq!(symbols.get("end"), Some(12));
+        assert_eq!(symbols.get("buffer"), Some(0));
+    }
+
+    #[test]
+    fn test_collect_wide_load_labels() {
+        let input = spanned(vec![
+            Token::Op(Opcode::LOAD),
+            Token::Register(0),
+            Token::IntegerOperand(70000),
+            Token::LabelDeclaration("end".to_owned()),
+            Token::Op(Opcode::HLT),
+        ]);
+
+        assert_eq!(collect_labels(&input, &[], 0).unwrap().get("end"), Some(8));
+    }
+
+    #[test]
+    fn test_collect_wide_label_usages() {
+        let code = spanned(vec![
+            Token::Op(Opcode::LOAD),
+            Token::Register(0),
+            Token::LabelUsage("buffer".to_owned()),
+            Token::LabelDeclaration("end".to_owned()),
+        ]);
+        let data = spanned(vec![
+            Token::Directive(Directive::Space),
+            Token::IntegerOperand(40000),
+            Token::LabelDeclaration("buffer".to_owned()),
+        ]);
+
+        assert_eq!(collect_labels(&code, &data, 0).unwrap().get("end"), Some(8));
+    }
+
+    #[test]
+    fn test_resolve_labels() {
+        let mut symbols = SymbolTable::new();
+        symbols.insert("end", Section::Code, 8).unwrap();
+        let input = spanned(vec![
+            Token::Op(Opcode::LOAD),
+            Token::Register(0),
+            Token::LabelUsage("end".to_owned()),
+            Token::LabelDeclaration("end".to_owned()),
+        ]);
+        let expected_output = spanned(vec![
+            Token::Op(Opcode::LOAD),
+            Token::Register(0),
+            Token::IntegerOperand(8),
+        ]);
+
+        let mut errors = vec![];
+
+        assert_eq!(
+            resolve_labels(input, &symbols, &mut errors),
+            expected_output
+        );
+        assert_eq!(errors, vec![]);
+    }
+
+    #[test]
+    fn test_resolve_undefined_labels() {
+        let input = spanned(vec![
+            Token::LabelUsage("start".to_owned()),
+            Token::LabelUsage("end".to_owned()),
+        ]);
+
+        let mut errors = vec![];
+
+        resolve_labels(input, &SymbolTable::new(), &mut errors);
+        assert_eq!(
+            errors,
+            vec![
+                Diagnostic::new(
+                    ParseError::UndefinedLabelError("start".to_owned()),
+                    Span::default()
+                ),
+                Diagnostic::new(
+                    ParseError::UndefinedLabelError("end".to_owned()),
+                    Span::default()
+                )
+            ]
+        );
+    }
+}
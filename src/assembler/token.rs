@@ -1,13 +1,30 @@
 use std::{error::Error, fmt::Display, num::ParseIntError};
 
-use crate::opcode::Opcode;
+use super::{diagnostic::Span, directive::Directive};
+use crate::{opcode::Opcode, vm::REGISTER_COUNT};
 
-#[derive(Debug, PartialEq)]
+#[allow(clippy::enum_variant_names)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum ParseError {
     InvalidOpcodeError(String),
     MissingRegisterSignError,
     MissingIntegerSignError,
     ParseIntError(ParseIntError),
+    InvalidLabelError(String),
+    DuplicateLabelError(String),
+    UndefinedLabelError(String),
+    InvalidDirectiveError(String),
+    InvalidDirectiveOperandError(Directive),
+    InvalidDataError,
+    UnterminatedStringError,
+    InvalidEscapeError(char),
+    IntegerOutOfRangeError(i32),
+    RegisterOutOfRangeError(u8),
+    UnalignedBytesError(usize),
+    InvalidOperandsError(Opcode),
+    TrailingTokenError,
+    ConstantPoolFullError,
+    InvalidIntegerError(String),
 }
 
 impl Display for ParseError {
@@ -18,36 +35,79 @@ impl Display for ParseError {
             PE::MissingRegisterSignError => write!(f, "Registers must start with '$'"),
             PE::MissingIntegerSignError => write!(f, "Integers must start with '#'"),
             PE::ParseIntError(e) => write!(f, "There was an error parsing the input: {e}"),
+            PE::InvalidLabelError(s) => write!(f, "'{s}' is not a valid label name"),
+            PE::DuplicateLabelError(s) => write!(f, "The label '{s}' is declared more than once"),
+            PE::UndefinedLabelError(s) => write!(f, "The label '{s}' is never declared"),
+            PE::InvalidDirectiveError(s) => write!(f, "The directive '{s}' does not exist"),
+            PE::InvalidDirectiveOperandError(d) => {
+                write!(f, "Invalid operand for the '{d}' directive")
+            }
+            PE::InvalidDataError => write!(
+                f,
+                "The .data section may only contain labels, directives and their operands"
+            ),
+            PE::UnterminatedStringError => write!(f, "String literal is missing a closing '\"'"),
+            PE::InvalidEscapeError(c) => write!(f, "'\\{c}' is not a valid escape sequence"),
+            PE::IntegerOutOfRangeError(n) => write!(f, "The integer {n} is out of range"),
+            PE::RegisterOutOfRangeError(n) => write!(
+                f,
+                "Register {n} does not exist, as there are only {REGISTER_COUNT}"
+            ),
+            PE::InvalidOperandsError(opcode) => write!(f, "Invalid operands for {opcode:?}"),
+            PE::ConstantPoolFullError => write!(
+                f,
+                "The program uses more than {} distinct float constants",
+                u16::MAX as usize + 1
+            ),
+            PE::TrailingTokenError => {
+                write!(f, "Expected the end of the line after an instruction")
+            }
+            PE::InvalidIntegerError(s) => write!(f, "'#{s}' is not a valid integer"),
+            PE::UnalignedBytesError(n) => write!(
+                f,
+                "'.byte' in the .code section must make up whole 4 byte words, but has {n} bytes"
+            ),
         }
     }
 }
 
 impl Error for ParseError {}
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Token {
     Op(Opcode),
     Register(u8),
+    FloatRegister(u8),
     IntegerOperand(i32),
+    FloatOperand(f64),
+    LabelDeclaration(String),
+    LabelUsage(String),
+    Directive(Directive),
+    StringLiteral(String),
 }
 
+/// A token along with where it appeared in the source
+pub type SpannedToken = (Token, Span);
+
 impl TryFrom<&str> for Token {
     type Error = ParseError;
     fn try_from(value: &str) -> Result<Self, Self::Error> {
-        if value.starts_with('$') {
-            value
-                .strip_prefix('$')
-                .ok_or(ParseError::MissingRegisterSignError)?
-                .parse::<u8>()
-                .map(Token::Register)
-                .map_err(|e| ParseError::ParseIntError(e))
-        } else if value.starts_with('#') {
-            value
-                .strip_prefix('#')
-                .ok_or(ParseError::MissingIntegerSignError)?
-                .parse::<i32>()
+        if let Some(register) = value.strip_prefix("$f") {
+            register_number(register).map(Token::FloatRegister)
+        } else if let Some(register) = value.strip_prefix('$') {
+            register_number(register).map(Token::Register)
+        } else if let Some(number) = value.strip_prefix('#') {
+            // Anything that is not an integer may still be a float, such as `#1.5`
+            // or `#-2e10`, but integer errors are the ones worth reporting
+            integer_operand(number)
                 .map(Token::IntegerOperand)
-                .map_err(|e| ParseError::ParseIntError(e))
+                .or_else(|e| number.parse().map(Token::FloatOperand).map_err(|_| e))
+        } else if value.starts_with('.') {
+            Directive::try_from(value).map(Token::Directive)
+        } else if let Some(label) = value.strip_prefix('@') {
+            label_name(label).map(Token::LabelUsage)
+        } else if let Some(label) = value.strip_suffix(':') {
+            label_name(label).map(Token::LabelDeclaration)
         } else {
             Opcode::try_from(value)
                 .map(Token::Op)
@@ -55,3 +115,83 @@ impl TryFrom<&str> for Token {
         }
     }
 }
+
+/// Parses the number of a register that exists
+fn register_number(register: &str) -> Result<u8, ParseError> {
+    let n = register.parse::<u8>().map_err(ParseError::ParseIntError)?;
+    if (n as usize) < REGISTER_COUNT {
+        Ok(n)
+    } else {
+        Err(ParseError::RegisterOutOfRangeError(n))
+    }
+}
+
+/// Checks that a label is made up only of letters, digits and underscores
+fn label_name(label: &str) -> Result<String, ParseError> {
+    if !label.is_empty() && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        Ok(label.to_owned())
+    } else {
+        Err(ParseError::InvalidLabelError(label.to_owned()))
+    }
+}
+
+/// Parses an integer written in decimal, hex (`0x1F4`), binary (`0b101`) or as a
+/// character literal (`'a'`). Numbers may be negative
+fn integer_operand(value: &str) -> Result<i32, ParseError> {
+    if value.starts_with('\'') {
+        return char_literal(value).map(|c| c as i32);
+    }
+
+    let (sign, digits) = match value.strip_prefix('-') {
+        Some(digits) => ("-", digits),
+        None => ("", value),
+    };
+    let (radix, digits) = if let Some(digits) = digits.strip_prefix("0x") {
+        (16, digits)
+    } else if let Some(digits) = digits.strip_prefix("0b") {
+        (2, digits)
+    } else {
+        (10, digits)
+    };
+
+    // `from_str_radix` accepts a sign of its own, which would let `#0x-5` through
+    if digits.starts_with(['-', '+']) {
+        return Err(ParseError::InvalidIntegerError(value.to_owned()));
+    }
+
+    i32::from_str_radix(&format!("{sign}{digits}"), radix).map_err(ParseError::ParseIntError)
+}
+
+/// Parses a single, possibly escaped, character between single quotes
+fn char_literal(value: &str) -> Result<char, ParseError> {
+    let error = || ParseError::InvalidIntegerError(value.to_owned());
+    let mut chars = value
+        .strip_prefix('\'')
+        .and_then(|value| value.strip_suffix('\''))
+        .ok_or_else(error)?
+        .chars();
+
+    let c = match chars.next().ok_or_else(error)? {
+        '\\' => chars.next().and_then(unescape).ok_or_else(error)?,
+        c => c,
+    };
+
+    match chars.next() {
+        None => Ok(c),
+        Some(_) => Err(error()),
+    }
+}
+
+/// The character that an escape sequence such as `\n` stands for
+pub fn unescape(c: char) -> Option<char> {
+    match c {
+        'n' => Some('\n'),
+        't' => Some('\t'),
+        'r' => Some('\r'),
+        '0' => Some('\0'),
+        '\\' => Some('\\'),
+        '"' => Some('"'),
+        '\'' => Some('\''),
+        _ => None,
+    }
+}
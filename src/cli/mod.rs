@@ -0,0 +1,413 @@
+use std::{error::Error, fmt::Display, process::ExitCode};
+
+use crate::{
+    assembler::{
+        assemble_program, disassemble, disassemble_data, render_all, write_bytecode, Program,
+    },
+    repl::{print_registers, REPL},
+    vm::{
+        is_bytecode, read_bytecode, ArithmeticMode, Builtins, ExitStatus, LoadError, Profiler,
+        TraceFormat, Tracer, VmError, VM,
+    },
+};
+
+const USAGE: &str = "\
+Usage: potassium [COMMAND]
+
+Commands:
+  repl                      Start the interactive REPL (the default)
+  asm <file> [-o <output>]  Assemble a source file into bytecode
+  run <file> [options]      Run a source or bytecode file
+  disasm <file>             Print the instructions in a source or bytecode file
+
+Options for run:
+  --max-instructions <n>    Stop with an error after executing n instructions
+  --dump-registers          Print the registers once the program stops
+  --arithmetic <mode>       Handle overflow by wrapping, trapping (the default) or saturating
+  --trace <format>          Write every executed instruction to stderr as human or json
+  --trace-last <n>          Only write the last n instructions, and only if the program fails
+  --profile                 Write execution counts and times to stderr once the program stops
+  --profile-folded <file>   Write the call stacks executed in the folded format for flamegraphs";
+
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    Repl,
+    Asm { input: String, output: String },
+    Run { input: String, options: RunOptions },
+    Disasm { input: String },
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct RunOptions {
+    pub max_instructions: Option<u64>,
+    pub dump_registers: bool,
+    pub arithmetic_mode: ArithmeticMode,
+    pub trace: Option<TraceFormat>,
+    pub trace_last: Option<usize>,
+    pub profile: bool,
+    pub profile_folded: Option<String>,
+}
+
+#[derive(Debug)]
+pub enum CliError {
+    Usage(String),
+    Io(String, std::io::Error),
+    /// Assembler diagnostics, already rendered against the source
+    Parse(String),
+    Load(LoadError),
+    Vm(VmError),
+    InstructionLimit(u64),
+}
+
+impl Display for CliError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        use CliError as CE;
+        match self {
+            CE::Usage(s) => write!(f, "{s}\n\n{USAGE}"),
+            CE::Io(path, e) => write!(f, "Failed to access '{path}': {e}"),
+            CE::Parse(diagnostics) => write!(f, "{diagnostics}"),
+            CE::Load(e) => write!(f, "Failed to load bytecode: {e}"),
+            CE::Vm(e) => write!(f, "{e}"),
+            CE::InstructionLimit(n) => write!(f, "Instruction limit of {n} reached"),
+        }
+    }
+}
+
+impl Error for CliError {}
+
+/// Runs the command-line interface, returning the process exit code
+pub fn run(args: impl Iterator<Item = String>) -> ExitCode {
+    let result = parse_args(args).and_then(|command| match command {
+        Command::Repl => REPL::new().start(),
+        Command::Asm { input, output } => asm(&input, &output),
+        Command::Run { input, options } => run_file(&input, &options),
+        Command::Disasm { input } => disasm(&input),
+    });
+
+    match result {
+        Ok(code) => ExitCode::from(code),
+        Err(e @ CliError::Usage(_)) => {
+            eprintln!("{e}");
+            ExitCode::from(2)
+        }
+        Err(e) => {
+            eprintln!("{e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+pub fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Command, CliError> {
+    let command = args.next();
+    let mut input = None;
+    let mut output = None;
+    let mut options = RunOptions::default();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-o" => {
+                output = Some(
+                    args.next()
+                        .ok_or(CliError::Usage("'-o' expects a file".to_owned()))?,
+                )
+            }
+            "--max-instructions" => {
+                let n = args
+                    .next()
+                    .and_then(|n| n.parse().ok())
+                    .ok_or(CliError::Usage(
+                        "'--max-instructions' expects a number".to_owned(),
+                    ))?;
+                options.max_instructions = Some(n);
+            }
+            "--dump-registers" => options.dump_registers = true,
+            "--arithmetic" => {
+                options.arithmetic_mode = args
+                    .next()
+                    .ok_or(CliError::Usage("'--arithmetic' expects a mode".to_owned()))?
+                    .parse()
+                    .map_err(CliError::Usage)?;
+            }
+            "--trace" => {
+                options.trace = Some(
+                    args.next()
+                        .ok_or(CliError::Usage("'--trace' expects a format".to_owned()))?
+                        .parse()
+                        .map_err(CliError::Usage)?,
+                );
+            }
+            "--trace-last" => {
+                let n = args
+                    .next()
+                    .and_then(|n| n.parse().ok())
+                    .ok_or(CliError::Usage(
+                        "'--trace-last' expects a number".to_owned(),
+                    ))?;
+                options.trace_last = Some(n);
+            }
+            "--profile" => options.profile = true,
+            "--profile-folded" => {
+                options.profile_folded = Some(args.next().ok_or(CliError::Usage(
+                    "'--profile-folded' expects a file".to_owned(),
+                ))?);
+            }
+            _ if input.is_none() && !arg.starts_with('-') => input = Some(arg),
+            _ => return Err(CliError::Usage(format!("Unexpected argument '{arg}'"))),
+        }
+    }
+
+    let input = || input.ok_or(CliError::Usage("Missing input file".to_owned()));
+
+    match command.as_deref() {
+        None | Some("repl") => Ok(Command::Repl),
+        Some("asm") => {
+            let input = input()?;
+            Ok(Command::Asm {
+                output: output.unwrap_or_else(|| default_output(&input)),
+                input,
+            })
+        }
+        Some("run") => Ok(Command::Run {
+            input: input()?,
+            options,
+        }),
+        Some("disasm") => Ok(Command::Disasm { input: input()? }),
+        Some(command) => Err(CliError::Usage(format!("Unknown command '{command}'"))),
+    }
+}
+
+/// Replaces the extension of `input` with `.pbc`
+fn default_output(input: &str) -> String {
+    std::path::Path::new(input)
+        .with_extension("pbc")
+        .to_string_lossy()
+        .into_owned()
+}
+
+fn asm(input: &str, output: &str) -> Result<u8, CliError> {
+    let program = load(input)?;
+
+    std::fs::write(output, write_bytecode(&program))
+        .map_err(|e| CliError::Io(output.to_owned(), e))?;
+
+    Ok(0)
+}
+
+fn run_file(input: &str, options: &RunOptions) -> Result<u8, CliError> {
+    let mut vm = VM::new();
+    vm.arithmetic_mode = options.arithmetic_mode;
+    vm.register(Builtins::stdio());
+    vm.tracer = match (options.trace, options.trace_last) {
+        (format, Some(n)) => Some(Tracer::ring_buffer(
+            format.unwrap_or_default(),
+            std::io::stderr(),
+            n,
+        )),
+        (Some(format), None) => Some(Tracer::log(format, std::io::stderr())),
+        (None, None) => None,
+    };
+    if options.profile || options.profile_folded.is_some() {
+        vm.profiler = Some(Profiler::new());
+    }
+    let (program, source) = load_with_source(input)?;
+    let (symbols, debug) = (program.symbols.clone(), program.debug.clone());
+    vm.load_program(program);
+
+    let result = execute(&mut vm, options.max_instructions);
+
+    if options.dump_registers {
+        print_registers(&vm);
+    }
+    if let Some(profiler) = &vm.profiler {
+        if options.profile {
+            eprint!(
+                "{}",
+                profiler.report(&vm, &symbols, &debug, source.as_deref())
+            );
+        }
+        if let Some(path) = &options.profile_folded {
+            std::fs::write(path, profiler.folded(&symbols))
+                .map_err(|e| CliError::Io(path.clone(), e))?;
+        }
+    }
+
+    match result? {
+        ExitStatus::Halted | ExitStatus::Running | ExitStatus::Paused => Ok(0),
+        ExitStatus::Exited(code) => Ok(code as u8),
+    }
+}
+
+/// Runs the VM until it stops, or until `max_instructions` have been executed
+fn execute(vm: &mut VM, max_instructions: Option<u64>) -> Result<ExitStatus, CliError> {
+    let Some(limit) = max_instructions else {
+        return vm.run().map_err(CliError::Vm);
+    };
+
+    match vm.run_for(limit).map_err(CliError::Vm)? {
+        ExitStatus::Running => Err(CliError::InstructionLimit(limit)),
+        status => Ok(status),
+    }
+}
+
+fn disasm(input: &str) -> Result<u8, CliError> {
+    let program = load(input)?;
+
+    let code: Vec<u8> = program.code.into_iter().flatten().collect();
+
+    for line in disassemble(&code, &program.constants) {
+        println!("{line}");
+    }
+
+    if !program.data.is_empty() {
+        println!(".data");
+        for line in disassemble_data(&program.data) {
+            println!("{line}");
+        }
+    }
+
+    Ok(0)
+}
+
+/// Reads a program from either a bytecode or a source file
+fn load(path: &str) -> Result<Program, CliError> {
+    load_with_source(path).map(|(program, _)| program)
+}
+
+/// Reads a program along with its source, if it was read from a source file
+fn load_with_source(path: &str) -> Result<(Program, Option<String>), CliError> {
+    let file = std::fs::read(path).map_err(|e| CliError::Io(path.to_owned(), e))?;
+
+    if is_bytecode(&file) {
+        read_bytecode(&file)
+            .map(|program| (program, None))
+            .map_err(CliError::Load)
+    } else {
+        let source = String::from_utf8(file).map_err(|e| {
+            CliError::Io(
+                path.to_owned(),
+                std::io::Error::new(std::io::ErrorKind::InvalidData, e),
+            )
+        })?;
+        match assemble_program(&source) {
+            Ok(program) => Ok((program, Some(source))),
+            Err(diagnostics) => Err(CliError::Parse(render_all(&diagnostics, &source, path))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(input: &str) -> impl Iterator<Item = String> + '_ {
+        input.split_whitespace().map(str::to_owned)
+    }
+
+    #[test]
+    fn test_parse_args_repl() {
+        assert_eq!(parse_args(args("")).unwrap(), Command::Repl);
+        assert_eq!(parse_args(args("repl")).unwrap(), Command::Repl);
+    }
+
+    #[test]
+    fn test_parse_args_asm() {
+        assert_eq!(
+            parse_args(args("asm prog.iasm")).unwrap(),
+            Command::Asm {
+                input: "prog.iasm".to_owned(),
+                output: "prog.pbc".to_owned()
+            }
+        );
+        assert_eq!(
+            parse_args(args("asm prog.iasm -o out.pbc")).unwrap(),
+            Command::Asm {
+                input: "prog.iasm".to_owned(),
+                output: "out.pbc".to_owned()
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_args_run() {
+        assert_eq!(
+            parse_args(args(
+                "run prog.pbc --max-instructions 10 --dump-registers --arithmetic wrapping"
+            ))
+            .unwrap(),
+            Command::Run {
+                input: "prog.pbc".to_owned(),
+                options: RunOptions {
+                    max_instructions: Some(10),
+                    dump_registers: true,
+                    arithmetic_mode: ArithmeticMode::Wrapping,
+                    ..RunOptions::default()
+                }
+            }
+        );
+        assert_eq!(
+            parse_args(args("run prog.pbc --trace json --trace-last 20")).unwrap(),
+            Command::Run {
+                input: "prog.pbc".to_owned(),
+                options: RunOptions {
+                    trace: Some(TraceFormat::JsonLines),
+                    trace_last: Some(20),
+                    ..RunOptions::default()
+                }
+            }
+        );
+        assert_eq!(
+            parse_args(args("run prog.pbc --profile --profile-folded out.folded")).unwrap(),
+            Command::Run {
+                input: "prog.pbc".to_owned(),
+                options: RunOptions {
+                    profile: true,
+                    profile_folded: Some("out.folded".to_owned()),
+                    ..RunOptions::default()
+                }
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_args_errors() {
+        assert!(matches!(parse_args(args("run")), Err(CliError::Usage(_))));
+        assert!(matches!(
+            parse_args(args("run a b")),
+            Err(CliError::Usage(_))
+        ));
+        assert!(matches!(
+            parse_args(args("run a --max-instructions x")),
+            Err(CliError::Usage(_))
+        ));
+        assert!(matches!(
+            parse_args(args("run a --arithmetic clamping")),
+            Err(CliError::Usage(_))
+        ));
+        assert!(matches!(
+            parse_args(args("run a --trace xml")),
+            Err(CliError::Usage(_))
+        ));
+        assert!(matches!(
+            parse_args(args("run a --profile-folded")),
+            Err(CliError::Usage(_))
+        ));
+        assert!(matches!(
+            parse_args(args("frobnicate")),
+            Err(CliError::Usage(_))
+        ));
+    }
+
+    #[test]
+    fn test_execute_instruction_limit() {
+        let mut vm = VM::new();
+        vm.set_program(vec![
+            [1, 0, 0, 0], // Set reg0 to 0
+            [6, 0, 0, 0], // Jump to reg0
+        ]);
+
+        assert!(matches!(
+            execute(&mut vm, Some(100)),
+            Err(CliError::InstructionLimit(100))
+        ));
+    }
+}
@@ -0,0 +1,5 @@
+pub mod assembler;
+pub mod cli;
+pub mod opcode;
+pub mod repl;
+pub mod vm;
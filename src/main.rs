@@ -1,9 +1,5 @@
-pub mod assembler;
-pub mod opcode;
-pub mod repl;
-pub mod vm;
+use std::process::ExitCode;
 
-fn main() {
-    let mut repl = repl::REPL::new();
-    repl.start();
+fn main() -> ExitCode {
+    potassium::cli::run(std::env::args().skip(1))
 }
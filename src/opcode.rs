@@ -1,6 +1,6 @@
 use std::fmt::Display;
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Opcode {
     HLT,
     LOAD,
@@ -19,6 +19,80 @@ pub enum Opcode {
     LTQ,
     JEQ,
     JNEQ,
+    ALOC,
+    LOADB,
+    LOADH,
+    LOADW,
+    STOREB,
+    STOREH,
+    STOREW,
+    PUSH,
+    POP,
+    CALL,
+    RET,
+    LOADHI,
+    LOADLO,
+    AND,
+    OR,
+    XOR,
+    NOT,
+    SHL,
+    SHR,
+    SAR,
+    SHLI,
+    SHRI,
+    SARI,
+    JC,
+    JNC,
+    JO,
+    JNO,
+    LOADF,
+    ADDF,
+    SUBF,
+    MULF,
+    DIVF,
+    EQF,
+    NEQF,
+    GTF,
+    LTF,
+    GTQF,
+    LTQF,
+    ITOF,
+    FTOI,
+    CMP,
+    JZ,
+    JNZ,
+    JLT,
+    JGE,
+    JGT,
+    JLE,
+    JMPR,
+    JMPI,
+    JEQR,
+    JEQI,
+    JNEQR,
+    JNEQI,
+    JZR,
+    JZI,
+    JNZR,
+    JNZI,
+    JLTR,
+    JLTI,
+    JGER,
+    JGEI,
+    JGTR,
+    JGTI,
+    JLER,
+    JLEI,
+    JCR,
+    JCI,
+    JNCR,
+    JNCI,
+    JOR,
+    JOI,
+    JNOR,
+    JNOI,
+    SYSCALL,
 }
 
 #[derive(Debug)]
@@ -59,6 +133,80 @@ impl TryFrom<u8> for Opcode {
             14 => Ok(Opcode::LTQ),
             15 => Ok(Opcode::JEQ),
             16 => Ok(Opcode::JNEQ),
+            17 => Ok(Opcode::ALOC),
+            18 => Ok(Opcode::LOADB),
+            19 => Ok(Opcode::LOADH),
+            20 => Ok(Opcode::LOADW),
+            21 => Ok(Opcode::STOREB),
+            22 => Ok(Opcode::STOREH),
+            23 => Ok(Opcode::STOREW),
+            24 => Ok(Opcode::PUSH),
+            25 => Ok(Opcode::POP),
+            26 => Ok(Opcode::CALL),
+            27 => Ok(Opcode::RET),
+            28 => Ok(Opcode::LOADHI),
+            29 => Ok(Opcode::LOADLO),
+            30 => Ok(Opcode::AND),
+            31 => Ok(Opcode::OR),
+            32 => Ok(Opcode::XOR),
+            33 => Ok(Opcode::NOT),
+            34 => Ok(Opcode::SHL),
+            35 => Ok(Opcode::SHR),
+            36 => Ok(Opcode::SAR),
+            37 => Ok(Opcode::SHLI),
+            38 => Ok(Opcode::SHRI),
+            39 => Ok(Opcode::SARI),
+            40 => Ok(Opcode::JC),
+            41 => Ok(Opcode::JNC),
+            42 => Ok(Opcode::JO),
+            43 => Ok(Opcode::JNO),
+            44 => Ok(Opcode::LOADF),
+            45 => Ok(Opcode::ADDF),
+            46 => Ok(Opcode::SUBF),
+            47 => Ok(Opcode::MULF),
+            48 => Ok(Opcode::DIVF),
+            49 => Ok(Opcode::EQF),
+            50 => Ok(Opcode::NEQF),
+            51 => Ok(Opcode::GTF),
+            52 => Ok(Opcode::LTF),
+            53 => Ok(Opcode::GTQF),
+            54 => Ok(Opcode::LTQF),
+            55 => Ok(Opcode::ITOF),
+            56 => Ok(Opcode::FTOI),
+            57 => Ok(Opcode::CMP),
+            58 => Ok(Opcode::JZ),
+            59 => Ok(Opcode::JNZ),
+            60 => Ok(Opcode::JLT),
+            61 => Ok(Opcode::JGE),
+            62 => Ok(Opcode::JGT),
+            63 => Ok(Opcode::JLE),
+            64 => Ok(Opcode::JMPR),
+            65 => Ok(Opcode::JMPI),
+            66 => Ok(Opcode::JEQR),
+            67 => Ok(Opcode::JEQI),
+            68 => Ok(Opcode::JNEQR),
+            69 => Ok(Opcode::JNEQI),
+            70 => Ok(Opcode::JZR),
+            71 => Ok(Opcode::JZI),
+            72 => Ok(Opcode::JNZR),
+            73 => Ok(Opcode::JNZI),
+            74 => Ok(Opcode::JLTR),
+            75 => Ok(Opcode::JLTI),
+            76 => Ok(Opcode::JGER),
+            77 => Ok(Opcode::JGEI),
+            78 => Ok(Opcode::JGTR),
+            79 => Ok(Opcode::JGTI),
+            80 => Ok(Opcode::JLER),
+            81 => Ok(Opcode::JLEI),
+            82 => Ok(Opcode::JCR),
+            83 => Ok(Opcode::JCI),
+            84 => Ok(Opcode::JNCR),
+            85 => Ok(Opcode::JNCI),
+            86 => Ok(Opcode::JOR),
+            87 => Ok(Opcode::JOI),
+            88 => Ok(Opcode::JNOR),
+            89 => Ok(Opcode::JNOI),
+            90 => Ok(Opcode::SYSCALL),
             n => Err(InvalidOpcodeError::new(n)),
         }
     }
@@ -84,6 +232,80 @@ impl From<Opcode> for u8 {
             Opcode::LTQ => 14,
             Opcode::JEQ => 15,
             Opcode::JNEQ => 16,
+            Opcode::ALOC => 17,
+            Opcode::LOADB => 18,
+            Opcode::LOADH => 19,
+            Opcode::LOADW => 20,
+            Opcode::STOREB => 21,
+            Opcode::STOREH => 22,
+            Opcode::STOREW => 23,
+            Opcode::PUSH => 24,
+            Opcode::POP => 25,
+            Opcode::CALL => 26,
+            Opcode::RET => 27,
+            Opcode::LOADHI => 28,
+            Opcode::LOADLO => 29,
+            Opcode::AND => 30,
+            Opcode::OR => 31,
+            Opcode::XOR => 32,
+            Opcode::NOT => 33,
+            Opcode::SHL => 34,
+            Opcode::SHR => 35,
+            Opcode::SAR => 36,
+            Opcode::SHLI => 37,
+            Opcode::SHRI => 38,
+            Opcode::SARI => 39,
+            Opcode::JC => 40,
+            Opcode::JNC => 41,
+            Opcode::JO => 42,
+            Opcode::JNO => 43,
+            Opcode::LOADF => 44,
+            Opcode::ADDF => 45,
+            Opcode::SUBF => 46,
+            Opcode::MULF => 47,
+            Opcode::DIVF => 48,
+            Opcode::EQF => 49,
+            Opcode::NEQF => 50,
+            Opcode::GTF => 51,
+            Opcode::LTF => 52,
+            Opcode::GTQF => 53,
+            Opcode::LTQF => 54,
+            Opcode::ITOF => 55,
+            Opcode::FTOI => 56,
+            Opcode::CMP => 57,
+            Opcode::JZ => 58,
+            Opcode::JNZ => 59,
+            Opcode::JLT => 60,
+            Opcode::JGE => 61,
+            Opcode::JGT => 62,
+            Opcode::JLE => 63,
+            Opcode::JMPR => 64,
+            Opcode::JMPI => 65,
+            Opcode::JEQR => 66,
+            Opcode::JEQI => 67,
+            Opcode::JNEQR => 68,
+            Opcode::JNEQI => 69,
+            Opcode::JZR => 70,
+            Opcode::JZI => 71,
+            Opcode::JNZR => 72,
+            Opcode::JNZI => 73,
+            Opcode::JLTR => 74,
+            Opcode::JLTI => 75,
+            Opcode::JGER => 76,
+            Opcode::JGEI => 77,
+            Opcode::JGTR => 78,
+            Opcode::JGTI => 79,
+            Opcode::JLER => 80,
+            Opcode::JLEI => 81,
+            Opcode::JCR => 82,
+            Opcode::JCI => 83,
+            Opcode::JNCR => 84,
+            Opcode::JNCI => 85,
+            Opcode::JOR => 86,
+            Opcode::JOI => 87,
+            Opcode::JNOR => 88,
+            Opcode::JNOI => 89,
+            Opcode::SYSCALL => 90,
         }
     }
 }
@@ -110,7 +332,163 @@ impl TryFrom<&str> for Opcode {
             "ltq" => Ok(Opcode::LTQ),
             "jeq" => Ok(Opcode::JEQ),
             "jneq" => Ok(Opcode::JNEQ),
+            "aloc" => Ok(Opcode::ALOC),
+            "loadb" => Ok(Opcode::LOADB),
+            "loadh" => Ok(Opcode::LOADH),
+            "loadw" => Ok(Opcode::LOADW),
+            "storeb" => Ok(Opcode::STOREB),
+            "storeh" => Ok(Opcode::STOREH),
+            "storew" => Ok(Opcode::STOREW),
+            "push" => Ok(Opcode::PUSH),
+            "pop" => Ok(Opcode::POP),
+            "call" => Ok(Opcode::CALL),
+            "ret" => Ok(Opcode::RET),
+            "loadhi" => Ok(Opcode::LOADHI),
+            "loadlo" => Ok(Opcode::LOADLO),
+            "and" => Ok(Opcode::AND),
+            "or" => Ok(Opcode::OR),
+            "xor" => Ok(Opcode::XOR),
+            "not" => Ok(Opcode::NOT),
+            "shl" => Ok(Opcode::SHL),
+            "shr" => Ok(Opcode::SHR),
+            "sar" => Ok(Opcode::SAR),
+            "shli" => Ok(Opcode::SHLI),
+            "shri" => Ok(Opcode::SHRI),
+            "sari" => Ok(Opcode::SARI),
+            "jc" => Ok(Opcode::JC),
+            "jnc" => Ok(Opcode::JNC),
+            "jo" => Ok(Opcode::JO),
+            "jno" => Ok(Opcode::JNO),
+            "loadf" => Ok(Opcode::LOADF),
+            "addf" => Ok(Opcode::ADDF),
+            "subf" => Ok(Opcode::SUBF),
+            "mulf" => Ok(Opcode::MULF),
+            "divf" => Ok(Opcode::DIVF),
+            "eqf" => Ok(Opcode::EQF),
+            "neqf" => Ok(Opcode::NEQF),
+            "gtf" => Ok(Opcode::GTF),
+            "ltf" => Ok(Opcode::LTF),
+            "gtqf" => Ok(Opcode::GTQF),
+            "ltqf" => Ok(Opcode::LTQF),
+            "itof" => Ok(Opcode::ITOF),
+            "ftoi" => Ok(Opcode::FTOI),
+            "cmp" => Ok(Opcode::CMP),
+            "jz" => Ok(Opcode::JZ),
+            "jnz" => Ok(Opcode::JNZ),
+            "jlt" => Ok(Opcode::JLT),
+            "jge" => Ok(Opcode::JGE),
+            "jgt" => Ok(Opcode::JGT),
+            "jle" => Ok(Opcode::JLE),
+            "jmpr" => Ok(Opcode::JMPR),
+            "jmpi" => Ok(Opcode::JMPI),
+            "jeqr" => Ok(Opcode::JEQR),
+            "jeqi" => Ok(Opcode::JEQI),
+            "jneqr" => Ok(Opcode::JNEQR),
+            "jneqi" => Ok(Opcode::JNEQI),
+            "jzr" => Ok(Opcode::JZR),
+            "jzi" => Ok(Opcode::JZI),
+            "jnzr" => Ok(Opcode::JNZR),
+            "jnzi" => Ok(Opcode::JNZI),
+            "jltr" => Ok(Opcode::JLTR),
+            "jlti" => Ok(Opcode::JLTI),
+            "jger" => Ok(Opcode::JGER),
+            "jgei" => Ok(Opcode::JGEI),
+            "jgtr" => Ok(Opcode::JGTR),
+            "jgti" => Ok(Opcode::JGTI),
+            "jler" => Ok(Opcode::JLER),
+            "jlei" => Ok(Opcode::JLEI),
+            "jcr" => Ok(Opcode::JCR),
+            "jci" => Ok(Opcode::JCI),
+            "jncr" => Ok(Opcode::JNCR),
+            "jnci" => Ok(Opcode::JNCI),
+            "jor" => Ok(Opcode::JOR),
+            "joi" => Ok(Opcode::JOI),
+            "jnor" => Ok(Opcode::JNOR),
+            "jnoi" => Ok(Opcode::JNOI),
+            "syscall" => Ok(Opcode::SYSCALL),
             _ => Err(InvalidOpcodeError::new(value.to_owned())),
         }
     }
 }
+
+/// Where a jump takes its target from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JumpTarget {
+    /// A register holding the address
+    Register,
+    /// A signed 16 bit offset from the address of the jump itself
+    Relative,
+    /// A 24 bit address
+    Absolute,
+}
+
+/// The register, relative and absolute forms of every jump
+const JUMPS: [[Opcode; 3]; 13] = [
+    [Opcode::JMP, Opcode::JMPR, Opcode::JMPI],
+    [Opcode::JEQ, Opcode::JEQR, Opcode::JEQI],
+    [Opcode::JNEQ, Opcode::JNEQR, Opcode::JNEQI],
+    [Opcode::JZ, Opcode::JZR, Opcode::JZI],
+    [Opcode::JNZ, Opcode::JNZR, Opcode::JNZI],
+    [Opcode::JLT, Opcode::JLTR, Opcode::JLTI],
+    [Opcode::JGE, Opcode::JGER, Opcode::JGEI],
+    [Opcode::JGT, Opcode::JGTR, Opcode::JGTI],
+    [Opcode::JLE, Opcode::JLER, Opcode::JLEI],
+    [Opcode::JC, Opcode::JCR, Opcode::JCI],
+    [Opcode::JNC, Opcode::JNCR, Opcode::JNCI],
+    [Opcode::JO, Opcode::JOR, Opcode::JOI],
+    [Opcode::JNO, Opcode::JNOR, Opcode::JNOI],
+];
+
+impl Opcode {
+    /// For any form of a jump, the register form that decides whether it is taken,
+    /// along with where this form takes its target from
+    pub fn jump(self) -> Option<(Opcode, JumpTarget)> {
+        JUMPS.iter().find_map(|forms| {
+            let target = match forms.iter().position(|&opcode| opcode == self)? {
+                0 => JumpTarget::Register,
+                1 => JumpTarget::Relative,
+                _ => JumpTarget::Absolute,
+            };
+            Some((forms[0], target))
+        })
+    }
+
+    /// The form of this jump that takes its target from `target`
+    pub fn jump_form(self, target: JumpTarget) -> Option<Opcode> {
+        let forms = JUMPS.iter().find(|forms| forms.contains(&self))?;
+        Some(forms[target as usize])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_jump_forms() {
+        assert_eq!(
+            Opcode::JLTR.jump(),
+            Some((Opcode::JLT, JumpTarget::Relative))
+        );
+        assert_eq!(
+            Opcode::JMP.jump(),
+            Some((Opcode::JMP, JumpTarget::Register))
+        );
+        assert_eq!(Opcode::JMPF.jump(), None);
+        assert_eq!(
+            Opcode::JNOR.jump_form(JumpTarget::Absolute),
+            Some(Opcode::JNOI)
+        );
+    }
+
+    #[test]
+    fn test_opcode_round_trip() {
+        for byte in 0..=u8::MAX {
+            if let Ok(opcode) = Opcode::try_from(byte) {
+                assert_eq!(u8::from(opcode), byte);
+                let name = format!("{opcode:?}").to_lowercase();
+                assert_eq!(Opcode::try_from(name.as_str()).ok(), Some(opcode));
+            }
+        }
+    }
+}
@@ -0,0 +1,326 @@
+use crate::{
+    assembler::{disassemble, Section, Symbol},
+    vm::{ExitStatus, VmError, REGISTER_COUNT, VM},
+};
+
+/// The debugger state that lives outside the VM. Breakpoints are kept in
+/// [`VM::breakpoints`] and watchpoints are checked from [`VM::step_hook`]
+#[derive(Debug, Default)]
+pub struct Debugger {
+    /// The labels of the loaded program, so breakpoints can be set by name
+    pub symbols: Vec<Symbol>,
+    /// Watched registers, with the value each had when execution last stopped
+    watches: Vec<(usize, i32)>,
+}
+
+/// The outcome of running under the debugger
+#[derive(Debug, PartialEq)]
+pub struct Stop {
+    pub result: Result<ExitStatus, VmError>,
+    /// Each watched register that changed, with its old and new values
+    pub changes: Vec<(usize, i32, i32)>,
+}
+
+impl Debugger {
+    /// Finds the code offset for a decimal offset or a code label, with or without `@`
+    pub fn resolve(&self, target: &str) -> Option<usize> {
+        if let Ok(offset) = target.parse() {
+            return Some(offset);
+        }
+
+        let label = target.strip_prefix('@').unwrap_or(target);
+        self.symbols
+            .iter()
+            .find(|symbol| symbol.section == Section::Code && symbol.name == label)
+            .map(|symbol| symbol.offset as usize)
+    }
+
+    /// Adds a breakpoint at `target`, returning its offset
+    pub fn add_breakpoint(&self, vm: &mut VM, target: &str) -> Result<usize, String> {
+        let offset = self
+            .resolve(target)
+            .ok_or(format!("'{target}' is not an offset or a code label"))?;
+        if !offset.is_multiple_of(4) {
+            return Err(format!("{offset} is not the start of an instruction"));
+        }
+
+        vm.breakpoints.insert(offset);
+        Ok(offset)
+    }
+
+    /// Watches the register named by `$n`
+    pub fn add_watch(&mut self, vm: &VM, register: &str) -> Result<usize, String> {
+        let register = parse_register(register)?;
+        if !self.watches.iter().any(|&(watched, _)| watched == register) {
+            self.watches.push((register, vm.registers[register]));
+        }
+        Ok(register)
+    }
+
+    pub fn watches(&self) -> impl Iterator<Item = usize> + '_ {
+        self.watches.iter().map(|&(register, _)| register)
+    }
+
+    /// Removes the watch on `$n` or the breakpoint at `target`, or every watch and
+    /// breakpoint if there is no target
+    pub fn delete(&mut self, vm: &mut VM, target: Option<&str>) -> Result<(), String> {
+        let Some(target) = target else {
+            vm.breakpoints.clear();
+            self.watches.clear();
+            return Ok(());
+        };
+
+        if target.starts_with('$') {
+            let register = parse_register(target)?;
+            let len = self.watches.len();
+            self.watches.retain(|&(watched, _)| watched != register);
+            if self.watches.len() == len {
+                return Err(format!("{target} is not being watched"));
+            }
+        } else {
+            let offset = self
+                .resolve(target)
+                .ok_or(format!("'{target}' is not an offset or a code label"))?;
+            if !vm.breakpoints.remove(&offset) {
+                return Err(format!("There is no breakpoint at {offset}"));
+            }
+        }
+        Ok(())
+    }
+
+    /// Goes back `steps` instructions, returning how many were undone
+    pub fn step_back(&mut self, vm: &mut VM, steps: u64) -> Result<u64, VmError> {
+        let result = vm.step_back(steps);
+        self.sync_watches(vm);
+        result
+    }
+
+    /// Goes back to the last breakpoint that was passed, returning whether there was one
+    pub fn reverse_continue(&mut self, vm: &mut VM) -> Result<bool, VmError> {
+        let result = vm.reverse_continue();
+        self.sync_watches(vm);
+        result
+    }
+
+    /// Takes the current values of the watched registers as their last known values
+    fn sync_watches(&mut self, vm: &VM) {
+        for (register, value) in &mut self.watches {
+            *value = vm.registers[*register];
+        }
+    }
+
+    /// Runs at most `steps` instructions, or until the program stops if there is no
+    /// limit, pausing early at a breakpoint or when a watched register changes
+    pub fn run(&mut self, vm: &mut VM, steps: Option<u64>) -> Stop {
+        let previous = vm.step_hook.take();
+        if !self.watches.is_empty() {
+            let watches = self.watches.clone();
+            vm.step_hook = Some(Box::new(move |vm, _| {
+                watches
+                    .iter()
+                    .any(|&(register, value)| vm.registers[register] != value)
+            }));
+        }
+
+        let result = match steps {
+            Some(steps) => vm.run_for(steps),
+            None => vm.run(),
+        };
+        vm.step_hook = previous;
+
+        let mut changes = vec![];
+        for (register, value) in &mut self.watches {
+            let new = vm.registers[*register];
+            if new != *value {
+                changes.push((*register, *value, new));
+                *value = new;
+            }
+        }
+
+        Stop { result, changes }
+    }
+}
+
+/// Disassembles up to `radius` instructions either side of the pc, marking the pc
+/// with `=>` and breakpoints with `*`. Nothing is shown if the pc is outside the
+/// program, as a jump can leave it anywhere
+pub fn disassemble_around(vm: &VM, radius: usize) -> Vec<String> {
+    if vm.pc >= vm.program.len() {
+        return vec![];
+    }
+
+    let pc = vm.pc - vm.pc % 4;
+    let span = radius.saturating_mul(4);
+    let end = pc
+        .saturating_add(span)
+        .saturating_add(4)
+        .min(vm.program.len());
+    let start = pc.saturating_sub(span).min(end);
+
+    disassemble(&vm.program[start..end], &vm.constants)
+        .into_iter()
+        .enumerate()
+        .map(|(n, line)| {
+            let offset = start + n * 4;
+            let marker = if offset == vm.pc { "=>" } else { "  " };
+            let breakpoint = if vm.breakpoints.contains(&offset) {
+                '*'
+            } else {
+                ' '
+            };
+            format!("{marker}{breakpoint}{offset:04}: {line}")
+        })
+        .collect()
+}
+
+fn parse_register(register: &str) -> Result<usize, String> {
+    register
+        .strip_prefix('$')
+        .and_then(|n| n.parse().ok())
+        .filter(|&n| n < REGISTER_COUNT)
+        .ok_or(format!("'{register}' is not a register"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assembler::assemble_program;
+
+    const SOURCE: &str = "
+        LOAD $0 #3
+        LOAD $1 #1
+    loop:
+        ADD $2 $1 $2
+        SUB $0 $1 $0
+        JNZ @loop
+        HLT
+    ";
+
+    fn debug(source: &str) -> (Debugger, VM) {
+        let program = assemble_program(source).unwrap();
+        let debugger = Debugger {
+            symbols: program.symbols.clone(),
+            ..Debugger::default()
+        };
+        let mut vm = VM::new();
+        vm.load_program(program);
+        (debugger, vm)
+    }
+
+    #[test]
+    fn test_breakpoint() {
+        let (mut debugger, mut vm) = debug(SOURCE);
+
+        assert_eq!(debugger.add_breakpoint(&mut vm, "@loop"), Ok(8));
+        assert_eq!(debugger.add_breakpoint(&mut vm, "16"), Ok(16));
+        assert!(debugger.add_breakpoint(&mut vm, "6").is_err());
+        assert!(debugger.add_breakpoint(&mut vm, "nowhere").is_err());
+
+        assert_eq!(debugger.run(&mut vm, None).result, Ok(ExitStatus::Paused));
+        assert_eq!(vm.pc, 8);
+        assert_eq!(debugger.run(&mut vm, None).result, Ok(ExitStatus::Paused));
+        assert_eq!(vm.pc, 16);
+        assert_eq!(debugger.run(&mut vm, None).result, Ok(ExitStatus::Paused));
+        assert_eq!(vm.pc, 8);
+        assert_eq!(vm.registers[0], 2);
+
+        assert_eq!(debugger.delete(&mut vm, Some("loop")), Ok(()));
+        assert!(debugger.delete(&mut vm, Some("loop")).is_err());
+        assert_eq!(debugger.run(&mut vm, None).result, Ok(ExitStatus::Paused));
+        assert_eq!(vm.pc, 16);
+
+        debugger.delete(&mut vm, None).unwrap();
+        assert_eq!(debugger.run(&mut vm, None).result, Ok(ExitStatus::Halted));
+    }
+
+    #[test]
+    fn test_step() {
+        let (mut debugger, mut vm) = debug(SOURCE);
+        vm.breakpoints.insert(8);
+
+        let stop = debugger.run(&mut vm, Some(1));
+        assert_eq!(stop.result, Ok(ExitStatus::Running));
+        assert_eq!(vm.pc, 4);
+
+        assert_eq!(
+            debugger.run(&mut vm, Some(5)).result,
+            Ok(ExitStatus::Paused)
+        );
+        assert_eq!(vm.pc, 8);
+    }
+
+    #[test]
+    fn test_watch() {
+        let (mut debugger, mut vm) = debug(SOURCE);
+
+        assert_eq!(debugger.add_watch(&vm, "$2"), Ok(2));
+        assert!(debugger.add_watch(&vm, "$32").is_err());
+        assert!(debugger.add_watch(&vm, "2").is_err());
+
+        let stop = debugger.run(&mut vm, None);
+        assert_eq!(stop.result, Ok(ExitStatus::Paused));
+        assert_eq!(stop.changes, vec![(2, 0, 1)]);
+        assert_eq!(vm.pc, 12);
+
+        let stop = debugger.run(&mut vm, None);
+        assert_eq!(stop.changes, vec![(2, 1, 2)]);
+
+        debugger.delete(&mut vm, Some("$2")).unwrap();
+        assert_eq!(debugger.watches().count(), 0);
+        assert_eq!(debugger.run(&mut vm, None).result, Ok(ExitStatus::Halted));
+        assert!(vm.step_hook.is_none());
+    }
+
+    #[test]
+    fn test_reverse_keeps_watches() {
+        let (mut debugger, mut vm) = debug(SOURCE);
+        vm.history = Some(crate::vm::History::new(4, 8));
+        debugger.add_watch(&vm, "$2").unwrap();
+
+        debugger.run(&mut vm, None);
+        debugger.run(&mut vm, None);
+        assert_eq!(vm.registers[2], 2);
+
+        assert_eq!(debugger.step_back(&mut vm, 3), Ok(3));
+        assert_eq!((vm.pc, vm.registers[2]), (12, 1));
+
+        let stop = debugger.run(&mut vm, None);
+        assert_eq!(stop.changes, vec![(2, 1, 2)]);
+        assert_eq!(vm.pc, 12);
+    }
+
+    #[test]
+    fn test_disassemble_around() {
+        let (_, mut vm) = debug(SOURCE);
+        vm.pc = 8;
+        vm.breakpoints.insert(12);
+
+        assert_eq!(
+            disassemble_around(&vm, 1),
+            vec![
+                "   0004: LOAD $1 #1",
+                "=> 0008: ADD $2 $1 $2",
+                "  *0012: SUB $0 $1 $0",
+            ]
+        );
+
+        vm.pc = 20;
+        assert_eq!(
+            disassemble_around(&vm, 2),
+            vec!["  *0012: SUB $0 $1 $0", "   0016: JNZR #-8", "=> 0020: HLT"]
+        );
+    }
+
+    #[test]
+    fn test_disassemble_around_out_of_range() {
+        let (_, mut vm) = debug(SOURCE);
+
+        for pc in [24, 1000, usize::MAX - 3] {
+            vm.pc = pc;
+            assert!(disassemble_around(&vm, 3).is_empty());
+        }
+
+        vm.pc = 0;
+        assert_eq!(disassemble_around(&vm, usize::MAX).len(), 6);
+    }
+}
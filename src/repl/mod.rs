@@ -1,19 +1,40 @@
-use crate::{assembler::assemble, vm::VM};
+use crate::{
+    assembler::{assemble_line, assemble_program, disassemble, render_all},
+    vm::{is_bytecode, read_bytecode, Builtins, ExitStatus, History, VmError, VM},
+};
+use debugger::{disassemble_around, Debugger};
 use std::{
     io::{self, Write},
     num::ParseIntError,
 };
 
+pub mod debugger;
+
+/// How many instructions either side of the pc are shown when execution stops
+const CONTEXT_LINES: usize = 3;
+
 pub struct REPL {
     command_buffer: Vec<String>,
     pub vm: VM,
+    pub debugger: Debugger,
+}
+
+impl Default for REPL {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl REPL {
     pub fn new() -> Self {
+        let mut vm = VM::new();
+        vm.register(Builtins::stdio());
+        vm.history = Some(History::default());
+
         REPL {
             command_buffer: vec![],
-            vm: VM::new(),
+            vm,
+            debugger: Debugger::default(),
         }
     }
 
@@ -27,10 +48,15 @@ impl REPL {
             print!(">>> ");
             io::stdout().flush().expect("Unable to flush stdout!");
 
-            stdin
+            let read = stdin
                 .read_line(&mut buffer)
                 .expect("Unable to read line from stdin!");
 
+            if read == 0 {
+                println!();
+                std::process::exit(0);
+            }
+
             let buffer = buffer.trim();
 
             match buffer {
@@ -44,47 +70,103 @@ impl REPL {
                     }
                 }
                 ".program" => {
-                    for instruction in self.vm.program.chunks(4) {
-                        println!("{:02X?}", instruction);
+                    for (n, line) in disassemble(&self.vm.program, &self.vm.constants)
+                        .into_iter()
+                        .enumerate()
+                    {
+                        println!("{:04}: {}", n * 4, line);
                     }
                 }
-                ".run" => {
-                    self.vm.run();
+                ".run" | ".continue" => self.debug_run(None),
+                ".step" => self.debug_run(Some(1)),
+                ".back" | ".reverse-step" => self.step_back(1),
+                ".reverse-continue" => {
+                    match self.debugger.reverse_continue(&mut self.vm) {
+                        Ok(true) => println!("Breakpoint at {:04}", self.vm.pc),
+                        Ok(false) => println!("Reached the start of the history"),
+                        Err(e) => println!("{e}"),
+                    }
+                    self.print_context();
                 }
-                ".registers" => {
-                    println!("pc: {}", self.vm.pc);
-                    println!("rem: {}", self.vm.remainder);
-                    println!("bool: {}", self.vm.equal_flag);
-                    for (n, value) in self.vm.registers.into_iter().enumerate() {
-                        println!("reg{}: {}", n, value);
+                ".list" => self.print_context(),
+                ".break" => {
+                    for offset in &self.vm.breakpoints {
+                        println!("Breakpoint at {offset:04}");
+                    }
+                    for register in self.debugger.watches() {
+                        println!("Watching ${register}");
                     }
                 }
+                ".delete" => {
+                    if let Err(e) = self.debugger.delete(&mut self.vm, None) {
+                        println!("{e}");
+                    }
+                }
+                ".registers" => {
+                    print_registers(&self.vm);
+                }
                 _ => {
                     if let Some(filename) = buffer.strip_prefix(".load ") {
-                        if let Ok(file) = std::fs::read_to_string(filename) {
-                            if let Ok(instructions) = assemble(&file) {
-                                self.vm.set_program(instructions);
-                                self.vm.pc = 0;
-                            } else {
-                                println!("Failed to assemble program");
-                            }
+                        if let Ok(file) = std::fs::read(filename) {
+                            self.load(&file, filename);
                         } else {
                             println!("Failed to read file");
                         }
+                    } else if let Some(filename) = buffer.strip_prefix(".save ") {
+                        match std::fs::write(filename.trim(), self.vm.save_snapshot()) {
+                            Ok(()) => println!("Saved a snapshot to {}", filename.trim()),
+                            Err(e) => println!("Failed to write file: {e}"),
+                        }
+                    } else if let Some(filename) = buffer.strip_prefix(".restore ") {
+                        self.restore(filename.trim());
+                    } else if let Some(steps) = buffer
+                        .strip_prefix(".back ")
+                        .or_else(|| buffer.strip_prefix(".reverse-step "))
+                    {
+                        match steps.trim().parse() {
+                            Ok(steps) => self.step_back(steps),
+                            Err(_) => println!("Invalid number of steps"),
+                        }
+                    } else if let Some(steps) = buffer.strip_prefix(".step ") {
+                        match steps.trim().parse() {
+                            Ok(steps) => self.debug_run(Some(steps)),
+                            Err(_) => println!("Invalid number of steps"),
+                        }
+                    } else if let Some(target) = buffer.strip_prefix(".break ") {
+                        match self.debugger.add_breakpoint(&mut self.vm, target.trim()) {
+                            Ok(offset) => println!("Breakpoint at {offset:04}"),
+                            Err(e) => println!("{e}"),
+                        }
+                    } else if let Some(register) = buffer.strip_prefix(".watch ") {
+                        match self.debugger.add_watch(&self.vm, register.trim()) {
+                            Ok(register) => println!("Watching ${register}"),
+                            Err(e) => println!("{e}"),
+                        }
+                    } else if let Some(target) = buffer.strip_prefix(".delete ") {
+                        if let Err(e) = self.debugger.delete(&mut self.vm, Some(target.trim())) {
+                            println!("{e}");
+                        }
+                    } else if let Some(reg) = buffer.strip_prefix(".reg f").map(|s| s.trim()) {
+                        match reg
+                            .parse::<usize>()
+                            .ok()
+                            .and_then(|n| self.vm.float_registers.get(n))
+                        {
+                            Some(value) => println!("f{}: {}", reg, value),
+                            None => println!("Invalid register number"),
+                        }
                     } else if let Some(reg) = buffer.strip_prefix(".reg").map(|s| s.trim()) {
                         if let Ok(reg) = reg.parse::<usize>() {
                             println!("reg{}: {}", reg, self.vm.registers[reg]);
                         } else {
                             println!("Invalid register number");
                         }
-                    } else if let Ok(instruction) = assemble(buffer) {
-                        self.vm
-                            .program
-                            .append(&mut instruction.into_iter().flatten().collect());
-                        self.vm.run_once();
+                    } else if let Ok(instruction) =
+                        assemble_line(buffer, self.vm.program.len() as u32, &mut self.vm.constants)
+                    {
+                        report(self.execute(instruction.into_iter().flatten().collect()));
                     } else if let Ok(instruction) = parse_hex(buffer) {
-                        self.vm.program.append(&mut instruction.clone());
-                        self.vm.run_once();
+                        report(self.execute(instruction));
                     } else {
                         println!("Invalid input");
                     }
@@ -94,6 +176,114 @@ impl REPL {
             self.command_buffer.push(buffer.to_owned());
         }
     }
+
+    /// Loads either a bytecode file or assembly source into the VM
+    fn load(&mut self, file: &[u8], filename: &str) {
+        let program = if is_bytecode(file) {
+            match read_bytecode(file) {
+                Ok(program) => program,
+                Err(e) => return println!("Failed to load bytecode: {e}"),
+            }
+        } else if let Ok(source) = std::str::from_utf8(file) {
+            match assemble_program(source) {
+                Ok(program) => program,
+                Err(diagnostics) => {
+                    return println!("{}", render_all(&diagnostics, source, filename))
+                }
+            }
+        } else {
+            return println!("Failed to assemble program");
+        };
+
+        self.debugger.symbols = program.symbols.clone();
+        self.vm.load_program(program);
+    }
+
+    /// Replaces the VM's state and program with a snapshot file. The snapshot has no
+    /// symbols, so the labels of the previously loaded program are forgotten
+    fn restore(&mut self, filename: &str) {
+        let file = match std::fs::read(filename) {
+            Ok(file) => file,
+            Err(_) => return println!("Failed to read file"),
+        };
+        match self.vm.restore_snapshot(&file) {
+            Ok(()) => {
+                self.debugger.symbols.clear();
+                println!("Restored a snapshot from {filename}");
+                self.print_context();
+            }
+            Err(e) => println!("Failed to restore snapshot: {e}"),
+        }
+    }
+
+    /// Appends instructions typed at the prompt to the program and runs them. One line
+    /// can assemble to more than one word, such as a `LOAD` too wide for one
+    fn execute(&mut self, bytes: Vec<u8>) -> Result<ExitStatus, VmError> {
+        let words = bytes.len().div_ceil(4) as u64;
+        self.vm.program.extend(bytes);
+        self.vm.run_for(words)
+    }
+
+    /// Runs under the debugger, then shows why execution stopped and where
+    fn debug_run(&mut self, steps: Option<u64>) {
+        let stop = self.debugger.run(&mut self.vm, steps);
+
+        for (register, old, new) in &stop.changes {
+            println!("${register} changed from {old} to {new}");
+        }
+        match stop.result {
+            Ok(ExitStatus::Paused) if stop.changes.is_empty() => {
+                println!("Breakpoint at {:04}", self.vm.pc)
+            }
+            Ok(ExitStatus::Paused) => {}
+            result => report(result),
+        }
+
+        self.print_context();
+    }
+
+    /// Goes back up to `steps` instructions and shows where execution is now
+    fn step_back(&mut self, steps: u64) {
+        match self.debugger.step_back(&mut self.vm, steps) {
+            Ok(undone) if undone < steps => {
+                println!("Went back {undone} steps to the start of the history")
+            }
+            Ok(_) => {}
+            Err(e) => println!("{e}"),
+        }
+        self.print_context();
+    }
+
+    fn print_context(&self) {
+        for line in disassemble_around(&self.vm, CONTEXT_LINES) {
+            println!("{line}");
+        }
+    }
+}
+
+/// Prints the pc, flags, stack depth and every register of the VM
+pub fn print_registers(vm: &VM) {
+    println!("pc: {}", vm.pc);
+    println!("rem: {}", vm.remainder);
+    println!("flags: {}", vm.flags);
+    println!("sp: {}", vm.stack.len());
+    for (n, value) in vm.registers.into_iter().enumerate() {
+        println!("reg{}: {}", n, value);
+    }
+    for (n, value) in vm.float_registers.into_iter().enumerate() {
+        println!("f{}: {}", n, value);
+    }
+}
+
+/// Prints the outcome of running the VM, staying quiet while it is still running
+fn report(result: Result<ExitStatus, VmError>) {
+    match result {
+        Ok(ExitStatus::Halted) => println!("HLT encountered."),
+        Ok(ExitStatus::Running) => {}
+        Ok(ExitStatus::Exited(code)) => println!("Exited with status {code}."),
+        Ok(ExitStatus::Paused) => println!("Paused."),
+        Err(e) => println!("{e}"),
+    }
 }
 
 fn parse_hex(input: &str) -> Result<Vec<u8>, ParseIntError> {
@@ -117,4 +307,16 @@ mod tests {
 
         assert!(result.is_err());
     }
+
+    #[test]
+    fn test_execute_wide_load() {
+        let mut repl = REPL::new();
+        let words = assemble_line("LOAD $0 #70000", 0, &mut repl.vm.constants).unwrap();
+        assert_eq!(words.len(), 2);
+
+        let result = repl.execute(words.into_iter().flatten().collect());
+        assert_eq!(result, Ok(ExitStatus::Running));
+        assert_eq!(repl.vm.registers[0], 70000);
+        assert_eq!(repl.vm.pc, 8);
+    }
 }
@@ -0,0 +1,114 @@
+use std::{fmt::Display, str::FromStr};
+
+use crate::opcode::Opcode;
+
+/// How `ADD`, `SUB`, `MUL` and `DIV` handle results that do not fit in an `i32`
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ArithmeticMode {
+    /// Wrap around in two's complement
+    Wrapping,
+    /// Stop with [`VmError::ArithmeticOverflow`](super::VmError::ArithmeticOverflow)
+    #[default]
+    Trapping,
+    /// Clamp to `i32::MIN` or `i32::MAX`
+    Saturating,
+}
+
+impl FromStr for ArithmeticMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "wrapping" => Ok(ArithmeticMode::Wrapping),
+            "trapping" => Ok(ArithmeticMode::Trapping),
+            "saturating" => Ok(ArithmeticMode::Saturating),
+            _ => Err(format!("'{s}' is not an arithmetic mode")),
+        }
+    }
+}
+
+impl Display for ArithmeticMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ArithmeticMode::Wrapping => write!(f, "wrapping"),
+            ArithmeticMode::Trapping => write!(f, "trapping"),
+            ArithmeticMode::Saturating => write!(f, "saturating"),
+        }
+    }
+}
+
+/// Every possible result of an arithmetic instruction, so that the VM can pick one
+/// based on its [`ArithmeticMode`]
+pub struct Evaluation {
+    pub wrapped: i32,
+    pub saturated: i32,
+    /// Unsigned carry out of, or borrow into, the top bit
+    pub carry: bool,
+    /// The signed result does not fit in an `i32`
+    pub overflow: bool,
+}
+
+/// Evaluates `ADD`, `SUB`, `MUL` or `DIV`. The divisor must not be zero
+pub fn evaluate(opcode: Opcode, a: i32, b: i32) -> Evaluation {
+    let (ua, ub) = (a as u32, b as u32);
+    let ((wrapped, overflow), saturated, carry) = match opcode {
+        Opcode::ADD => (
+            a.overflowing_add(b),
+            a.saturating_add(b),
+            ua.overflowing_add(ub).1,
+        ),
+        Opcode::SUB => (a.overflowing_sub(b), a.saturating_sub(b), ua < ub),
+        Opcode::MUL => (
+            a.overflowing_mul(b),
+            a.saturating_mul(b),
+            ua.overflowing_mul(ub).1,
+        ),
+        _ => (a.overflowing_div(b), a.saturating_div(b), false),
+    };
+
+    Evaluation {
+        wrapped,
+        saturated,
+        carry,
+        overflow,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_evaluate_carry() {
+        let evaluation = evaluate(Opcode::ADD, -1, 1);
+
+        assert_eq!(evaluation.wrapped, 0);
+        assert!(evaluation.carry);
+        assert!(!evaluation.overflow);
+    }
+
+    #[test]
+    fn test_evaluate_overflow() {
+        let evaluation = evaluate(Opcode::SUB, i32::MIN, 1);
+
+        assert_eq!(evaluation.wrapped, i32::MAX);
+        assert_eq!(evaluation.saturated, i32::MIN);
+        assert!(!evaluation.carry);
+        assert!(evaluation.overflow);
+    }
+
+    #[test]
+    fn test_evaluate_div_overflow() {
+        let evaluation = evaluate(Opcode::DIV, i32::MIN, -1);
+
+        assert_eq!(evaluation.wrapped, i32::MIN);
+        assert_eq!(evaluation.saturated, i32::MAX);
+        assert!(evaluation.overflow);
+    }
+
+    #[test]
+    fn test_arithmetic_mode_from_str() {
+        assert_eq!("saturating".parse(), Ok(ArithmeticMode::Saturating));
+        assert!("clamping".parse::<ArithmeticMode>().is_err());
+    }
+}
@@ -0,0 +1,206 @@
+use super::VmError;
+use crate::opcode::{JumpTarget, Opcode};
+
+/// The number of integer registers, which is also the number of float registers
+pub const REGISTER_COUNT: usize = 32;
+
+/// An instruction word whose register operands have been checked, so that it can be
+/// executed without any further validation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Decoded {
+    pub opcode: Opcode,
+    /// The three operand bytes of the word
+    pub a: u8,
+    pub b: u8,
+    pub c: u8,
+    /// The value of a `LOAD`, `LOADHI`, `LOADLO`, `LOADF` or `SYSCALL` immediate, or
+    /// the address that a relative or absolute jump goes to
+    pub immediate: i32,
+}
+
+/// Decodes the instruction word at `offset`
+pub fn decode(offset: usize, word: [u8; 4]) -> Result<Decoded, VmError> {
+    let [opcode, a, b, c] = word;
+    let opcode = Opcode::try_from(opcode).map_err(|_| VmError::UnknownOpcode {
+        offset,
+        byte: opcode,
+    })?;
+
+    for &position in register_operands(opcode) {
+        let register = word[position];
+        if register as usize >= REGISTER_COUNT {
+            return Err(VmError::InvalidRegister {
+                offset: offset + position,
+                register,
+            });
+        }
+    }
+
+    let immediate = match (opcode, opcode.jump()) {
+        (Opcode::LOAD, _) => i16::from_be_bytes([b, c]) as i32,
+        (Opcode::LOADHI | Opcode::LOADLO | Opcode::LOADF, _) => u16::from_be_bytes([b, c]) as i32,
+        (Opcode::SYSCALL, _) => u16::from_be_bytes([a, b]) as i32,
+        (_, Some((_, JumpTarget::Relative))) => {
+            (offset as i32).wrapping_add(i16::from_be_bytes([a, b]) as i32)
+        }
+        (_, Some((_, JumpTarget::Absolute))) => i32::from_be_bytes([0, a, b, c]),
+        _ => 0,
+    };
+
+    Ok(Decoded {
+        opcode,
+        a,
+        b,
+        c,
+        immediate,
+    })
+}
+
+/// The positions in an instruction word that hold integer or float registers
+fn register_operands(opcode: Opcode) -> &'static [usize] {
+    use Opcode as O;
+    match opcode {
+        O::LOAD
+        | O::LOADHI
+        | O::LOADLO
+        | O::LOADF
+        | O::JMP
+        | O::JMPF
+        | O::JMPB
+        | O::JEQ
+        | O::JNEQ
+        | O::JZ
+        | O::JNZ
+        | O::JLT
+        | O::JGE
+        | O::JGT
+        | O::JLE
+        | O::JC
+        | O::JNC
+        | O::JO
+        | O::JNO
+        | O::ALOC
+        | O::PUSH
+        | O::POP
+        | O::CALL => &[1],
+        O::EQ
+        | O::NEQ
+        | O::GT
+        | O::LT
+        | O::GTQ
+        | O::LTQ
+        | O::CMP
+        | O::LOADB
+        | O::LOADH
+        | O::LOADW
+        | O::STOREB
+        | O::STOREH
+        | O::STOREW
+        | O::NOT
+        | O::EQF
+        | O::NEQF
+        | O::GTF
+        | O::LTF
+        | O::GTQF
+        | O::LTQF
+        | O::ITOF
+        | O::FTOI => &[1, 2],
+        O::ADD
+        | O::SUB
+        | O::MUL
+        | O::DIV
+        | O::AND
+        | O::OR
+        | O::XOR
+        | O::SHL
+        | O::SHR
+        | O::SAR
+        | O::ADDF
+        | O::SUBF
+        | O::MULF
+        | O::DIVF => &[1, 2, 3],
+        O::SHLI | O::SHRI | O::SARI => &[1, 3],
+        _ => &[],
+    }
+}
+
+/// Every whole word of a program decoded ahead of time, so that a loop only decodes
+/// its instructions once. Words that fail to decode are left out, and are decoded
+/// again to report their error if they are ever executed
+#[derive(Debug, Default)]
+pub struct DecodeCache {
+    words: Vec<Option<Decoded>>,
+    /// The length of the program the cache was built from
+    len: usize,
+}
+
+impl DecodeCache {
+    pub fn new(program: &[u8]) -> DecodeCache {
+        let words = program
+            .chunks_exact(4)
+            .enumerate()
+            .map(|(n, word)| decode(n * 4, [word[0], word[1], word[2], word[3]]).ok())
+            .collect();
+
+        DecodeCache {
+            words,
+            len: program.len(),
+        }
+    }
+
+    /// Whether the cache was built from a program of this length. Appending to a
+    /// program always changes its length
+    pub fn matches(&self, program: &[u8]) -> bool {
+        self.len == program.len()
+    }
+
+    /// The decoded word at `pc`, if `pc` is the start of a word that decoded
+    pub fn get(&self, pc: usize) -> Option<Decoded> {
+        if pc.is_multiple_of(4) {
+            self.words.get(pc / 4).copied().flatten()
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_immediates() {
+        assert_eq!(decode(0, [1, 2, 255, 254]).unwrap().immediate, -2);
+        assert_eq!(decode(0, [28, 2, 255, 254]).unwrap().immediate, 0xFFFE);
+        assert_eq!(decode(8, [64, 255, 248, 0]).unwrap().immediate, 0);
+        assert_eq!(decode(8, [65, 1, 2, 4]).unwrap().immediate, 0x10204);
+    }
+
+    #[test]
+    fn test_decode_invalid_register() {
+        assert_eq!(
+            decode(8, [2, 0, 32, 0]),
+            Err(VmError::InvalidRegister {
+                offset: 10,
+                register: 32
+            })
+        );
+        assert!(decode(8, [37, 0, 32, 0]).is_ok());
+    }
+
+    #[test]
+    fn test_decode_cache() {
+        let program = [0, 0, 0, 0, 255, 0, 0, 0, 1, 0];
+        let cache = DecodeCache::new(&program);
+
+        assert_eq!(
+            cache.get(0).map(|decoded| decoded.opcode),
+            Some(Opcode::HLT)
+        );
+        assert_eq!(cache.get(4), None);
+        assert_eq!(cache.get(2), None);
+        assert_eq!(cache.get(8), None);
+        assert!(cache.matches(&program));
+        assert!(!cache.matches(&program[..8]));
+    }
+}
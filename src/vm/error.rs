@@ -0,0 +1,155 @@
+use std::{error::Error, fmt::Display};
+
+/// How a successfully executed instruction left the VM
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExitStatus {
+    /// An `HLT` instruction was executed
+    Halted,
+    /// The instruction completed and execution can continue
+    Running,
+    /// A host function stopped the program with this status code
+    Exited(i32),
+    /// A breakpoint or the step hook stopped execution before the instruction at the pc
+    Paused,
+}
+
+/// An error raised while executing a program
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VmError {
+    UnknownOpcode {
+        offset: usize,
+        byte: u8,
+    },
+    PcOutOfBounds {
+        pc: usize,
+        len: usize,
+    },
+    InvalidRegister {
+        offset: usize,
+        register: u8,
+    },
+    DivideByZero {
+        offset: usize,
+    },
+    ArithmeticOverflow {
+        offset: usize,
+    },
+    TruncatedOperands {
+        offset: usize,
+    },
+    InvalidAllocation {
+        offset: usize,
+        size: i32,
+    },
+    /// Growing the heap by `size` bytes would take it past the VM's heap limit
+    HeapLimitExceeded {
+        offset: usize,
+        size: usize,
+    },
+    MemoryOutOfBounds {
+        offset: usize,
+        address: i32,
+        width: usize,
+    },
+    StackOverflow {
+        offset: usize,
+    },
+    StackUnderflow {
+        offset: usize,
+    },
+    InvalidConstant {
+        offset: usize,
+        index: u16,
+    },
+    MisalignedJump {
+        offset: usize,
+        target: usize,
+    },
+    /// The jump at `offset` targets an address before the start of the program
+    NegativeJump {
+        offset: usize,
+        target: i64,
+    },
+    /// The instruction at `offset` needed more fuel than was left. It has not been
+    /// executed, so adding fuel and running again resumes from it
+    OutOfFuel {
+        offset: usize,
+    },
+    UnknownSyscall {
+        offset: usize,
+        number: u16,
+    },
+    SyscallFailed {
+        offset: usize,
+        number: u16,
+        message: String,
+    },
+}
+
+impl Display for VmError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        use VmError as VE;
+        match self {
+            VE::UnknownOpcode { offset, byte } => {
+                write!(f, "Unrecognized opcode {byte} at offset {offset}")
+            }
+            VE::PcOutOfBounds { pc, len } => write!(
+                f,
+                "Program counter {pc} has exceeded program length {len}! Did you forget to include an HLT?"
+            ),
+            VE::InvalidRegister { offset, register } => write!(
+                f,
+                "Register {register} at offset {offset} does not exist"
+            ),
+            VE::DivideByZero { offset } => write!(f, "Division by zero at offset {offset}"),
+            VE::ArithmeticOverflow { offset } => {
+                write!(f, "Arithmetic overflow at offset {offset}")
+            }
+            VE::TruncatedOperands { offset } => write!(
+                f,
+                "Program ended at offset {offset} while reading instruction operands"
+            ),
+            VE::InvalidAllocation { offset, size } => {
+                write!(f, "Cannot allocate {size} bytes at offset {offset}")
+            }
+            VE::HeapLimitExceeded { offset, size } => write!(
+                f,
+                "Allocating {size} bytes at offset {offset} would exceed the heap limit"
+            ),
+            VE::MemoryOutOfBounds {
+                offset,
+                address,
+                width,
+            } => write!(
+                f,
+                "Access of {width} bytes at address {address} is out of bounds at offset {offset}"
+            ),
+            VE::StackOverflow { offset } => write!(f, "Stack overflow at offset {offset}"),
+            VE::StackUnderflow { offset } => write!(f, "Stack underflow at offset {offset}"),
+            VE::InvalidConstant { offset, index } => write!(
+                f,
+                "Constant {index} at offset {offset} is not in the constant pool"
+            ),
+            VE::MisalignedJump { offset, target } => write!(
+                f,
+                "Jump at offset {offset} targets {target}, which is not the start of an instruction"
+            ),
+            VE::NegativeJump { offset, target } => write!(
+                f,
+                "Jump at offset {offset} targets {target}, which is before the start of the program"
+            ),
+            VE::OutOfFuel { offset } => write!(f, "Ran out of fuel at offset {offset}"),
+            VE::UnknownSyscall { offset, number } => write!(
+                f,
+                "No host function handles syscall {number} at offset {offset}"
+            ),
+            VE::SyscallFailed {
+                offset,
+                number,
+                message,
+            } => write!(f, "Syscall {number} at offset {offset} failed: {message}"),
+        }
+    }
+}
+
+impl Error for VmError {}
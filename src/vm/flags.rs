@@ -0,0 +1,107 @@
+use std::fmt::Display;
+
+/// The condition flags register. Arithmetic and `CMP` set every flag from their
+/// result, with `CONDITION` matching `ZERO`. The boolean comparisons (`EQ`, `GT`,
+/// `LTF`, ...) set `ZNCV` as `CMP` would for their operands, so that `JGT`, `JLE` and
+/// the other conditional jumps work after them, and set `CONDITION` when the
+/// comparison holds for `JEQ` and `JNEQ` to branch on
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Flags(u8);
+
+impl Flags {
+    /// The result was zero, or the compared values were equal
+    pub const ZERO: u8 = 1 << 0;
+    /// The top bit of the result was set
+    pub const NEGATIVE: u8 = 1 << 1;
+    /// An unsigned result carried out of, or borrowed into, the top bit
+    pub const CARRY: u8 = 1 << 2;
+    /// The signed result did not fit in an `i32`
+    pub const OVERFLOW: u8 = 1 << 3;
+    /// The last boolean comparison held, or the result was zero
+    pub const CONDITION: u8 = 1 << 4;
+
+    pub fn from_bits(bits: u8) -> Flags {
+        Flags(bits & (Self::ZERO | Self::NEGATIVE | Self::CARRY | Self::OVERFLOW | Self::CONDITION))
+    }
+
+    pub fn bits(self) -> u8 {
+        self.0
+    }
+
+    /// Whether every flag in `flags` is set
+    pub fn contains(self, flags: u8) -> bool {
+        self.0 & flags == flags
+    }
+
+    pub fn set(&mut self, flags: u8, value: bool) {
+        if value {
+            self.0 |= flags;
+        } else {
+            self.0 &= !flags;
+        }
+    }
+
+    /// The flags for an integer `result`, with carry and overflow from the operation
+    /// that produced it
+    pub fn from_result(result: i32, carry: bool, overflow: bool) -> Flags {
+        let mut flags = Flags::default();
+        flags.set(Self::ZERO | Self::CONDITION, result == 0);
+        flags.set(Self::NEGATIVE, result < 0);
+        flags.set(Self::CARRY, carry);
+        flags.set(Self::OVERFLOW, overflow);
+        flags
+    }
+
+    /// The flags for comparing floats as `CMP` compares integers. An unordered
+    /// comparison, with a NaN, sets `OVERFLOW` and so counts as less
+    pub fn from_float_comparison(a: f64, b: f64) -> Flags {
+        let mut flags = Flags::default();
+        flags.set(Self::ZERO | Self::CONDITION, a == b);
+        flags.set(Self::NEGATIVE | Self::CARRY, a < b);
+        flags.set(Self::OVERFLOW, a.is_nan() || b.is_nan());
+        flags
+    }
+
+    /// Whether the last `CMP a b` found `a < b` as signed integers
+    pub fn less(self) -> bool {
+        self.contains(Self::NEGATIVE) != self.contains(Self::OVERFLOW)
+    }
+}
+
+/// Shows set flags as upper case letters and clear ones as `-`, in `ZNCVT` order
+/// where `T` is `CONDITION`
+impl Display for Flags {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (flag, letter) in [
+            (Self::ZERO, 'Z'),
+            (Self::NEGATIVE, 'N'),
+            (Self::CARRY, 'C'),
+            (Self::OVERFLOW, 'V'),
+            (Self::CONDITION, 'T'),
+        ] {
+            let c = if self.contains(flag) { letter } else { '-' };
+            write!(f, "{c}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_result() {
+        let flags = Flags::from_result(-3, true, false);
+
+        assert!(!flags.contains(Flags::ZERO));
+        assert!(flags.contains(Flags::NEGATIVE | Flags::CARRY));
+        assert!(!flags.contains(Flags::OVERFLOW));
+        assert_eq!(flags.to_string(), "-NC--");
+    }
+
+    #[test]
+    fn test_from_bits_ignores_unknown_bits() {
+        assert_eq!(Flags::from_bits(0xFF).bits(), 0b11111);
+    }
+}
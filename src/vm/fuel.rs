@@ -0,0 +1,36 @@
+use crate::opcode::Opcode;
+
+/// How much fuel each opcode uses when it is executed. Every opcode costs 1 until it
+/// is given another cost
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FuelCosts([u32; 256]);
+
+impl Default for FuelCosts {
+    fn default() -> Self {
+        FuelCosts([1; 256])
+    }
+}
+
+impl FuelCosts {
+    pub fn get(&self, opcode: Opcode) -> u32 {
+        self.0[u8::from(opcode) as usize]
+    }
+
+    pub fn set(&mut self, opcode: Opcode, cost: u32) {
+        self.0[u8::from(opcode) as usize] = cost;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fuel_costs() {
+        let mut costs = FuelCosts::default();
+        costs.set(Opcode::ALOC, 10);
+
+        assert_eq!(costs.get(Opcode::ALOC), 10);
+        assert_eq!(costs.get(Opcode::ADD), 1);
+    }
+}
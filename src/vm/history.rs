@@ -0,0 +1,366 @@
+use std::collections::VecDeque;
+
+use super::{Snapshot, VmError, VM};
+
+/// The default number of instructions between snapshots
+pub const DEFAULT_SNAPSHOT_INTERVAL: u64 = 1000;
+/// The default number of snapshots kept, which bounds how far back the VM can go
+pub const DEFAULT_SNAPSHOT_LIMIT: usize = 64;
+/// The default number of bytes the snapshots may take up between them
+pub const DEFAULT_SNAPSHOT_MEMORY: usize = 64 * 1024 * 1024;
+
+/// A record of execution that lets the VM step backwards. A snapshot is taken every
+/// `interval` instructions and after every `SYSCALL`, and going back restores the
+/// closest one before the target and replays forward from it, so replaying never
+/// calls the host again. Only the pc of each instruction is logged in between.
+///
+/// Every snapshot is a full copy of the heap and stack, so `limit` snapshots of a
+/// program with a 16 MiB heap would take 1 GiB. The oldest snapshots are dropped
+/// once there are more than `limit` of them or they take up more than
+/// `memory_limit` bytes, so memory use is bounded by `memory_limit` plus
+/// `interval` × `limit` pcs rather than by how long the program has run. The most
+/// recent snapshot is always kept, even if it alone is over the budget
+#[derive(Debug)]
+pub struct History {
+    interval: u64,
+    limit: usize,
+    /// The most bytes the snapshots may take up between them
+    pub memory_limit: usize,
+    /// How many bytes the snapshots take up
+    memory: usize,
+    /// How many instructions have been executed since recording started
+    step: u64,
+    /// Snapshots with the step they were taken at, oldest first
+    snapshots: VecDeque<(u64, Snapshot)>,
+    /// The pc of every step since the oldest snapshot
+    pcs: VecDeque<usize>,
+}
+
+impl Default for History {
+    fn default() -> Self {
+        History::new(DEFAULT_SNAPSHOT_INTERVAL, DEFAULT_SNAPSHOT_LIMIT)
+    }
+}
+
+impl History {
+    /// Records a snapshot every `interval` steps, keeping at most `limit` of them and
+    /// at most [`DEFAULT_SNAPSHOT_MEMORY`] bytes of them
+    pub fn new(interval: u64, limit: usize) -> History {
+        History {
+            interval: interval.max(1),
+            limit: limit.max(1),
+            memory_limit: DEFAULT_SNAPSHOT_MEMORY,
+            memory: 0,
+            step: 0,
+            snapshots: VecDeque::new(),
+            pcs: VecDeque::new(),
+        }
+    }
+
+    /// How many instructions have been executed since recording started
+    pub fn step(&self) -> u64 {
+        self.step
+    }
+
+    /// Forgets everything recorded so far, keeping the interval and limits
+    pub fn clear(&mut self) {
+        *self = History {
+            memory_limit: self.memory_limit,
+            ..History::new(self.interval, self.limit)
+        };
+    }
+
+    /// The earliest step the VM can go back to
+    pub fn earliest(&self) -> u64 {
+        self.snapshots.front().map_or(self.step, |&(step, _)| step)
+    }
+
+    /// Takes the first snapshot, before anything has been executed
+    pub(super) fn start(&mut self, vm: &VM) {
+        if self.snapshots.is_empty() {
+            self.push(vm.snapshot());
+        }
+    }
+
+    /// Logs the instruction at `offset`, which has just been executed
+    pub(super) fn record(&mut self, vm: &VM, offset: usize, syscall: bool) {
+        self.pcs.push_back(offset);
+        self.step += 1;
+
+        if syscall || self.step.is_multiple_of(self.interval) {
+            self.push(vm.snapshot());
+            while self.snapshots.len() > 1
+                && (self.snapshots.len() > self.limit || self.memory > self.memory_limit)
+            {
+                let (_, dropped) = self.snapshots.pop_front().unwrap();
+                self.memory -= snapshot_size(&dropped);
+            }
+            let dropped = self.pcs.len() - (self.step - self.earliest()) as usize;
+            self.pcs.drain(..dropped);
+        }
+    }
+
+    /// Adds a snapshot of the current step
+    fn push(&mut self, snapshot: Snapshot) {
+        self.memory += snapshot_size(&snapshot);
+        self.snapshots.push_back((self.step, snapshot));
+    }
+
+    /// The pc the VM had at `step`
+    fn pc_at(&self, step: u64) -> usize {
+        self.pcs[(step - self.earliest()) as usize]
+    }
+
+    /// Forgets everything after `step`
+    fn truncate(&mut self, step: u64) {
+        self.pcs.truncate((step - self.earliest()) as usize);
+        self.snapshots.retain(|&(taken, _)| taken <= step);
+        self.memory = self.snapshots.iter().map(|(_, s)| snapshot_size(s)).sum();
+        self.step = step;
+    }
+}
+
+/// Roughly how many bytes `snapshot` takes up, counting its heap and stack
+fn snapshot_size(snapshot: &Snapshot) -> usize {
+    std::mem::size_of::<Snapshot>() + snapshot.heap.len() + 4 * snapshot.stack.len()
+}
+
+impl VM {
+    /// Goes back `steps` instructions, or as far as the history allows, returning how
+    /// many steps were undone. Running forward again afterwards executes the program
+    /// afresh, including its syscalls
+    pub fn step_back(&mut self, steps: u64) -> Result<u64, VmError> {
+        let Some(history) = &self.history else {
+            return Ok(0);
+        };
+        let target = history.step.saturating_sub(steps).max(history.earliest());
+        let undone = history.step - target;
+
+        self.go_to(target)?;
+        Ok(undone)
+    }
+
+    /// Goes back to the most recent step that was at a breakpoint, or to the earliest
+    /// step in the history if there is none. Returns whether a breakpoint was found
+    pub fn reverse_continue(&mut self) -> Result<bool, VmError> {
+        let Some(history) = &self.history else {
+            return Ok(false);
+        };
+        let found = (history.earliest()..history.step)
+            .rev()
+            .find(|&step| self.breakpoints.contains(&history.pc_at(step)));
+        let target = found.unwrap_or(history.earliest());
+
+        self.go_to(target)?;
+        Ok(found.is_some())
+    }
+
+    /// Restores the last snapshot at or before `target` and replays up to it. Replaying
+    /// uses no fuel, and if it fails the VM is left at the restored snapshot
+    fn go_to(&mut self, target: u64) -> Result<(), VmError> {
+        let Some(mut history) = self.history.take() else {
+            return Ok(());
+        };
+
+        let Some((start, snapshot)) = history
+            .snapshots
+            .iter()
+            .rev()
+            .find(|&&(step, _)| step <= target)
+            .cloned()
+        else {
+            // Nothing has been recorded yet
+            self.history = Some(history);
+            return Ok(());
+        };
+        self.restore(&snapshot);
+        history.truncate(target);
+
+        let fuel = self.fuel.take();
+        self.refresh_cache();
+        let mut result = Ok(());
+        for _ in start..target {
+            if let Err(e) = self.execute_instruction() {
+                self.restore(&snapshot);
+                history.truncate(start);
+                result = Err(e);
+                break;
+            }
+        }
+        self.fuel = fuel;
+
+        self.history = Some(history);
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        assembler::assemble_program,
+        vm::{Builtins, ExitStatus},
+    };
+
+    /// Counts `$0` down from 10, pushing each value and adding it into `$2`
+    const SOURCE: &str = "
+        LOAD $0 #10
+        LOAD $1 #1
+    loop:
+        PUSH $0
+        ADD $2 $0 $2
+        SUB $0 $1 $0
+        JNZ @loop
+        HLT
+    ";
+
+    fn recording_vm(interval: u64, limit: usize) -> VM {
+        let mut vm = VM::new();
+        vm.history = Some(History::new(interval, limit));
+        vm.load_program(assemble_program(SOURCE).unwrap());
+        vm
+    }
+
+    #[test]
+    fn test_step_back() {
+        let mut vm = recording_vm(3, 100);
+        let mut states = vec![vm.snapshot()];
+        while vm.run_once() == Ok(ExitStatus::Running) {
+            states.push(vm.snapshot());
+        }
+        states.push(vm.snapshot());
+        let steps = states.len() as u64 - 1;
+        assert_eq!(vm.history.as_ref().unwrap().step(), steps);
+
+        for back in [1, 4, 1, 7] {
+            let step = vm.history.as_ref().unwrap().step();
+            assert_eq!(vm.step_back(back), Ok(back));
+            assert_eq!(vm.snapshot(), states[(step - back) as usize]);
+        }
+
+        assert_eq!(vm.run(), Ok(ExitStatus::Halted));
+        assert_eq!(vm.snapshot(), states[steps as usize]);
+        assert_eq!(vm.step_back(1000), Ok(steps));
+        assert_eq!(vm.snapshot(), states[0]);
+    }
+
+    #[test]
+    fn test_step_back_without_history() {
+        let mut vm = recording_vm(3, 100);
+        assert_eq!(vm.step_back(1), Ok(0));
+        assert_eq!(vm.reverse_continue(), Ok(false));
+
+        vm.history = None;
+        vm.run_once().unwrap();
+        assert_eq!(vm.step_back(1), Ok(0));
+        assert_eq!(vm.registers[0], 10);
+    }
+
+    #[test]
+    fn test_snapshot_limit() {
+        let mut vm = recording_vm(4, 2);
+        assert_eq!(vm.run(), Ok(ExitStatus::Halted));
+
+        let history = vm.history.as_ref().unwrap();
+        let step = history.step();
+        assert_eq!(history.earliest(), step - step % 4 - 4);
+        assert!(history.pcs.len() < 12);
+        assert_eq!(vm.step_back(1000), Ok(4 + step % 4));
+    }
+
+    #[test]
+    fn test_reverse_continue() {
+        let mut vm = recording_vm(5, 100);
+        vm.breakpoints.insert(12);
+        assert_eq!(vm.run(), Ok(ExitStatus::Paused));
+        assert_eq!(vm.run(), Ok(ExitStatus::Paused));
+        assert_eq!(vm.registers[0], 9);
+
+        assert_eq!(vm.reverse_continue(), Ok(true));
+        assert_eq!((vm.pc, vm.registers[0], vm.registers[2]), (12, 10, 0));
+        assert_eq!(vm.reverse_continue(), Ok(false));
+        assert_eq!((vm.pc, vm.registers[0]), (0, 0));
+    }
+
+    #[test]
+    fn test_replay_skips_syscalls() {
+        let mut vm = VM::new();
+        vm.history = Some(History::new(100, 10));
+        vm.register(Builtins::new(&b"12\n"[..], std::io::sink()));
+        vm.heap = vec![0; 4];
+        vm.set_program(vec![
+            [1, 1, 0, 4],  // Set reg1 to 4
+            [90, 0, 3, 0], // Read a line into the heap
+            [1, 3, 0, 1],  // Set reg3 to 1
+            [0, 0, 0, 0],  // Halt
+        ]);
+
+        assert_eq!(vm.run(), Ok(ExitStatus::Halted));
+        assert_eq!(vm.step_back(2), Ok(2));
+        assert_eq!((vm.pc, vm.registers[0]), (8, 2));
+        assert_eq!(vm.heap, b"12\0\0");
+    }
+
+    #[test]
+    fn test_syscall_snapshots_count_toward_limit() {
+        let mut vm = VM::new();
+        vm.history = Some(History::new(1000, 4));
+        vm.register(Builtins::new(std::io::empty(), std::io::sink()));
+        vm.set_program(vec![
+            [1, 0, 0, 20], // Set reg0 to 20
+            [1, 1, 0, 1],  // Set reg1 to 1
+            [90, 0, 1, 0], // Print reg0
+            [3, 0, 1, 0],  // Set reg0 to reg0 - reg1
+            [73, 0, 0, 8], // Jump to 8 if reg0 is not zero
+            [0, 0, 0, 0],  // Halt
+        ]);
+
+        assert_eq!(vm.run(), Ok(ExitStatus::Halted));
+        let history = vm.history.as_ref().unwrap();
+        assert_eq!(history.snapshots.len(), 4);
+        assert_eq!(
+            history.pcs.len() as u64,
+            history.step() - history.earliest()
+        );
+
+        assert_eq!(vm.step_back(6), Ok(6));
+        assert_eq!((vm.pc, vm.registers[0]), (12, 2));
+    }
+
+    #[test]
+    fn test_snapshot_memory_limit() {
+        let mut vm = recording_vm(2, 100);
+        vm.heap = vec![0; 1000];
+        let size = snapshot_size(&vm.snapshot());
+        vm.history.as_mut().unwrap().memory_limit = 3 * size;
+        assert_eq!(vm.run(), Ok(ExitStatus::Halted));
+
+        // Each push makes the stack, and so each snapshot, a little larger
+        let history = vm.history.as_ref().unwrap();
+        assert_eq!(history.snapshots.len(), 2);
+        assert!(history.memory <= history.memory_limit);
+        assert_eq!(
+            history.pcs.len() as u64,
+            history.step() - history.earliest()
+        );
+
+        // Loading a program clears the history but keeps its limits
+        vm.history.as_mut().unwrap().memory_limit = 0;
+        vm.load_program(assemble_program(SOURCE).unwrap());
+        assert_eq!(vm.run(), Ok(ExitStatus::Halted));
+        assert_eq!(vm.history.as_ref().unwrap().snapshots.len(), 1);
+        assert_eq!(vm.step_back(1), Ok(1));
+    }
+
+    #[test]
+    fn test_replay_uses_no_fuel() {
+        let mut vm = recording_vm(1000, 10);
+        vm.fuel = Some(20);
+        assert_eq!(vm.run_for(15), Ok(ExitStatus::Running));
+        assert_eq!(vm.fuel, Some(5));
+
+        assert_eq!(vm.step_back(1), Ok(1));
+        assert_eq!(vm.fuel, Some(5));
+        assert_eq!(vm.history.as_ref().unwrap().step(), 14);
+    }
+}
@@ -0,0 +1,221 @@
+use std::io::{Read, Stdin, Stdout, Write};
+
+use super::{ExitStatus, VM};
+
+/// Stops the program with the status code in `$0`
+pub const EXIT: u16 = 0;
+/// Prints `$0` as a decimal integer
+pub const PRINT_INT: u16 = 1;
+/// Prints the NUL-terminated string at heap address `$0`
+pub const PRINT_STRING: u16 = 2;
+/// Reads a line of input into the heap at `$0`, writing at most `$1` bytes including a
+/// NUL terminator and leaving out the line ending. Returns the full length of the line
+/// in `$0`, or -1 at the end of input. A length of `$1` or more means the line was cut
+/// short to fit
+pub const READ_LINE: u16 = 3;
+
+/// A set of functions that a program calls with `SYSCALL #number`. Arguments are
+/// passed in `$0`, `$1`, ... and results are returned in the same registers
+pub trait HostFunctions {
+    /// Runs function `number`, or returns `None` if it is not one of these functions.
+    /// An error stops the program with [`VmError::SyscallFailed`](super::VmError::SyscallFailed)
+    fn call(&mut self, vm: &mut VM, number: u16) -> Option<Result<ExitStatus, String>>;
+}
+
+/// The `EXIT`, `PRINT_INT`, `PRINT_STRING` and `READ_LINE` functions, reading from
+/// `input` and writing to `output`
+pub struct Builtins<R, W> {
+    input: R,
+    output: W,
+}
+
+impl Builtins<Stdin, Stdout> {
+    /// Builtins that use the process's stdin and stdout. Stdin is only locked while a
+    /// line is being read, so the REPL can keep reading its own input
+    pub fn stdio() -> Self {
+        Builtins::new(std::io::stdin(), std::io::stdout())
+    }
+}
+
+impl<R: Read, W: Write> Builtins<R, W> {
+    pub fn new(input: R, output: W) -> Self {
+        Builtins { input, output }
+    }
+
+    fn print_string(&mut self, vm: &VM) -> Result<ExitStatus, String> {
+        let address = vm.registers[0];
+        let bytes = usize::try_from(address)
+            .ok()
+            .and_then(|start| vm.heap.get(start..))
+            .ok_or(format!("address {address} is out of bounds"))?;
+        let len = bytes
+            .iter()
+            .position(|&b| b == 0)
+            .ok_or(format!("the string at address {address} is not terminated"))?;
+
+        self.output
+            .write_all(&bytes[..len])
+            .and_then(|_| self.output.flush())
+            .map_err(|e| e.to_string())?;
+        Ok(ExitStatus::Running)
+    }
+
+    fn read_line(&mut self, vm: &mut VM) -> Result<ExitStatus, String> {
+        let (address, capacity) = (vm.registers[0], vm.registers[1]);
+        let buffer = usize::try_from(address)
+            .ok()
+            .zip(usize::try_from(capacity).ok().filter(|&n| n > 0))
+            .and_then(|(start, n)| vm.heap.get_mut(start..start.checked_add(n)?))
+            .ok_or(format!(
+                "{capacity} bytes at address {address} are out of bounds"
+            ))?;
+
+        // Reading a byte at a time leaves everything after the line unread
+        let mut line = vec![];
+        let mut byte = [0];
+        while self.input.read(&mut byte).map_err(|e| e.to_string())? == 1 {
+            line.push(byte[0]);
+            if byte[0] == b'\n' {
+                break;
+            }
+        }
+        if line.is_empty() {
+            vm.registers[0] = -1;
+            return Ok(ExitStatus::Running);
+        }
+
+        let line = line
+            .strip_suffix(b"\n")
+            .map(|line| line.strip_suffix(b"\r").unwrap_or(line))
+            .unwrap_or(&line);
+        let len = line.len().min(buffer.len() - 1);
+        buffer[..len].copy_from_slice(&line[..len]);
+        buffer[len] = 0;
+
+        vm.registers[0] = i32::try_from(line.len()).unwrap_or(i32::MAX);
+        Ok(ExitStatus::Running)
+    }
+}
+
+impl<R: Read, W: Write> HostFunctions for Builtins<R, W> {
+    fn call(&mut self, vm: &mut VM, number: u16) -> Option<Result<ExitStatus, String>> {
+        let result = match number {
+            EXIT => Ok(ExitStatus::Exited(vm.registers[0])),
+            PRINT_INT => write!(self.output, "{}", vm.registers[0])
+                .and_then(|_| self.output.flush())
+                .map(|_| ExitStatus::Running)
+                .map_err(|e| e.to_string()),
+            PRINT_STRING => self.print_string(vm),
+            READ_LINE => self.read_line(vm),
+            _ => return None,
+        };
+        Some(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::vm::{test_util::SharedOutput, VmError};
+
+    fn builtins_vm(input: &str, program: Vec<[u8; 4]>) -> (VM, SharedOutput) {
+        let output = SharedOutput::default();
+        let mut vm = VM::new();
+        vm.register(Builtins::new(
+            Cursor::new(input.as_bytes().to_vec()),
+            output.clone(),
+        ));
+        vm.set_program(program);
+        (vm, output)
+    }
+
+    #[test]
+    fn test_print() {
+        let (mut vm, output) = builtins_vm(
+            "",
+            vec![
+                [90, 0, 1, 0], // Print reg0
+                [1, 0, 0, 0],  // Set reg0 to 0
+                [90, 0, 2, 0], // Print the string at address reg0
+                [0, 0, 0, 0],  // Halt
+            ],
+        );
+        vm.registers[0] = -42;
+        vm.heap = b" is the answer\n\0".to_vec();
+
+        assert_eq!(vm.run(), Ok(ExitStatus::Halted));
+        assert_eq!(output.text(), "-42 is the answer\n");
+    }
+
+    #[test]
+    fn test_print_unterminated_string() {
+        let (mut vm, _) = builtins_vm("", vec![[90, 0, 2, 0]]);
+        vm.heap = b"abc".to_vec();
+
+        assert!(matches!(
+            vm.run(),
+            Err(VmError::SyscallFailed {
+                offset: 0,
+                number: PRINT_STRING,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_read_line() {
+        let (mut vm, _) = builtins_vm("hello\r\nhi\n", vec![[90, 0, 3, 0]]);
+        vm.heap = vec![0xFF; 8];
+        vm.registers[1] = 6;
+
+        assert_eq!(vm.run_once(), Ok(ExitStatus::Running));
+        assert_eq!(vm.registers[0], 5);
+        assert_eq!(vm.heap, b"hello\0\xFF\xFF");
+
+        vm.pc = 0;
+        vm.registers[0] = 0;
+        assert_eq!(vm.run_once(), Ok(ExitStatus::Running));
+        assert_eq!(vm.registers[0], 2);
+        assert_eq!(&vm.heap[..3], b"hi\0");
+
+        vm.pc = 0;
+        vm.registers[0] = 0;
+        assert_eq!(vm.run_once(), Ok(ExitStatus::Running));
+        assert_eq!(vm.registers[0], -1);
+    }
+
+    #[test]
+    fn test_read_line_longer_than_buffer() {
+        let (mut vm, _) = builtins_vm("hello world\nhi\n", vec![[90, 0, 3, 0]]);
+        vm.heap = vec![0xFF; 8];
+        vm.registers[1] = 6;
+
+        assert_eq!(vm.run_once(), Ok(ExitStatus::Running));
+        assert_eq!(vm.registers[0], 11);
+        assert_eq!(vm.heap, b"hello\0\xFF\xFF");
+
+        // The rest of the long line is dropped rather than read as the next line
+        vm.pc = 0;
+        vm.registers[0] = 0;
+        assert_eq!(vm.run_once(), Ok(ExitStatus::Running));
+        assert_eq!(vm.registers[0], 2);
+        assert_eq!(&vm.heap[..3], b"hi\0");
+    }
+
+    #[test]
+    fn test_exit() {
+        let (mut vm, _) = builtins_vm(
+            "",
+            vec![
+                [1, 0, 0, 3],  // Set reg0 to 3
+                [90, 0, 0, 0], // Exit with status reg0
+                [0, 0, 0, 0],  // Halt
+            ],
+        );
+
+        assert_eq!(vm.run(), Ok(ExitStatus::Exited(3)));
+        assert_eq!(vm.pc, 8);
+    }
+}
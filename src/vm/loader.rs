@@ -0,0 +1,316 @@
+use std::{error::Error, fmt::Display};
+
+use super::VM;
+use crate::assembler::{
+    bytecode::{checksum, SectionKind, HEADER_SIZE, MAGIC, SECTION_ENTRY_SIZE, VERSION},
+    LineInfo, Program, Section, Symbol,
+};
+
+/// An error raised while reading a bytecode file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LoadError {
+    Truncated,
+    BadMagic([u8; 4]),
+    UnsupportedVersion(u16),
+    ChecksumMismatch { expected: u32, actual: u32 },
+    UnknownSection(u8),
+    DuplicateSection(SectionKind),
+    SectionOutOfBounds(SectionKind),
+    MissingCodeSection,
+    MisalignedCode(usize),
+    InvalidSymbol,
+    EntryPointOutOfBounds(u32),
+    MisalignedEntryPoint(u32),
+}
+
+impl Display for LoadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        use LoadError as LE;
+        match self {
+            LE::Truncated => write!(f, "The file ended unexpectedly"),
+            LE::BadMagic(magic) => write!(f, "{magic:02X?} is not a potassium bytecode header"),
+            LE::UnsupportedVersion(v) => write!(f, "Bytecode version {v} is not supported"),
+            LE::ChecksumMismatch { expected, actual } => write!(
+                f,
+                "Checksum mismatch: the header says {expected:08X} but the file hashes to {actual:08X}"
+            ),
+            LE::UnknownSection(kind) => write!(f, "Unknown section kind {kind}"),
+            LE::DuplicateSection(kind) => write!(f, "The {kind:?} section appears more than once"),
+            LE::SectionOutOfBounds(kind) => {
+                write!(f, "The {kind:?} section extends past the end of the file")
+            }
+            LE::MissingCodeSection => write!(f, "The file has no code section"),
+            LE::MisalignedCode(len) => {
+                write!(f, "The code section is {len} bytes, which is not a multiple of 4")
+            }
+            LE::InvalidSymbol => write!(f, "The symbol section is malformed"),
+            LE::EntryPointOutOfBounds(entry) => {
+                write!(f, "The entry point {entry} is outside of the code section")
+            }
+            LE::MisalignedEntryPoint(entry) => {
+                write!(f, "The entry point {entry} is not the start of an instruction")
+            }
+        }
+    }
+}
+
+impl Error for LoadError {}
+
+impl VM {
+    /// Loads a program from the bytecode file format
+    pub fn load_bytecode(&mut self, bytes: &[u8]) -> Result<(), LoadError> {
+        self.load_program(read_bytecode(bytes)?);
+        Ok(())
+    }
+}
+
+/// Whether `bytes` starts with the bytecode magic number
+pub fn is_bytecode(bytes: &[u8]) -> bool {
+    bytes.starts_with(&MAGIC)
+}
+
+/// Parses and validates a bytecode file
+pub fn read_bytecode(bytes: &[u8]) -> Result<Program, LoadError> {
+    let header = bytes.get(..HEADER_SIZE).ok_or(LoadError::Truncated)?;
+
+    let magic = [header[0], header[1], header[2], header[3]];
+    if magic != MAGIC {
+        return Err(LoadError::BadMagic(magic));
+    }
+
+    let version = u16::from_le_bytes([header[4], header[5]]);
+    if !(1..=VERSION).contains(&version) {
+        return Err(LoadError::UnsupportedVersion(version));
+    }
+
+    let section_count = u16::from_le_bytes([header[6], header[7]]) as usize;
+    let entry_point = read_u32(header, 8)?;
+
+    let expected = read_u32(header, 12)?;
+    let actual = checksum(&bytes[HEADER_SIZE..]);
+    if expected != actual {
+        return Err(LoadError::ChecksumMismatch { expected, actual });
+    }
+
+    let mut code = None;
+    let mut program = Program {
+        entry_point,
+        ..Default::default()
+    };
+    let mut seen = vec![];
+
+    for n in 0..section_count {
+        let entry = HEADER_SIZE + n * SECTION_ENTRY_SIZE;
+        let kind = *bytes.get(entry).ok_or(LoadError::Truncated)?;
+        let kind = SectionKind::try_from(kind).map_err(LoadError::UnknownSection)?;
+
+        if seen.contains(&kind) {
+            return Err(LoadError::DuplicateSection(kind));
+        }
+        seen.push(kind);
+
+        let offset = read_u32(bytes, entry + 4)? as usize;
+        let len = read_u32(bytes, entry + 8)? as usize;
+        let contents = offset
+            .checked_add(len)
+            .and_then(|end| bytes.get(offset..end))
+            .ok_or(LoadError::SectionOutOfBounds(kind))?;
+
+        match kind {
+            SectionKind::Code => code = Some(read_code(contents)?),
+            SectionKind::Data => program.data = contents.to_vec(),
+            SectionKind::Symbols => program.symbols = read_symbols(contents)?,
+            SectionKind::Debug => program.debug = read_debug(contents)?,
+            SectionKind::Constants => program.constants = read_constants(contents)?,
+        }
+    }
+
+    program.code = code.ok_or(LoadError::MissingCodeSection)?;
+
+    if entry_point != 0 && entry_point as usize >= program.code.len() * 4 {
+        return Err(LoadError::EntryPointOutOfBounds(entry_point));
+    }
+    if !entry_point.is_multiple_of(4) {
+        return Err(LoadError::MisalignedEntryPoint(entry_point));
+    }
+
+    Ok(program)
+}
+
+fn read_code(contents: &[u8]) -> Result<Vec<[u8; 4]>, LoadError> {
+    if !contents.len().is_multiple_of(4) {
+        return Err(LoadError::MisalignedCode(contents.len()));
+    }
+
+    Ok(contents
+        .chunks_exact(4)
+        .map(|word| [word[0], word[1], word[2], word[3]])
+        .collect())
+}
+
+fn read_symbols(contents: &[u8]) -> Result<Vec<Symbol>, LoadError> {
+    let mut symbols = vec![];
+    let mut pos = 0;
+
+    while pos < contents.len() {
+        let section = *contents.get(pos).ok_or(LoadError::InvalidSymbol)?;
+        let section = Section::try_from(section).map_err(|_| LoadError::InvalidSymbol)?;
+        let offset = read_u32(contents, pos + 1).map_err(|_| LoadError::InvalidSymbol)?;
+        let len = contents
+            .get(pos + 5..pos + 7)
+            .map(|len| u16::from_le_bytes([len[0], len[1]]) as usize)
+            .ok_or(LoadError::InvalidSymbol)?;
+        let name = contents
+            .get(pos + 7..pos + 7 + len)
+            .and_then(|name| std::str::from_utf8(name).ok())
+            .ok_or(LoadError::InvalidSymbol)?;
+
+        symbols.push(Symbol {
+            name: name.to_owned(),
+            section,
+            offset,
+        });
+        pos += 7 + len;
+    }
+
+    Ok(symbols)
+}
+
+fn read_debug(contents: &[u8]) -> Result<Vec<LineInfo>, LoadError> {
+    if !contents.len().is_multiple_of(8) {
+        return Err(LoadError::SectionOutOfBounds(SectionKind::Debug));
+    }
+
+    contents
+        .chunks_exact(8)
+        .map(|pair| {
+            Ok(LineInfo {
+                offset: read_u32(pair, 0)?,
+                line: read_u32(pair, 4)?,
+            })
+        })
+        .collect()
+}
+
+fn read_constants(contents: &[u8]) -> Result<Vec<f64>, LoadError> {
+    if !contents.len().is_multiple_of(8) {
+        return Err(LoadError::SectionOutOfBounds(SectionKind::Constants));
+    }
+
+    Ok(contents
+        .chunks_exact(8)
+        .map(|bytes| f64::from_le_bytes(bytes.try_into().unwrap()))
+        .collect())
+}
+
+fn read_u32(bytes: &[u8], pos: usize) -> Result<u32, LoadError> {
+    bytes
+        .get(pos..pos + 4)
+        .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .ok_or(LoadError::Truncated)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assembler::{assemble_program, write_bytecode};
+
+    #[test]
+    fn test_bytecode_round_trip() {
+        let program = assemble_program(
+            r#"
+            .data
+            greeting: .asciiz "hi"
+            .code
+            main: LOAD $0 @greeting
+            LOADF $f0 #0.5
+            HLT
+            "#,
+        )
+        .unwrap();
+        let bytes = write_bytecode(&program);
+
+        assert!(is_bytecode(&bytes));
+        assert_eq!(read_bytecode(&bytes), Ok(program));
+    }
+
+    #[test]
+    fn test_load_bytecode() {
+        let program = assemble_program("HLT\nmain: LOAD $0 #7\nHLT").unwrap();
+        let mut test_vm = VM::new();
+
+        test_vm.load_bytecode(&write_bytecode(&program)).unwrap();
+        assert_eq!(test_vm.pc, 4);
+        test_vm.run().unwrap();
+        assert_eq!(test_vm.registers[0], 7);
+    }
+
+    #[test]
+    fn test_read_version_1() {
+        let mut bytes = write_bytecode(&Program {
+            code: vec![[0, 0, 0, 0]],
+            ..Default::default()
+        });
+        bytes[4..6].copy_from_slice(&1u16.to_le_bytes());
+
+        assert!(read_bytecode(&bytes).is_ok());
+    }
+
+    #[test]
+    fn test_bad_magic() {
+        let mut bytes = write_bytecode(&Program::default());
+        bytes[0] = b'X';
+
+        assert_eq!(read_bytecode(&bytes), Err(LoadError::BadMagic(*b"XPBC")));
+    }
+
+    #[test]
+    fn test_unsupported_version() {
+        let mut bytes = write_bytecode(&Program::default());
+        bytes[4..6].copy_from_slice(&99u16.to_le_bytes());
+
+        assert_eq!(
+            read_bytecode(&bytes),
+            Err(LoadError::UnsupportedVersion(99))
+        );
+    }
+
+    #[test]
+    fn test_checksum_mismatch() {
+        let mut bytes = write_bytecode(&Program {
+            code: vec![[0, 0, 0, 0]],
+            ..Default::default()
+        });
+        let last = bytes.len() - 1;
+        bytes[last] = 1;
+
+        assert!(matches!(
+            read_bytecode(&bytes),
+            Err(LoadError::ChecksumMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn test_invalid_entry_point() {
+        let program = |entry_point| Program {
+            code: vec![[0, 0, 0, 0], [0, 0, 0, 0]],
+            entry_point,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            read_bytecode(&write_bytecode(&program(6))),
+            Err(LoadError::MisalignedEntryPoint(6))
+        );
+        assert_eq!(
+            read_bytecode(&write_bytecode(&program(8))),
+            Err(LoadError::EntryPointOutOfBounds(8))
+        );
+        assert!(read_bytecode(&write_bytecode(&program(4))).is_ok());
+    }
+
+    #[test]
+    fn test_truncated_header() {
+        assert_eq!(read_bytecode(&MAGIC), Err(LoadError::Truncated));
+    }
+}
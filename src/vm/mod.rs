@@ -0,0 +1,2344 @@
+use std::{collections::BTreeSet, time::Instant};
+
+use crate::{assembler::Program, opcode::Opcode};
+use decode::{decode, DecodeCache, Decoded};
+use trace::TraceState;
+
+pub use arithmetic::ArithmeticMode;
+pub use decode::REGISTER_COUNT;
+pub use error::{ExitStatus, VmError};
+pub use flags::Flags;
+pub use fuel::FuelCosts;
+pub use history::History;
+pub use host::{Builtins, HostFunctions};
+pub use loader::{is_bytecode, read_bytecode, LoadError};
+pub use profile::{Block, Profiler};
+pub use snapshot::{Snapshot, SnapshotError, SNAPSHOT_MAGIC, SNAPSHOT_VERSION};
+pub use trace::{Change, TraceEntry, TraceFormat, Tracer};
+
+mod arithmetic;
+mod decode;
+mod error;
+mod flags;
+mod fuel;
+pub mod history;
+pub mod host;
+mod loader;
+mod profile;
+mod snapshot;
+#[cfg(test)]
+mod test_util;
+mod trace;
+
+/// Called after each instruction with the offset it was executed from. Returning
+/// `true` pauses the VM with [`ExitStatus::Paused`]
+pub type StepHook = Box<dyn FnMut(&VM, usize) -> bool>;
+
+/// The default maximum number of values the stack can hold
+pub const DEFAULT_STACK_LIMIT: usize = 1024;
+/// The default maximum size of the heap in bytes
+pub const DEFAULT_HEAP_LIMIT: usize = 16 * 1024 * 1024;
+
+pub struct VM {
+    pub registers: [i32; REGISTER_COUNT],
+    pub float_registers: [f64; REGISTER_COUNT],
+    pub pc: usize,
+    /// The program's bytes. Changing them without changing their length needs a call
+    /// to `invalidate_cache` before the next instruction runs
+    pub program: Vec<u8>,
+    pub remainder: u32,
+    pub flags: Flags,
+    pub arithmetic_mode: ArithmeticMode,
+    /// Byte-addressable memory, grown by `ALOC`. Multi-byte values are little-endian
+    pub heap: Vec<u8>,
+    /// The size in bytes that `ALOC` may not grow the heap beyond
+    pub heap_limit: usize,
+    /// Values pushed by `PUSH` and return addresses pushed by `CALL`
+    pub stack: Vec<i32>,
+    pub stack_limit: usize,
+    /// Float values that `LOADF` loads by index
+    pub constants: Vec<f64>,
+    /// Whether to run instructions from `decoded` rather than decoding every word
+    /// as it is executed
+    pub use_decode_cache: bool,
+    /// The fuel left to run instructions with, or `None` to run without a limit.
+    /// Each instruction uses its cost from `fuel_costs` before it is executed
+    pub fuel: Option<u64>,
+    pub fuel_costs: FuelCosts,
+    /// The functions `SYSCALL` can call, searched from the most recently registered
+    host_functions: Vec<Box<dyn HostFunctions>>,
+    /// Offsets that `run` and `run_for` pause before executing, unless execution
+    /// started there
+    pub breakpoints: BTreeSet<usize>,
+    pub step_hook: Option<StepHook>,
+    pub tracer: Option<Tracer>,
+    /// Records execution so that the VM can step backwards
+    pub history: Option<History>,
+    pub profiler: Option<Profiler>,
+    decoded: DecodeCache,
+}
+
+impl Default for VM {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl VM {
+    pub fn new() -> VM {
+        VM {
+            registers: [0; REGISTER_COUNT],
+            float_registers: [0.0; REGISTER_COUNT],
+            pc: 0,
+            program: vec![],
+            remainder: 0,
+            flags: Flags::default(),
+            arithmetic_mode: ArithmeticMode::default(),
+            heap: vec![],
+            heap_limit: DEFAULT_HEAP_LIMIT,
+            stack: vec![],
+            stack_limit: DEFAULT_STACK_LIMIT,
+            constants: vec![],
+            use_decode_cache: true,
+            fuel: None,
+            fuel_costs: FuelCosts::default(),
+            host_functions: vec![],
+            breakpoints: BTreeSet::new(),
+            step_hook: None,
+            tracer: None,
+            history: None,
+            profiler: None,
+            decoded: DecodeCache::default(),
+        }
+    }
+
+    pub fn set_program(&mut self, program: Vec<[u8; 4]>) {
+        self.program = program.into_iter().flatten().collect();
+        self.invalidate_cache();
+        if let Some(history) = &mut self.history {
+            history.clear();
+        }
+    }
+
+    /// Decodes the program again before the next instruction runs
+    pub fn invalidate_cache(&mut self) {
+        self.decoded = DecodeCache::new(&self.program);
+    }
+
+    /// Makes `functions` available to `SYSCALL`, ahead of any registered before it
+    pub fn register(&mut self, functions: impl HostFunctions + 'static) {
+        self.host_functions.push(Box::new(functions));
+    }
+
+    /// Loads an assembled program, copying its data section into the heap
+    /// and starting execution at its entry point
+    pub fn load_program(&mut self, program: Program) {
+        self.set_program(program.code);
+        self.heap = program.data;
+        self.constants = program.constants;
+        self.pc = program.entry_point as usize;
+    }
+
+    /// Executes the VM's entire program
+    pub fn run(&mut self) -> Result<ExitStatus, VmError> {
+        if !self.breakpoints.is_empty()
+            || self.step_hook.is_some()
+            || self.tracer.is_some()
+            || self.history.is_some()
+            || self.profiler.is_some()
+        {
+            return self.run_for(u64::MAX);
+        }
+
+        self.refresh_cache();
+        loop {
+            match self.execute_instruction()? {
+                ExitStatus::Running => {}
+                status => return Ok(status),
+            }
+        }
+    }
+
+    /// Executes at most `steps` instructions, returning [`ExitStatus::Running`] if the
+    /// program has not stopped so that a later call can carry on from where it left off
+    pub fn run_for(&mut self, steps: u64) -> Result<ExitStatus, VmError> {
+        self.refresh_cache();
+        for step in 0..steps {
+            if step > 0 && self.breakpoints.contains(&self.pc) {
+                return Ok(ExitStatus::Paused);
+            }
+
+            let offset = self.pc;
+            let before = self.tracer.as_ref().map(|_| TraceState::of(self));
+            let syscall = self.history.is_some() && self.is_syscall(offset);
+            if let Some(mut history) = self.history.take() {
+                history.start(self);
+                self.history = Some(history);
+            }
+            let started = self.profiler.as_ref().map(|_| Instant::now());
+            let status = match self.execute_instruction() {
+                Ok(status) => status,
+                Err(e) => {
+                    if let Some(tracer) = &mut self.tracer {
+                        tracer.dump();
+                    }
+                    return Err(e);
+                }
+            };
+            if let (Some(profiler), Some(started)) = (&mut self.profiler, started) {
+                profiler.record(offset, self.program[offset], self.pc, started.elapsed());
+            }
+            if let Some(mut history) = self.history.take() {
+                history.record(self, offset, syscall);
+                self.history = Some(history);
+            }
+            if let Some(before) = before {
+                let entry = before.entry(self, offset);
+                if let Some(tracer) = &mut self.tracer {
+                    tracer.record(entry);
+                }
+            }
+
+            match status {
+                ExitStatus::Running if self.call_step_hook(offset) => {
+                    return Ok(ExitStatus::Paused)
+                }
+                ExitStatus::Running => {}
+                status => {
+                    self.call_step_hook(offset);
+                    return Ok(status);
+                }
+            }
+        }
+        Ok(ExitStatus::Running)
+    }
+
+    /// Executes one instruction for finer control over the VM
+    pub fn run_once(&mut self) -> Result<ExitStatus, VmError> {
+        self.run_for(1)
+    }
+
+    fn is_syscall(&self, offset: usize) -> bool {
+        self.program.get(offset) == Some(&u8::from(Opcode::SYSCALL))
+    }
+
+    /// Runs the step hook, if there is one, for the instruction at `offset`
+    fn call_step_hook(&mut self, offset: usize) -> bool {
+        let Some(mut hook) = self.step_hook.take() else {
+            return false;
+        };
+        let pause = hook(self, offset);
+        self.step_hook = Some(hook);
+        pause
+    }
+
+    fn execute_instruction(&mut self) -> Result<ExitStatus, VmError> {
+        let offset = self.pc;
+        let decoded = self.fetch()?;
+        let opcode = decoded.opcode;
+        if let Some(fuel) = self.fuel {
+            let cost = self.fuel_costs.get(opcode) as u64;
+            self.fuel = Some(
+                fuel.checked_sub(cost)
+                    .ok_or(VmError::OutOfFuel { offset })?,
+            );
+        }
+        self.pc += 4;
+
+        match opcode {
+            Opcode::HLT => return Ok(ExitStatus::Halted),
+            Opcode::SYSCALL => return self.syscall(offset, decoded.immediate as u16),
+            Opcode::LOAD => {
+                self.registers[decoded.a as usize] = decoded.immediate;
+            }
+            Opcode::ADD | Opcode::SUB | Opcode::MUL => {
+                let reg1 = decoded.a as usize;
+                let reg2 = decoded.b as usize;
+                let result_reg = decoded.c as usize;
+
+                self.registers[result_reg] =
+                    self.arithmetic(offset, opcode, self.registers[reg1], self.registers[reg2])?;
+            }
+            Opcode::DIV => {
+                let reg1 = decoded.a as usize;
+                let reg2 = decoded.b as usize;
+                let result_reg = decoded.c as usize;
+
+                let (dividend, divisor) = (self.registers[reg1], self.registers[reg2]);
+
+                if divisor == 0 {
+                    return Err(VmError::DivideByZero { offset });
+                }
+
+                self.registers[result_reg] = self.arithmetic(offset, opcode, dividend, divisor)?;
+                self.remainder = dividend.wrapping_rem(divisor) as u32;
+            }
+            Opcode::JMPF => {
+                // Like the relative jumps, counted from the start of this instruction
+                let value = self.registers[decoded.a as usize];
+                self.jump(offset, offset as i64 + value as i64)?;
+            }
+            Opcode::JMPB => {
+                let value = self.registers[decoded.a as usize];
+                self.jump(offset, offset as i64 - value as i64)?;
+            }
+            Opcode::EQ => {
+                let val1 = self.registers[decoded.a as usize];
+                let val2 = self.registers[decoded.b as usize];
+
+                self.set_comparison(val1, val2, val1 == val2);
+            }
+            Opcode::NEQ => {
+                let val1 = self.registers[decoded.a as usize];
+                let val2 = self.registers[decoded.b as usize];
+
+                self.set_comparison(val1, val2, val1 != val2);
+            }
+            Opcode::GT => {
+                let val1 = self.registers[decoded.a as usize];
+                let val2 = self.registers[decoded.b as usize];
+
+                self.set_comparison(val1, val2, val1 > val2);
+            }
+            Opcode::LT => {
+                let val1 = self.registers[decoded.a as usize];
+                let val2 = self.registers[decoded.b as usize];
+
+                self.set_comparison(val1, val2, val1 < val2);
+            }
+            Opcode::GTQ => {
+                let val1 = self.registers[decoded.a as usize];
+                let val2 = self.registers[decoded.b as usize];
+
+                self.set_comparison(val1, val2, val1 >= val2);
+            }
+            Opcode::LTQ => {
+                let val1 = self.registers[decoded.a as usize];
+                let val2 = self.registers[decoded.b as usize];
+
+                self.set_comparison(val1, val2, val1 <= val2);
+            }
+            Opcode::CMP => {
+                let val1 = self.registers[decoded.a as usize];
+                let val2 = self.registers[decoded.b as usize];
+
+                let evaluation = arithmetic::evaluate(Opcode::SUB, val1, val2);
+                self.flags =
+                    Flags::from_result(evaluation.wrapped, evaluation.carry, evaluation.overflow);
+            }
+            Opcode::JMP
+            | Opcode::JEQ
+            | Opcode::JNEQ
+            | Opcode::JZ
+            | Opcode::JNZ
+            | Opcode::JLT
+            | Opcode::JGE
+            | Opcode::JGT
+            | Opcode::JLE
+            | Opcode::JC
+            | Opcode::JNC
+            | Opcode::JO
+            | Opcode::JNO => {
+                let target = self.registers[decoded.a as usize];
+                self.branch(offset, opcode, target as i64)?;
+            }
+            Opcode::JMPR
+            | Opcode::JEQR
+            | Opcode::JNEQR
+            | Opcode::JZR
+            | Opcode::JNZR
+            | Opcode::JLTR
+            | Opcode::JGER
+            | Opcode::JGTR
+            | Opcode::JLER
+            | Opcode::JCR
+            | Opcode::JNCR
+            | Opcode::JOR
+            | Opcode::JNOR => {
+                self.branch(offset, opcode, decoded.immediate as i64)?;
+            }
+            Opcode::JMPI
+            | Opcode::JEQI
+            | Opcode::JNEQI
+            | Opcode::JZI
+            | Opcode::JNZI
+            | Opcode::JLTI
+            | Opcode::JGEI
+            | Opcode::JGTI
+            | Opcode::JLEI
+            | Opcode::JCI
+            | Opcode::JNCI
+            | Opcode::JOI
+            | Opcode::JNOI => {
+                self.branch(offset, opcode, decoded.immediate as i64)?;
+            }
+            Opcode::ALOC => {
+                let size = self.registers[decoded.a as usize];
+                let size = usize::try_from(size)
+                    .map_err(|_| VmError::InvalidAllocation { offset, size })?;
+                let len = self
+                    .heap
+                    .len()
+                    .checked_add(size)
+                    .filter(|&len| len <= self.heap_limit)
+                    .ok_or(VmError::HeapLimitExceeded { offset, size })?;
+
+                self.heap.resize(len, 0);
+            }
+            Opcode::LOADB => {
+                let register = decoded.a as usize;
+                let address = self.registers[decoded.b as usize];
+
+                let bytes = self.heap_slice(offset, address, 1)?;
+                self.registers[register] = bytes[0] as i32;
+            }
+            Opcode::LOADH => {
+                let register = decoded.a as usize;
+                let address = self.registers[decoded.b as usize];
+
+                let bytes = self.heap_slice(offset, address, 2)?;
+                self.registers[register] = u16::from_le_bytes([bytes[0], bytes[1]]) as i32;
+            }
+            Opcode::LOADW => {
+                let register = decoded.a as usize;
+                let address = self.registers[decoded.b as usize];
+
+                let bytes = self.heap_slice(offset, address, 4)?;
+                self.registers[register] =
+                    i32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+            }
+            Opcode::STOREB => {
+                let value = self.registers[decoded.a as usize];
+                let address = self.registers[decoded.b as usize];
+
+                self.heap_slice_mut(offset, address, 1)?
+                    .copy_from_slice(&(value as u8).to_le_bytes());
+            }
+            Opcode::STOREH => {
+                let value = self.registers[decoded.a as usize];
+                let address = self.registers[decoded.b as usize];
+
+                self.heap_slice_mut(offset, address, 2)?
+                    .copy_from_slice(&(value as u16).to_le_bytes());
+            }
+            Opcode::STOREW => {
+                let value = self.registers[decoded.a as usize];
+                let address = self.registers[decoded.b as usize];
+
+                self.heap_slice_mut(offset, address, 4)?
+                    .copy_from_slice(&value.to_le_bytes());
+            }
+            Opcode::PUSH => {
+                let value = self.registers[decoded.a as usize];
+                self.push(offset, value)?;
+            }
+            Opcode::POP => {
+                let register = decoded.a as usize;
+                self.registers[register] = self.pop(offset)?;
+            }
+            Opcode::CALL => {
+                let target = self.registers[decoded.a as usize];
+                self.push(offset, self.pc as i32)?;
+                self.jump(offset, target as i64)?;
+            }
+            Opcode::RET => {
+                let target = self.pop(offset)?;
+                self.jump(offset, target as i64)?;
+            }
+            Opcode::AND => {
+                let val1 = self.registers[decoded.a as usize];
+                let val2 = self.registers[decoded.b as usize];
+
+                self.registers[decoded.c as usize] = val1 & val2;
+            }
+            Opcode::OR => {
+                let val1 = self.registers[decoded.a as usize];
+                let val2 = self.registers[decoded.b as usize];
+
+                self.registers[decoded.c as usize] = val1 | val2;
+            }
+            Opcode::XOR => {
+                let val1 = self.registers[decoded.a as usize];
+                let val2 = self.registers[decoded.b as usize];
+
+                self.registers[decoded.c as usize] = val1 ^ val2;
+            }
+            Opcode::NOT => {
+                let value = self.registers[decoded.a as usize];
+
+                self.registers[decoded.b as usize] = !value;
+            }
+            Opcode::SHL | Opcode::SHR | Opcode::SAR => {
+                let value = self.registers[decoded.a as usize];
+                let amount = self.registers[decoded.b as usize] as u32;
+
+                self.registers[decoded.c as usize] = shift(opcode, value, amount);
+            }
+            Opcode::SHLI | Opcode::SHRI | Opcode::SARI => {
+                let value = self.registers[decoded.a as usize];
+                let amount = decoded.b as u32;
+
+                self.registers[decoded.c as usize] = shift(opcode, value, amount);
+            }
+            Opcode::LOADF => {
+                let register = decoded.a as usize;
+                let index = decoded.immediate as u16;
+
+                self.float_registers[register] = *self
+                    .constants
+                    .get(index as usize)
+                    .ok_or(VmError::InvalidConstant { offset, index })?;
+            }
+            Opcode::ADDF => {
+                let val1 = self.float_registers[decoded.a as usize];
+                let val2 = self.float_registers[decoded.b as usize];
+
+                self.float_registers[decoded.c as usize] = val1 + val2;
+            }
+            Opcode::SUBF => {
+                let val1 = self.float_registers[decoded.a as usize];
+                let val2 = self.float_registers[decoded.b as usize];
+
+                self.float_registers[decoded.c as usize] = val1 - val2;
+            }
+            Opcode::MULF => {
+                let val1 = self.float_registers[decoded.a as usize];
+                let val2 = self.float_registers[decoded.b as usize];
+
+                self.float_registers[decoded.c as usize] = val1 * val2;
+            }
+            Opcode::DIVF => {
+                let val1 = self.float_registers[decoded.a as usize];
+                let val2 = self.float_registers[decoded.b as usize];
+
+                self.float_registers[decoded.c as usize] = val1 / val2;
+            }
+            Opcode::EQF => {
+                let val1 = self.float_registers[decoded.a as usize];
+                let val2 = self.float_registers[decoded.b as usize];
+
+                self.flags = Flags::from_float_comparison(val1, val2);
+                self.flags.set(Flags::CONDITION, val1 == val2);
+            }
+            Opcode::NEQF => {
+                let val1 = self.float_registers[decoded.a as usize];
+                let val2 = self.float_registers[decoded.b as usize];
+
+                self.flags = Flags::from_float_comparison(val1, val2);
+                self.flags.set(Flags::CONDITION, val1 != val2);
+            }
+            Opcode::GTF => {
+                let val1 = self.float_registers[decoded.a as usize];
+                let val2 = self.float_registers[decoded.b as usize];
+
+                self.flags = Flags::from_float_comparison(val1, val2);
+                self.flags.set(Flags::CONDITION, val1 > val2);
+            }
+            Opcode::LTF => {
+                let val1 = self.float_registers[decoded.a as usize];
+                let val2 = self.float_registers[decoded.b as usize];
+
+                self.flags = Flags::from_float_comparison(val1, val2);
+                self.flags.set(Flags::CONDITION, val1 < val2);
+            }
+            Opcode::GTQF => {
+                let val1 = self.float_registers[decoded.a as usize];
+                let val2 = self.float_registers[decoded.b as usize];
+
+                self.flags = Flags::from_float_comparison(val1, val2);
+                self.flags.set(Flags::CONDITION, val1 >= val2);
+            }
+            Opcode::LTQF => {
+                let val1 = self.float_registers[decoded.a as usize];
+                let val2 = self.float_registers[decoded.b as usize];
+
+                self.flags = Flags::from_float_comparison(val1, val2);
+                self.flags.set(Flags::CONDITION, val1 <= val2);
+            }
+            Opcode::ITOF => {
+                let value = self.registers[decoded.a as usize];
+
+                self.float_registers[decoded.b as usize] = value as f64;
+            }
+            Opcode::FTOI => {
+                let value = self.float_registers[decoded.a as usize];
+
+                // Rounds toward zero, saturating at the ends of the i32 range, with NaN as 0
+                self.registers[decoded.b as usize] = value as i32;
+            }
+            Opcode::LOADHI => {
+                let register = decoded.a as usize;
+                let half = decoded.immediate as u32;
+
+                self.registers[register] = (half << 16) as i32;
+            }
+            Opcode::LOADLO => {
+                let register = decoded.a as usize;
+                let half = decoded.immediate as u32;
+                let high = self.registers[register] as u32 & 0xFFFF_0000;
+
+                self.registers[register] = (high | half) as i32;
+            }
+        }
+
+        Ok(ExitStatus::Running)
+    }
+
+    /// Calls host function `number` from the most recently registered set that has it
+    fn syscall(&mut self, offset: usize, number: u16) -> Result<ExitStatus, VmError> {
+        // The functions are taken out of the VM while they run so they can borrow it
+        let mut host_functions = std::mem::take(&mut self.host_functions);
+        let result = host_functions
+            .iter_mut()
+            .rev()
+            .find_map(|functions| functions.call(self, number));
+        host_functions.append(&mut self.host_functions);
+        self.host_functions = host_functions;
+
+        match result {
+            Some(result) => result.map_err(|message| VmError::SyscallFailed {
+                offset,
+                number,
+                message,
+            }),
+            None => Err(VmError::UnknownSyscall { offset, number }),
+        }
+    }
+
+    /// Performs `ADD`, `SUB`, `MUL` or `DIV`, setting the carry and overflow flags and
+    /// handling overflow according to the arithmetic mode
+    fn arithmetic(
+        &mut self,
+        offset: usize,
+        opcode: Opcode,
+        a: i32,
+        b: i32,
+    ) -> Result<i32, VmError> {
+        let evaluation = arithmetic::evaluate(opcode, a, b);
+
+        // Like hardware flags, these describe the two's complement result even when
+        // a saturated value is what gets stored
+        self.flags = Flags::from_result(evaluation.wrapped, evaluation.carry, evaluation.overflow);
+
+        match (self.arithmetic_mode, evaluation.overflow) {
+            (_, false) | (ArithmeticMode::Wrapping, true) => Ok(evaluation.wrapped),
+            (ArithmeticMode::Saturating, true) => Ok(evaluation.saturated),
+            (ArithmeticMode::Trapping, true) => Err(VmError::ArithmeticOverflow { offset }),
+        }
+    }
+
+    /// Sets the flags as `CMP a b` would, with `CONDITION` recording whether a boolean
+    /// comparison held
+    fn set_comparison(&mut self, a: i32, b: i32, holds: bool) {
+        let evaluation = arithmetic::evaluate(Opcode::SUB, a, b);
+        self.flags = Flags::from_result(evaluation.wrapped, evaluation.carry, evaluation.overflow);
+        self.flags.set(Flags::CONDITION, holds);
+    }
+
+    /// Jumps to `target` if the flags meet the condition of the jump `opcode`
+    fn branch(&mut self, offset: usize, opcode: Opcode, target: i64) -> Result<(), VmError> {
+        use Opcode as O;
+        let flags = self.flags;
+        let taken = match opcode {
+            O::JEQ | O::JEQR | O::JEQI => flags.contains(Flags::CONDITION),
+            O::JNEQ | O::JNEQR | O::JNEQI => !flags.contains(Flags::CONDITION),
+            O::JZ | O::JZR | O::JZI => flags.contains(Flags::ZERO),
+            O::JNZ | O::JNZR | O::JNZI => !flags.contains(Flags::ZERO),
+            O::JLT | O::JLTR | O::JLTI => flags.less(),
+            O::JGE | O::JGER | O::JGEI => !flags.less(),
+            O::JGT | O::JGTR | O::JGTI => !flags.contains(Flags::ZERO) && !flags.less(),
+            O::JLE | O::JLER | O::JLEI => flags.contains(Flags::ZERO) || flags.less(),
+            O::JC | O::JCR | O::JCI => flags.contains(Flags::CARRY),
+            O::JNC | O::JNCR | O::JNCI => !flags.contains(Flags::CARRY),
+            O::JO | O::JOR | O::JOI => flags.contains(Flags::OVERFLOW),
+            O::JNO | O::JNOR | O::JNOI => !flags.contains(Flags::OVERFLOW),
+            _ => true,
+        };
+
+        if taken {
+            self.jump(offset, target)?;
+        }
+        Ok(())
+    }
+
+    /// Rebuilds the decode cache if the program's length has changed since it was built
+    fn refresh_cache(&mut self) {
+        if self.use_decode_cache && !self.decoded.matches(&self.program) {
+            self.invalidate_cache();
+        }
+    }
+
+    /// Decodes the instruction word at the pc, taking it from the decode cache when
+    /// possible
+    fn fetch(&self) -> Result<Decoded, VmError> {
+        if self.use_decode_cache {
+            if let Some(decoded) = self.decoded.get(self.pc) {
+                return Ok(decoded);
+            }
+        }
+
+        let len = self.program.len();
+        if self.pc >= len {
+            return Err(VmError::PcOutOfBounds { pc: self.pc, len });
+        }
+
+        let word = self
+            .program
+            .get(self.pc..self.pc + 4)
+            .ok_or(VmError::TruncatedOperands { offset: len })?;
+        decode(self.pc, [word[0], word[1], word[2], word[3]])
+    }
+
+    /// Moves the pc to `target` for the jump at `offset`. Every instruction is one
+    /// 4 byte word, so a target that is not a multiple of 4 is an error, as is one
+    /// before the start of the program
+    fn jump(&mut self, offset: usize, target: i64) -> Result<(), VmError> {
+        let target =
+            usize::try_from(target).map_err(|_| VmError::NegativeJump { offset, target })?;
+        if !target.is_multiple_of(4) {
+            return Err(VmError::MisalignedJump { offset, target });
+        }
+
+        self.pc = target;
+        Ok(())
+    }
+
+    fn push(&mut self, offset: usize, value: i32) -> Result<(), VmError> {
+        if self.stack.len() >= self.stack_limit {
+            return Err(VmError::StackOverflow { offset });
+        }
+
+        self.stack.push(value);
+        Ok(())
+    }
+
+    fn pop(&mut self, offset: usize) -> Result<i32, VmError> {
+        self.stack.pop().ok_or(VmError::StackUnderflow { offset })
+    }
+
+    /// Returns the `width` bytes of heap starting at `address`, if they are all in bounds
+    fn heap_slice(&self, offset: usize, address: i32, width: usize) -> Result<&[u8], VmError> {
+        let range = self.heap_range(offset, address, width)?;
+        Ok(&self.heap[range])
+    }
+
+    fn heap_slice_mut(
+        &mut self,
+        offset: usize,
+        address: i32,
+        width: usize,
+    ) -> Result<&mut [u8], VmError> {
+        let range = self.heap_range(offset, address, width)?;
+        Ok(&mut self.heap[range])
+    }
+
+    fn heap_range(
+        &self,
+        offset: usize,
+        address: i32,
+        width: usize,
+    ) -> Result<std::ops::Range<usize>, VmError> {
+        usize::try_from(address)
+            .ok()
+            .and_then(|start| Some(start..start.checked_add(width)?))
+            .filter(|range| range.end <= self.heap.len())
+            .ok_or(VmError::MemoryOutOfBounds {
+                offset,
+                address,
+                width,
+            })
+    }
+}
+
+/// Shifts `value` for one of the shift opcodes. Only the low 5 bits of the amount
+/// are used, so shifting by 32 is the same as shifting by 0
+fn shift(opcode: Opcode, value: i32, amount: u32) -> i32 {
+    match opcode {
+        Opcode::SHL | Opcode::SHLI => value.wrapping_shl(amount),
+        Opcode::SHR | Opcode::SHRI => (value as u32).wrapping_shr(amount) as i32,
+        _ => value.wrapping_shr(amount),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_create_vm() {
+        let test_vm = VM::new();
+        assert_eq!(test_vm.registers[0], 0);
+    }
+
+    #[test]
+    fn test_opcode_hlt() {
+        let mut test_vm = VM::new();
+        test_vm.set_program(vec![
+            [0, 0, 0, 0], // Halt
+        ]);
+
+        assert_eq!(test_vm.run_once(), Ok(ExitStatus::Halted));
+        assert_eq!(test_vm.pc, 4);
+    }
+
+    #[test]
+    fn test_opcode_load() {
+        let mut test_vm = VM::new();
+        test_vm.set_program(vec![
+            [1, 0, 1, 244], // Set reg0 to 500
+            [0, 0, 0, 0],   // Halt
+        ]);
+
+        test_vm.run().unwrap();
+        assert_eq!(test_vm.registers[0], 500);
+    }
+
+    #[test]
+    fn test_opcode_load_negative() {
+        let mut test_vm = VM::new();
+        test_vm.set_program(vec![
+            [1, 0, 255, 255], // Set reg0 to -1
+            [0, 0, 0, 0],     // Halt
+        ]);
+
+        test_vm.run().unwrap();
+        assert_eq!(test_vm.registers[0], -1);
+    }
+
+    #[test]
+    fn test_opcode_loadhi() {
+        let mut test_vm = VM::new();
+        test_vm.set_program(vec![
+            [1, 0, 0, 5],      // Set reg0 to 5
+            [28, 0, 255, 254], // Set the upper half of reg0 to 0xFFFE, clearing the lower
+            [0, 0, 0, 0],      // Halt
+        ]);
+
+        test_vm.run().unwrap();
+        assert_eq!(test_vm.registers[0], 0xFFFE_0000_u32 as i32);
+    }
+
+    #[test]
+    fn test_opcode_loadlo() {
+        let mut test_vm = VM::new();
+        test_vm.set_program(vec![
+            [28, 0, 255, 254], // Set the upper half of reg0 to 0xFFFE
+            [29, 0, 238, 144], // Set the lower half of reg0 to 0xEE90
+            [0, 0, 0, 0],      // Halt
+        ]);
+
+        test_vm.run().unwrap();
+        assert_eq!(test_vm.registers[0], -70000);
+    }
+
+    #[test]
+    fn test_opcode_add() {
+        let mut test_vm = VM::new();
+        test_vm.set_program(vec![
+            [1, 0, 1, 244], // Set reg0 to 500
+            [1, 1, 0, 250], // Set reg1 to 250
+            [2, 0, 1, 2],   // Set reg2 to reg0 + reg2
+            [0, 0, 0, 0],   // Halt
+        ]);
+
+        test_vm.run().unwrap();
+        assert_eq!(test_vm.registers[2], 750);
+    }
+
+    #[test]
+    fn test_opcode_sub() {
+        let mut test_vm = VM::new();
+        test_vm.set_program(vec![
+            [1, 0, 1, 244], // Set reg0 to 500
+            [1, 1, 0, 250], // Set reg1 to 250
+            [3, 0, 1, 2],   // Set reg2 to reg0 - reg1
+            [0, 0, 0, 0],   // Halt
+        ]);
+
+        test_vm.run().unwrap();
+        assert_eq!(test_vm.registers[2], 250);
+    }
+    #[test]
+    fn test_opcode_mul() {
+        let mut test_vm = VM::new();
+        test_vm.set_program(vec![
+            [1, 0, 0, 8], // Set reg0 to 8
+            [1, 1, 0, 6], // Set reg1 to 6
+            [4, 0, 1, 2], // Set reg2 to reg0 * reg1
+            [0, 0, 0, 0], // Halt
+        ]);
+
+        test_vm.run().unwrap();
+        assert_eq!(test_vm.registers[2], 48);
+    }
+
+    #[test]
+    fn test_opcode_div() {
+        let mut test_vm = VM::new();
+        test_vm.set_program(vec![
+            [1, 0, 0, 8], // Set reg0 to 8
+            [1, 1, 0, 6], // Set reg1 to 6
+            [5, 0, 1, 2], // Set reg2 to reg0 / reg1 and remainder to reg0 % reg1
+            [0, 0, 0, 0], // Halt
+        ]);
+
+        test_vm.run().unwrap();
+        assert_eq!(test_vm.registers[2], 1);
+        assert_eq!(test_vm.remainder, 2);
+    }
+
+    #[test]
+    fn test_opcode_jmp() {
+        let mut test_vm = VM::new();
+        test_vm.set_program(vec![
+            [1, 0, 0, 0], // Set reg0 to 0
+            [6, 0, 0, 0], // Jump to reg0
+        ]);
+
+        test_vm.run_once().unwrap();
+        test_vm.run_once().unwrap();
+        assert_eq!(test_vm.pc, 0);
+    }
+
+    #[test]
+    fn test_opcode_jmpf() {
+        let mut test_vm = VM::new();
+        test_vm.set_program(vec![
+            [1, 0, 0, 8], // Set reg0 to 8
+            [7, 0, 0, 0], // Jump forward reg0
+        ]);
+
+        test_vm.run_once().unwrap();
+        test_vm.run_once().unwrap();
+        assert_eq!(test_vm.pc, 12);
+    }
+
+    #[test]
+    fn test_opcode_jmpb() {
+        let mut test_vm = VM::new();
+        test_vm.set_program(vec![
+            [1, 0, 0, 4], // Set reg0 to 4
+            [8, 0, 0, 0], // Jump backward reg0
+        ]);
+
+        test_vm.run_once().unwrap();
+        test_vm.run_once().unwrap();
+        assert_eq!(test_vm.pc, 0);
+    }
+
+    #[test]
+    fn test_opcode_eq() {
+        let mut test_vm = VM::new();
+        test_vm.set_program(vec![
+            [1, 0, 0, 6], // Set reg0 to 6
+            [1, 1, 0, 6], // Set reg1 to 6
+            [9, 0, 1, 0], // Set the condition flag to reg0 == reg1
+            [1, 1, 0, 7], // Set reg1 to 7
+            [9, 0, 1, 0], // Set the condition flag to reg0 == reg1
+        ]);
+
+        test_vm.run_once().unwrap();
+        test_vm.run_once().unwrap();
+        test_vm.run_once().unwrap();
+        assert!(test_vm.flags.contains(Flags::CONDITION));
+        test_vm.run_once().unwrap();
+        test_vm.run_once().unwrap();
+        assert!(!test_vm.flags.contains(Flags::CONDITION));
+    }
+
+    #[test]
+    fn test_opcode_neq() {
+        let mut test_vm = VM::new();
+        test_vm.set_program(vec![
+            [1, 0, 0, 6],  // Set reg0 to 6
+            [1, 1, 0, 6],  // Set reg1 to 6
+            [10, 0, 1, 0], // Set the condition flag to reg0 != reg1
+            [1, 1, 0, 7],  // Set reg1 to 7
+            [10, 0, 1, 0], // Set the condition flag to reg0 == reg1
+        ]);
+
+        test_vm.run_once().unwrap();
+        test_vm.run_once().unwrap();
+        test_vm.run_once().unwrap();
+        assert!(!test_vm.flags.contains(Flags::CONDITION));
+        test_vm.run_once().unwrap();
+        test_vm.run_once().unwrap();
+        assert!(test_vm.flags.contains(Flags::CONDITION));
+    }
+
+    #[test]
+    fn test_opcode_gt() {
+        let mut test_vm = VM::new();
+        test_vm.set_program(vec![
+            [1, 0, 0, 6],  // Set reg0 to 6
+            [1, 1, 0, 7],  // Set reg1 to 7
+            [11, 0, 1, 0], // Set the condition flag to reg0 > reg1
+            [1, 1, 0, 5],  // Set reg1 to 5
+            [11, 0, 1, 0], // Set the condition flag to reg0 > reg1
+            [1, 1, 0, 6],  // Set reg1 to 6
+            [11, 0, 1, 0], // Set the condition flag to reg0 > reg1
+        ]);
+
+        test_vm.run_once().unwrap();
+        test_vm.run_once().unwrap();
+        test_vm.run_once().unwrap();
+        assert!(!test_vm.flags.contains(Flags::CONDITION));
+        test_vm.run_once().unwrap();
+        test_vm.run_once().unwrap();
+        assert!(test_vm.flags.contains(Flags::CONDITION));
+        test_vm.run_once().unwrap();
+        test_vm.run_once().unwrap();
+        assert!(!test_vm.flags.contains(Flags::CONDITION));
+    }
+
+    #[test]
+    fn test_opcode_lt() {
+        let mut test_vm = VM::new();
+        test_vm.set_program(vec![
+            [1, 0, 0, 6],  // Set reg0 to 6
+            [1, 1, 0, 7],  // Set reg1 to 7
+            [12, 0, 1, 0], // Set the condition flag to reg0 < reg1
+            [1, 1, 0, 5],  // Set reg1 to 5
+            [12, 0, 1, 0], // Set the condition flag to reg0 < reg1
+            [1, 1, 0, 6],  // Set reg1 to 6
+            [12, 0, 1, 0], // Set the condition flag to reg0 < reg1
+        ]);
+
+        test_vm.run_once().unwrap();
+        test_vm.run_once().unwrap();
+        test_vm.run_once().unwrap();
+        assert!(test_vm.flags.contains(Flags::CONDITION));
+        test_vm.run_once().unwrap();
+        test_vm.run_once().unwrap();
+        assert!(!test_vm.flags.contains(Flags::CONDITION));
+        test_vm.run_once().unwrap();
+        test_vm.run_once().unwrap();
+        assert!(!test_vm.flags.contains(Flags::CONDITION));
+    }
+
+    #[test]
+    fn test_opcode_gtq() {
+        let mut test_vm = VM::new();
+        test_vm.set_program(vec![
+            [1, 0, 0, 6],  // Set reg0 to 6
+            [1, 1, 0, 7],  // Set reg1 to 7
+            [13, 0, 1, 0], // Set the condition flag to reg0 >= reg1
+            [1, 1, 0, 5],  // Set reg1 to 5
+            [13, 0, 1, 0], // Set the condition flag to reg0 >= reg1
+            [1, 1, 0, 6],  // Set reg1 to 6
+            [13, 0, 1, 0], // Set the condition flag to reg0 >= reg1
+        ]);
+
+        test_vm.run_once().unwrap();
+        test_vm.run_once().unwrap();
+        test_vm.run_once().unwrap();
+        assert!(!test_vm.flags.contains(Flags::CONDITION));
+        test_vm.run_once().unwrap();
+        test_vm.run_once().unwrap();
+        assert!(test_vm.flags.contains(Flags::CONDITION));
+        test_vm.run_once().unwrap();
+        test_vm.run_once().unwrap();
+        assert!(test_vm.flags.contains(Flags::CONDITION));
+    }
+
+    #[test]
+    fn test_opcode_ltq() {
+        let mut test_vm = VM::new();
+        test_vm.set_program(vec![
+            [1, 0, 0, 6],  // Set reg0 to 6
+            [1, 1, 0, 7],  // Set reg1 to 7
+            [14, 0, 1, 0], // Set the condition flag to reg0 <= reg1
+            [1, 1, 0, 5],  // Set reg1 to 5
+            [14, 0, 1, 0], // Set the condition flag to reg0 <= reg1
+            [1, 1, 0, 6],  // Set reg1 to 6
+            [14, 0, 1, 0], // Set the condition flag to reg0 <= reg1
+        ]);
+
+        test_vm.run_once().unwrap();
+        test_vm.run_once().unwrap();
+        test_vm.run_once().unwrap();
+        assert!(test_vm.flags.contains(Flags::CONDITION));
+        test_vm.run_once().unwrap();
+        test_vm.run_once().unwrap();
+        assert!(!test_vm.flags.contains(Flags::CONDITION));
+        test_vm.run_once().unwrap();
+        test_vm.run_once().unwrap();
+        assert!(test_vm.flags.contains(Flags::CONDITION));
+    }
+
+    #[test]
+    fn test_opcode_jeq() {
+        let mut test_vm = VM::new();
+        test_vm.set_program(vec![
+            [1, 0, 0, 6],  // Set reg0 to 6
+            [1, 1, 0, 7],  // Set reg1 to 7
+            [12, 0, 1, 0], // Set the zero flag to reg0 < reg1
+            [1, 2, 0, 24], // Set reg2 to 24
+            [15, 2, 0, 0], // Jump to reg2 if the zero flag is set
+            [0, 0, 0, 0],  // Halt
+            [1, 1, 0, 5],  // Set reg1 to 5
+            [12, 0, 1, 0], // Set the zero flag to reg0 < reg1
+            [1, 2, 0, 4],  // Set reg2 to 4
+            [15, 2, 0, 0], // Jump to reg2 if the zero flag is set
+        ]);
+
+        test_vm.run_once().unwrap();
+        test_vm.run_once().unwrap();
+        test_vm.run_once().unwrap();
+        test_vm.run_once().unwrap();
+        test_vm.run_once().unwrap();
+        assert_eq!(test_vm.pc, 24);
+        test_vm.run_once().unwrap();
+        test_vm.run_once().unwrap();
+        test_vm.run_once().unwrap();
+        test_vm.run_once().unwrap();
+        assert_eq!(test_vm.pc, 40);
+    }
+
+    #[test]
+    fn test_opcode_jneq() {
+        let mut test_vm = VM::new();
+        test_vm.set_program(vec![
+            [1, 0, 0, 6],  // Set reg0 to 6
+            [1, 1, 0, 7],  // Set reg1 to 7
+            [12, 0, 1, 0], // Set the zero flag to reg0 < reg1
+            [1, 2, 0, 24], // Set reg2 to 24
+            [16, 2, 0, 0], // Jump to reg2 unless the zero flag is set
+            [1, 1, 0, 5],  // Set reg1 to 5
+            [12, 0, 1, 0], // Set the zero flag to reg0 < reg1
+            [1, 2, 0, 4],  // Set reg2 to 4
+            [16, 2, 0, 0], // Jump to reg2 unless the zero flag is set
+        ]);
+
+        test_vm.run_once().unwrap();
+        test_vm.run_once().unwrap();
+        test_vm.run_once().unwrap();
+        test_vm.run_once().unwrap();
+        test_vm.run_once().unwrap();
+        assert_eq!(test_vm.pc, 20);
+        test_vm.run_once().unwrap();
+        test_vm.run_once().unwrap();
+        test_vm.run_once().unwrap();
+        test_vm.run_once().unwrap();
+        assert_eq!(test_vm.pc, 4);
+    }
+
+    #[test]
+    fn test_unknown_opcode() {
+        let mut test_vm = VM::new();
+        test_vm.set_program(vec![
+            [1, 0, 0, 6],   // Set reg0 to 6
+            [255, 0, 0, 0], // Not an opcode
+        ]);
+
+        test_vm.run_once().unwrap();
+        assert_eq!(
+            test_vm.run_once(),
+            Err(VmError::UnknownOpcode {
+                offset: 4,
+                byte: 255
+            })
+        );
+    }
+
+    #[test]
+    fn test_pc_out_of_bounds() {
+        let mut test_vm = VM::new();
+        test_vm.set_program(vec![
+            [1, 0, 0, 6], // Set reg0 to 6
+        ]);
+
+        assert_eq!(test_vm.run(), Err(VmError::PcOutOfBounds { pc: 4, len: 4 }));
+    }
+
+    #[test]
+    fn test_misaligned_jump() {
+        let mut test_vm = VM::new();
+        test_vm.set_program(vec![
+            [1, 0, 0, 6], // Set reg0 to 6
+            [6, 0, 0, 0], // Jump to reg0
+        ]);
+
+        assert_eq!(
+            test_vm.run(),
+            Err(VmError::MisalignedJump {
+                offset: 4,
+                target: 6
+            })
+        );
+        assert_eq!(test_vm.pc, 8);
+    }
+
+    #[test]
+    fn test_misaligned_return() {
+        let mut test_vm = VM::new();
+        test_vm.stack.push(2);
+        test_vm.set_program(vec![[27, 0, 0, 0]]);
+
+        assert_eq!(
+            test_vm.run_once(),
+            Err(VmError::MisalignedJump {
+                offset: 0,
+                target: 2
+            })
+        );
+    }
+
+    #[test]
+    fn test_negative_jump() {
+        let mut test_vm = VM::new();
+        test_vm.set_program(vec![[64, 255, 248, 0]]); // JMPR #-8
+
+        assert_eq!(
+            test_vm.run_once(),
+            Err(VmError::NegativeJump {
+                offset: 0,
+                target: -8
+            })
+        );
+
+        test_vm.set_program(vec![[1, 0, 0, 8], [8, 0, 0, 0]]); // JMPB 8 from offset 4
+        test_vm.pc = 0;
+        assert_eq!(
+            test_vm.run(),
+            Err(VmError::NegativeJump {
+                offset: 4,
+                target: -4
+            })
+        );
+    }
+
+    #[test]
+    fn test_instructions_are_one_word() {
+        let mut test_vm = VM::new();
+        test_vm.set_program(vec![
+            [9, 0, 1, 0],  // Set the zero flag to reg0 == reg1
+            [15, 2, 0, 0], // Jump to reg2 if the zero flag is set
+            [24, 0, 0, 0], // Push reg0
+            [33, 0, 1, 0], // Set reg1 to !reg0
+        ]);
+        test_vm.registers[2] = 12;
+
+        for pc in [4, 12, 16] {
+            test_vm.run_once().unwrap();
+            assert_eq!(test_vm.pc, pc);
+        }
+    }
+
+    #[test]
+    fn test_decode_cache_sees_appended_code() {
+        let mut test_vm = VM::new();
+        test_vm.set_program(vec![[1, 0, 0, 5]]);
+
+        test_vm.run_once().unwrap();
+        test_vm.program.extend([1, 1, 0, 6]);
+        test_vm.run_once().unwrap();
+        assert_eq!(test_vm.registers[1], 6);
+    }
+
+    #[test]
+    fn test_invalidate_cache() {
+        let mut test_vm = VM::new();
+        test_vm.set_program(vec![[1, 0, 0, 5]]);
+
+        test_vm.program[3] = 7;
+        test_vm.invalidate_cache();
+        test_vm.run_once().unwrap();
+        assert_eq!(test_vm.registers[0], 7);
+    }
+
+    #[test]
+    fn test_without_decode_cache() {
+        let mut test_vm = VM::new();
+        test_vm.use_decode_cache = false;
+        test_vm.registers[0] = 3;
+        test_vm.registers[1] = 1;
+        test_vm.set_program(vec![
+            [3, 0, 1, 0],      // Set reg0 to reg0 - reg1
+            [72, 255, 252, 0], // Jump back to the SUB if reg0 is not zero
+            [0, 0, 0, 0],      // Halt
+        ]);
+
+        assert_eq!(test_vm.run(), Ok(ExitStatus::Halted));
+        assert_eq!(test_vm.registers[0], 0);
+
+        test_vm.program[4] = 255;
+        test_vm.pc = 4;
+        assert_eq!(
+            test_vm.run_once(),
+            Err(VmError::UnknownOpcode {
+                offset: 4,
+                byte: 255
+            })
+        );
+    }
+
+    #[test]
+    fn test_out_of_fuel() {
+        let mut test_vm = VM::new();
+        test_vm.fuel = Some(10);
+        test_vm.set_program(vec![
+            [1, 0, 0, 0], // Set reg0 to 0
+            [6, 0, 0, 0], // Jump to reg0
+        ]);
+
+        assert_eq!(test_vm.run(), Err(VmError::OutOfFuel { offset: 0 }));
+        assert_eq!(test_vm.fuel, Some(0));
+        assert_eq!(test_vm.pc, 0);
+
+        test_vm.fuel = Some(1);
+        assert_eq!(test_vm.run(), Err(VmError::OutOfFuel { offset: 4 }));
+        assert_eq!(test_vm.pc, 4);
+    }
+
+    #[test]
+    fn test_fuel_costs() {
+        let mut test_vm = VM::new();
+        test_vm.fuel = Some(5);
+        test_vm.fuel_costs.set(Opcode::ALOC, 4);
+        test_vm.registers[0] = 8;
+        test_vm.set_program(vec![
+            [17, 0, 0, 0], // Grow the heap by reg0 bytes
+            [17, 0, 0, 0], // Grow the heap by reg0 bytes
+            [0, 0, 0, 0],  // Halt
+        ]);
+
+        assert_eq!(test_vm.run(), Err(VmError::OutOfFuel { offset: 4 }));
+        assert_eq!(test_vm.heap.len(), 8);
+        assert_eq!(test_vm.fuel, Some(1));
+
+        test_vm.fuel = Some(5);
+        assert_eq!(test_vm.run(), Ok(ExitStatus::Halted));
+        assert_eq!(test_vm.heap.len(), 16);
+        assert_eq!(test_vm.fuel, Some(0));
+    }
+
+    #[test]
+    fn test_breakpoints() {
+        let mut test_vm = VM::new();
+        test_vm.breakpoints.insert(4);
+        test_vm.set_program(vec![
+            [1, 0, 0, 1], // Set reg0 to 1
+            [1, 0, 0, 2], // Set reg0 to 2
+            [0, 0, 0, 0], // Halt
+        ]);
+
+        assert_eq!(test_vm.run(), Ok(ExitStatus::Paused));
+        assert_eq!(test_vm.pc, 4);
+        assert_eq!(test_vm.registers[0], 1);
+
+        assert_eq!(test_vm.run(), Ok(ExitStatus::Halted));
+        assert_eq!(test_vm.registers[0], 2);
+    }
+
+    #[test]
+    fn test_step_hook() {
+        let mut test_vm = VM::new();
+        test_vm.step_hook = Some(Box::new(|vm, offset| offset == 4 || vm.registers[0] > 2));
+        test_vm.set_program(vec![
+            [1, 0, 0, 1], // Set reg0 to 1
+            [1, 0, 0, 2], // Set reg0 to 2
+            [1, 0, 0, 3], // Set reg0 to 3
+            [0, 0, 0, 0], // Halt
+        ]);
+
+        assert_eq!(test_vm.run(), Ok(ExitStatus::Paused));
+        assert_eq!(test_vm.pc, 8);
+        assert_eq!(test_vm.run_once(), Ok(ExitStatus::Paused));
+        assert_eq!(test_vm.registers[0], 3);
+        assert_eq!(test_vm.run(), Ok(ExitStatus::Halted));
+        assert!(test_vm.step_hook.is_some());
+    }
+
+    /// Doubles `$0`, and handles syscall 1 in place of any registered earlier
+    struct Doubler;
+
+    impl HostFunctions for Doubler {
+        fn call(&mut self, vm: &mut VM, number: u16) -> Option<Result<ExitStatus, String>> {
+            match number {
+                1 | 7 => {
+                    vm.registers[0] *= 2;
+                    Some(Ok(ExitStatus::Running))
+                }
+                _ => None,
+            }
+        }
+    }
+
+    #[test]
+    fn test_opcode_syscall() {
+        let mut test_vm = VM::new();
+        test_vm.registers[0] = 5;
+        test_vm.register(Builtins::new(std::io::empty(), std::io::sink()));
+        test_vm.register(Doubler);
+        test_vm.set_program(vec![
+            [90, 0, 7, 0], // Double reg0
+            [90, 0, 1, 0], // Double reg0 rather than printing it
+            [90, 0, 0, 0], // Exit with status reg0
+        ]);
+
+        assert_eq!(test_vm.run(), Ok(ExitStatus::Exited(20)));
+    }
+
+    #[test]
+    fn test_unknown_syscall() {
+        let mut test_vm = VM::new();
+        test_vm.set_program(vec![[90, 0, 1, 0]]);
+
+        assert_eq!(
+            test_vm.run(),
+            Err(VmError::UnknownSyscall {
+                offset: 0,
+                number: 1
+            })
+        );
+    }
+
+    #[test]
+    fn test_run_for() {
+        let program = vec![
+            [3, 0, 1, 0],      // Set reg0 to reg0 - reg1
+            [72, 255, 252, 0], // Jump back to the SUB if reg0 is not zero
+            [0, 0, 0, 0],      // Halt
+        ];
+        let mut vms: Vec<VM> = [2, 5]
+            .into_iter()
+            .map(|count| {
+                let mut vm = VM::new();
+                vm.registers[0] = count;
+                vm.registers[1] = 1;
+                vm.set_program(program.clone());
+                vm
+            })
+            .collect();
+
+        for vm in &mut vms {
+            assert_eq!(vm.run_for(4), Ok(ExitStatus::Running));
+        }
+        assert_eq!(vms[0].registers[0], 0);
+        assert_eq!(vms[1].registers[0], 3);
+
+        assert_eq!(vms[0].run_for(4), Ok(ExitStatus::Halted));
+        assert_eq!(vms[1].run_for(6), Ok(ExitStatus::Running));
+        assert_eq!(vms[1].run_for(1), Ok(ExitStatus::Halted));
+        assert_eq!(vms[1].registers[0], 0);
+    }
+
+    #[test]
+    fn test_invalid_register() {
+        let mut test_vm = VM::new();
+        test_vm.set_program(vec![
+            [1, 32, 0, 6], // Set reg32 to 6
+        ]);
+
+        assert_eq!(
+            test_vm.run_once(),
+            Err(VmError::InvalidRegister {
+                offset: 1,
+                register: 32
+            })
+        );
+    }
+
+    #[test]
+    fn test_divide_by_zero() {
+        let mut test_vm = VM::new();
+        test_vm.set_program(vec![
+            [1, 0, 0, 8], // Set reg0 to 8
+            [5, 0, 1, 2], // Set reg2 to reg0 / reg1
+        ]);
+
+        assert_eq!(test_vm.run(), Err(VmError::DivideByZero { offset: 4 }));
+    }
+
+    #[test]
+    fn test_arithmetic_overflow() {
+        let mut test_vm = VM::new();
+        test_vm.registers[0] = i32::MAX;
+        test_vm.registers[1] = 1;
+        test_vm.set_program(vec![
+            [2, 0, 1, 2], // Set reg2 to reg0 + reg1
+        ]);
+
+        assert_eq!(
+            test_vm.run(),
+            Err(VmError::ArithmeticOverflow { offset: 0 })
+        );
+    }
+
+    #[test]
+    fn test_wrapping_arithmetic() {
+        let mut test_vm = VM::new();
+        test_vm.arithmetic_mode = ArithmeticMode::Wrapping;
+        test_vm.registers[0] = i32::MAX;
+        test_vm.registers[1] = 1;
+        test_vm.set_program(vec![
+            [2, 0, 1, 2], // Set reg2 to reg0 + reg1
+        ]);
+
+        test_vm.run_once().unwrap();
+        assert_eq!(test_vm.registers[2], i32::MIN);
+        assert!(test_vm.flags.contains(Flags::OVERFLOW));
+        assert!(!test_vm.flags.contains(Flags::CARRY));
+    }
+
+    #[test]
+    fn test_saturating_arithmetic() {
+        let mut test_vm = VM::new();
+        test_vm.arithmetic_mode = ArithmeticMode::Saturating;
+        test_vm.registers[0] = i32::MIN;
+        test_vm.registers[1] = 2;
+        test_vm.set_program(vec![
+            [4, 0, 1, 2], // Set reg2 to reg0 * reg1
+            [5, 0, 3, 4], // Set reg4 to reg0 / reg3
+        ]);
+        test_vm.registers[3] = -1;
+
+        test_vm.run_once().unwrap();
+        assert_eq!(test_vm.registers[2], i32::MIN);
+        test_vm.run_once().unwrap();
+        assert_eq!(test_vm.registers[4], i32::MAX);
+        assert!(test_vm.flags.contains(Flags::OVERFLOW));
+    }
+
+    #[test]
+    fn test_arithmetic_clears_flags() {
+        let mut test_vm = VM::new();
+        test_vm.registers[0] = -1;
+        test_vm.registers[1] = 1;
+        test_vm.set_program(vec![
+            [2, 0, 1, 2], // Set reg2 to reg0 + reg1, carrying
+            [2, 1, 1, 2], // Set reg2 to reg1 + reg1
+        ]);
+
+        test_vm.run_once().unwrap();
+        assert!(test_vm.flags.contains(Flags::CARRY));
+        test_vm.run_once().unwrap();
+        assert!(!test_vm.flags.contains(Flags::CARRY));
+        assert!(!test_vm.flags.contains(Flags::OVERFLOW));
+    }
+
+    #[test]
+    fn test_truncated_operands() {
+        let mut test_vm = VM::new();
+        test_vm.program = vec![1, 0, 1];
+
+        assert_eq!(test_vm.run(), Err(VmError::TruncatedOperands { offset: 3 }));
+    }
+
+    #[test]
+    fn test_run_halts() {
+        let mut test_vm = VM::new();
+        test_vm.set_program(vec![
+            [0, 0, 0, 0], // Halt
+        ]);
+
+        assert_eq!(test_vm.run(), Ok(ExitStatus::Halted));
+    }
+
+    #[test]
+    fn test_opcode_aloc() {
+        let mut test_vm = VM::new();
+        test_vm.set_program(vec![
+            [1, 0, 0, 16], // Set reg0 to 16
+            [17, 0, 0, 0], // Grow the heap by reg0 bytes
+            [17, 0, 0, 0], // Grow the heap by reg0 bytes
+        ]);
+
+        test_vm.run_once().unwrap();
+        test_vm.run_once().unwrap();
+        assert_eq!(test_vm.heap.len(), 16);
+        assert_eq!(test_vm.pc, 8);
+        test_vm.run_once().unwrap();
+        assert_eq!(test_vm.heap.len(), 32);
+    }
+
+    #[test]
+    fn test_opcode_aloc_negative() {
+        let mut test_vm = VM::new();
+        test_vm.registers[0] = -1;
+        test_vm.set_program(vec![
+            [17, 0, 0, 0], // Grow the heap by reg0 bytes
+        ]);
+
+        assert_eq!(
+            test_vm.run_once(),
+            Err(VmError::InvalidAllocation {
+                offset: 0,
+                size: -1
+            })
+        );
+    }
+
+    #[test]
+    fn test_opcode_aloc_heap_limit() {
+        let mut test_vm = VM::new();
+        test_vm.heap_limit = 24;
+        test_vm.registers[0] = 16;
+        test_vm.set_program(vec![
+            [17, 0, 0, 0], // Grow the heap by reg0 bytes
+            [17, 0, 0, 0], // Grow the heap by reg0 bytes
+        ]);
+
+        test_vm.run_once().unwrap();
+        assert_eq!(
+            test_vm.run_once(),
+            Err(VmError::HeapLimitExceeded {
+                offset: 4,
+                size: 16
+            })
+        );
+        assert_eq!(test_vm.heap.len(), 16);
+
+        test_vm.heap_limit = DEFAULT_HEAP_LIMIT;
+        test_vm.registers[0] = i32::MAX;
+        test_vm.pc = 4;
+        assert!(matches!(
+            test_vm.run_once(),
+            Err(VmError::HeapLimitExceeded { .. })
+        ));
+    }
+
+    #[test]
+    fn test_opcode_loadb() {
+        let mut test_vm = VM::new();
+        test_vm.heap = vec![0, 0xFF, 0x12];
+        test_vm.set_program(vec![
+            [1, 0, 0, 1],  // Set reg0 to 1
+            [18, 1, 0, 0], // Set reg1 to the byte at reg0
+        ]);
+
+        test_vm.run_once().unwrap();
+        test_vm.run_once().unwrap();
+        assert_eq!(test_vm.registers[1], 0xFF);
+        assert_eq!(test_vm.pc, 8);
+    }
+
+    #[test]
+    fn test_opcode_loadh() {
+        let mut test_vm = VM::new();
+        test_vm.heap = vec![0, 0x34, 0x12];
+        test_vm.set_program(vec![
+            [1, 0, 0, 1],  // Set reg0 to 1
+            [19, 1, 0, 0], // Set reg1 to the half word at reg0
+        ]);
+
+        test_vm.run_once().unwrap();
+        test_vm.run_once().unwrap();
+        assert_eq!(test_vm.registers[1], 0x1234);
+    }
+
+    #[test]
+    fn test_opcode_loadw() {
+        let mut test_vm = VM::new();
+        test_vm.heap = vec![0xFF, 0xFF, 0xFF, 0xFF];
+        test_vm.set_program(vec![
+            [20, 1, 0, 0], // Set reg1 to the word at reg0
+        ]);
+
+        test_vm.run_once().unwrap();
+        assert_eq!(test_vm.registers[1], -1);
+    }
+
+    #[test]
+    fn test_opcode_storeb() {
+        let mut test_vm = VM::new();
+        test_vm.heap = vec![0; 4];
+        test_vm.set_program(vec![
+            [1, 0, 1, 0x23], // Set reg0 to 0x123
+            [1, 1, 0, 2],    // Set reg1 to 2
+            [21, 0, 1, 0],   // Store the low byte of reg0 at reg1
+        ]);
+
+        test_vm.run_once().unwrap();
+        test_vm.run_once().unwrap();
+        test_vm.run_once().unwrap();
+        assert_eq!(test_vm.heap, vec![0, 0, 0x23, 0]);
+        assert_eq!(test_vm.pc, 12);
+    }
+
+    #[test]
+    fn test_opcode_storeh() {
+        let mut test_vm = VM::new();
+        test_vm.heap = vec![0; 4];
+        test_vm.set_program(vec![
+            [1, 0, 0x12, 0x34], // Set reg0 to 0x1234
+            [1, 1, 0, 1],       // Set reg1 to 1
+            [22, 0, 1, 0],      // Store the low half word of reg0 at reg1
+        ]);
+
+        test_vm.run_once().unwrap();
+        test_vm.run_once().unwrap();
+        test_vm.run_once().unwrap();
+        assert_eq!(test_vm.heap, vec![0, 0x34, 0x12, 0]);
+    }
+
+    #[test]
+    fn test_opcode_storew() {
+        let mut test_vm = VM::new();
+        test_vm.heap = vec![0; 4];
+        test_vm.registers[0] = -2;
+        test_vm.set_program(vec![
+            [23, 0, 1, 0], // Store reg0 at reg1
+        ]);
+
+        test_vm.run_once().unwrap();
+        assert_eq!(test_vm.heap, vec![0xFE, 0xFF, 0xFF, 0xFF]);
+    }
+
+    #[test]
+    fn test_memory_out_of_bounds() {
+        let mut test_vm = VM::new();
+        test_vm.heap = vec![0; 4];
+        test_vm.set_program(vec![
+            [1, 0, 0, 2],  // Set reg0 to 2
+            [20, 1, 0, 0], // Set reg1 to the word at reg0
+        ]);
+
+        test_vm.run_once().unwrap();
+        assert_eq!(
+            test_vm.run_once(),
+            Err(VmError::MemoryOutOfBounds {
+                offset: 4,
+                address: 2,
+                width: 4
+            })
+        );
+    }
+
+    #[test]
+    fn test_opcode_push() {
+        let mut test_vm = VM::new();
+        test_vm.set_program(vec![
+            [1, 0, 0, 6],  // Set reg0 to 6
+            [24, 0, 0, 0], // Push reg0
+        ]);
+
+        test_vm.run_once().unwrap();
+        test_vm.run_once().unwrap();
+        assert_eq!(test_vm.stack, vec![6]);
+        assert_eq!(test_vm.pc, 8);
+    }
+
+    #[test]
+    fn test_opcode_pop() {
+        let mut test_vm = VM::new();
+        test_vm.stack = vec![6];
+        test_vm.set_program(vec![
+            [25, 0, 0, 0], // Pop into reg0
+        ]);
+
+        test_vm.run_once().unwrap();
+        assert_eq!(test_vm.registers[0], 6);
+        assert!(test_vm.stack.is_empty());
+        assert_eq!(test_vm.pc, 4);
+    }
+
+    #[test]
+    fn test_opcode_call() {
+        let mut test_vm = VM::new();
+        test_vm.set_program(vec![
+            [1, 0, 0, 12], // Set reg0 to 12
+            [26, 0, 0, 0], // Call reg0
+        ]);
+
+        test_vm.run_once().unwrap();
+        test_vm.run_once().unwrap();
+        assert_eq!(test_vm.pc, 12);
+        assert_eq!(test_vm.stack, vec![8]);
+    }
+
+    #[test]
+    fn test_opcode_and() {
+        let mut test_vm = VM::new();
+        test_vm.registers[0] = 0b1100;
+        test_vm.registers[1] = 0b1010;
+        test_vm.set_program(vec![[30, 0, 1, 2]]);
+
+        test_vm.run_once().unwrap();
+        assert_eq!(test_vm.registers[2], 0b1000);
+    }
+
+    #[test]
+    fn test_opcode_or() {
+        let mut test_vm = VM::new();
+        test_vm.registers[0] = 0b1100;
+        test_vm.registers[1] = 0b1010;
+        test_vm.set_program(vec![[31, 0, 1, 2]]);
+
+        test_vm.run_once().unwrap();
+        assert_eq!(test_vm.registers[2], 0b1110);
+    }
+
+    #[test]
+    fn test_opcode_xor() {
+        let mut test_vm = VM::new();
+        test_vm.registers[0] = 0b1100;
+        test_vm.registers[1] = 0b1010;
+        test_vm.set_program(vec![[32, 0, 1, 2]]);
+
+        test_vm.run_once().unwrap();
+        assert_eq!(test_vm.registers[2], 0b0110);
+    }
+
+    #[test]
+    fn test_opcode_not() {
+        let mut test_vm = VM::new();
+        test_vm.registers[0] = 0;
+        test_vm.set_program(vec![[33, 0, 1, 0]]);
+
+        test_vm.run_once().unwrap();
+        assert_eq!(test_vm.registers[1], -1);
+        assert_eq!(test_vm.pc, 4);
+    }
+
+    #[test]
+    fn test_opcode_shl() {
+        let mut test_vm = VM::new();
+        test_vm.registers[0] = 3;
+        test_vm.registers[1] = 4;
+        test_vm.set_program(vec![[34, 0, 1, 2]]);
+
+        test_vm.run_once().unwrap();
+        assert_eq!(test_vm.registers[2], 48);
+    }
+
+    #[test]
+    fn test_opcode_shr() {
+        let mut test_vm = VM::new();
+        test_vm.registers[0] = -16;
+        test_vm.registers[1] = 28;
+        test_vm.set_program(vec![[35, 0, 1, 2]]);
+
+        test_vm.run_once().unwrap();
+        assert_eq!(test_vm.registers[2], 0xF);
+    }
+
+    #[test]
+    fn test_opcode_sar() {
+        let mut test_vm = VM::new();
+        test_vm.registers[0] = -16;
+        test_vm.registers[1] = 2;
+        test_vm.set_program(vec![[36, 0, 1, 2]]);
+
+        test_vm.run_once().unwrap();
+        assert_eq!(test_vm.registers[2], -4);
+    }
+
+    #[test]
+    fn test_opcode_shift_amount_wraps() {
+        let mut test_vm = VM::new();
+        test_vm.registers[0] = 1;
+        test_vm.registers[1] = 33;
+        test_vm.set_program(vec![[34, 0, 1, 2]]);
+
+        test_vm.run_once().unwrap();
+        assert_eq!(test_vm.registers[2], 2);
+    }
+
+    #[test]
+    fn test_opcode_shli() {
+        let mut test_vm = VM::new();
+        test_vm.registers[0] = 3;
+        test_vm.set_program(vec![[37, 0, 4, 1]]);
+
+        test_vm.run_once().unwrap();
+        assert_eq!(test_vm.registers[1], 48);
+    }
+
+    #[test]
+    fn test_opcode_shri() {
+        let mut test_vm = VM::new();
+        test_vm.registers[0] = -1;
+        test_vm.set_program(vec![[38, 0, 31, 1]]);
+
+        test_vm.run_once().unwrap();
+        assert_eq!(test_vm.registers[1], 1);
+    }
+
+    #[test]
+    fn test_opcode_sari() {
+        let mut test_vm = VM::new();
+        test_vm.registers[0] = -1;
+        test_vm.set_program(vec![[39, 0, 31, 1]]);
+
+        test_vm.run_once().unwrap();
+        assert_eq!(test_vm.registers[1], -1);
+    }
+
+    #[test]
+    fn test_opcode_jc() {
+        let mut test_vm = VM::new();
+        test_vm.registers[0] = 12;
+        test_vm.flags.set(Flags::CARRY, true);
+        test_vm.set_program(vec![[40, 0, 0, 0], [40, 0, 0, 0]]);
+
+        test_vm.run_once().unwrap();
+        assert_eq!(test_vm.pc, 12);
+
+        test_vm.pc = 4;
+        test_vm.flags.set(Flags::CARRY, false);
+        test_vm.run_once().unwrap();
+        assert_eq!(test_vm.pc, 8);
+    }
+
+    #[test]
+    fn test_opcode_jnc() {
+        let mut test_vm = VM::new();
+        test_vm.registers[0] = 12;
+        test_vm.flags.set(Flags::CARRY, false);
+        test_vm.set_program(vec![[41, 0, 0, 0], [41, 0, 0, 0]]);
+
+        test_vm.run_once().unwrap();
+        assert_eq!(test_vm.pc, 12);
+
+        test_vm.pc = 4;
+        test_vm.flags.set(Flags::CARRY, true);
+        test_vm.run_once().unwrap();
+        assert_eq!(test_vm.pc, 8);
+    }
+
+    #[test]
+    fn test_opcode_jo() {
+        let mut test_vm = VM::new();
+        test_vm.registers[0] = 12;
+        test_vm.flags.set(Flags::OVERFLOW, true);
+        test_vm.set_program(vec![[42, 0, 0, 0], [42, 0, 0, 0]]);
+
+        test_vm.run_once().unwrap();
+        assert_eq!(test_vm.pc, 12);
+
+        test_vm.pc = 4;
+        test_vm.flags.set(Flags::OVERFLOW, false);
+        test_vm.run_once().unwrap();
+        assert_eq!(test_vm.pc, 8);
+    }
+
+    #[test]
+    fn test_opcode_jno() {
+        let mut test_vm = VM::new();
+        test_vm.registers[0] = 12;
+        test_vm.flags.set(Flags::OVERFLOW, false);
+        test_vm.set_program(vec![[43, 0, 0, 0], [43, 0, 0, 0]]);
+
+        test_vm.run_once().unwrap();
+        assert_eq!(test_vm.pc, 12);
+
+        test_vm.pc = 4;
+        test_vm.flags.set(Flags::OVERFLOW, true);
+        test_vm.run_once().unwrap();
+        assert_eq!(test_vm.pc, 8);
+    }
+
+    #[test]
+    fn test_comparison_then_conditional_jump() {
+        let taken = |source: &str| {
+            let mut test_vm = VM::new();
+            test_vm.load_program(crate::assembler::assemble_program(source).unwrap());
+            assert_eq!(test_vm.run(), Ok(ExitStatus::Halted));
+            test_vm.registers[2] == 1
+        };
+        let program = |comparison: &str, jump: &str| {
+            format!(
+                "LOAD $0 #5\nLOAD $1 #3\nLOADF $f0 #5.0\nLOADF $f1 #3.0\n\
+                 {comparison}\n{jump} @yes\nHLT\nyes: LOAD $2 #1\nHLT"
+            )
+        };
+
+        assert!(taken(&program("GT $0 $1", "JGT")));
+        assert!(!taken(&program("GT $0 $1", "JLE")));
+        assert!(taken(&program("GT $0 $1", "JEQ")));
+        assert!(!taken(&program("GT $1 $0", "JEQ")));
+        assert!(taken(&program("LT $0 $1", "JNEQ")));
+        assert!(taken(&program("EQ $0 $1", "JNZ")));
+        assert!(taken(&program("GTF $f0 $f1", "JGT")));
+        assert!(taken(&program("LTF $f1 $f0", "JLT")));
+        assert!(!taken(&program("LTF $f1 $f0", "JGE")));
+    }
+
+    #[test]
+    fn test_opcode_cmp() {
+        let mut test_vm = VM::new();
+        test_vm.registers[0] = 3;
+        test_vm.registers[1] = 5;
+        test_vm.set_program(vec![
+            [57, 0, 1, 0], // Compare reg0 with reg1
+            [57, 1, 0, 0], // Compare reg1 with reg0
+            [57, 0, 0, 0], // Compare reg0 with itself
+        ]);
+
+        test_vm.run_once().unwrap();
+        assert_eq!(test_vm.flags.to_string(), "-NC--");
+        assert_eq!(test_vm.pc, 4);
+        test_vm.run_once().unwrap();
+        assert_eq!(test_vm.flags.to_string(), "-----");
+        test_vm.run_once().unwrap();
+        assert_eq!(test_vm.flags.to_string(), "Z---T");
+    }
+
+    #[test]
+    fn test_opcode_cmp_overflow() {
+        let mut test_vm = VM::new();
+        test_vm.registers[0] = i32::MIN;
+        test_vm.registers[1] = 1;
+        test_vm.registers[2] = 12;
+        test_vm.set_program(vec![
+            [57, 0, 1, 0], // Compare reg0 with reg1, overflowing
+            [60, 2, 0, 0], // Jump to reg2 if reg0 < reg1
+        ]);
+
+        assert_eq!(test_vm.run_once(), Ok(ExitStatus::Running));
+        assert!(test_vm.flags.contains(Flags::OVERFLOW));
+        test_vm.run_once().unwrap();
+        assert_eq!(test_vm.pc, 12);
+    }
+
+    /// Runs `CMP a b` followed by the branch `opcode`, returning whether it was taken
+    fn branch_taken(opcode: u8, a: i32, b: i32) -> bool {
+        let mut test_vm = VM::new();
+        test_vm.registers[0] = a;
+        test_vm.registers[1] = b;
+        test_vm.registers[2] = 100;
+        test_vm.set_program(vec![[57, 0, 1, 0], [opcode, 2, 0, 0]]);
+
+        test_vm.run_once().unwrap();
+        test_vm.run_once().unwrap();
+        test_vm.pc == 100
+    }
+
+    #[test]
+    fn test_opcode_jz() {
+        assert!(branch_taken(58, 4, 4));
+        assert!(!branch_taken(58, 4, 5));
+    }
+
+    #[test]
+    fn test_opcode_jnz() {
+        assert!(!branch_taken(59, 4, 4));
+        assert!(branch_taken(59, 4, 5));
+    }
+
+    #[test]
+    fn test_opcode_jlt() {
+        assert!(branch_taken(60, -4, 5));
+        assert!(!branch_taken(60, 5, 5));
+        assert!(!branch_taken(60, 5, -4));
+    }
+
+    #[test]
+    fn test_opcode_jge() {
+        assert!(!branch_taken(61, -4, 5));
+        assert!(branch_taken(61, 5, 5));
+        assert!(branch_taken(61, 5, -4));
+    }
+
+    #[test]
+    fn test_opcode_jgt() {
+        assert!(!branch_taken(62, -4, 5));
+        assert!(!branch_taken(62, 5, 5));
+        assert!(branch_taken(62, 5, -4));
+    }
+
+    #[test]
+    fn test_opcode_jle() {
+        assert!(branch_taken(63, -4, 5));
+        assert!(branch_taken(63, 5, 5));
+        assert!(!branch_taken(63, 5, -4));
+    }
+
+    #[test]
+    fn test_opcode_jmpr() {
+        let mut test_vm = VM::new();
+        test_vm.set_program(vec![[0, 0, 0, 0], [64, 255, 252, 0]]);
+        test_vm.pc = 4;
+
+        test_vm.run_once().unwrap();
+        assert_eq!(test_vm.pc, 0);
+    }
+
+    #[test]
+    fn test_opcode_jmpi() {
+        let mut test_vm = VM::new();
+        test_vm.set_program(vec![[65, 1, 2, 4]]);
+
+        test_vm.run_once().unwrap();
+        assert_eq!(test_vm.pc, 0x10204);
+    }
+
+    #[test]
+    fn test_immediate_jump_not_taken() {
+        let mut test_vm = VM::new();
+        test_vm.set_program(vec![
+            [66, 0, 12, 0], // Jump 12 bytes forward if the zero flag is set
+            [67, 0, 0, 12], // Jump to 12 if the zero flag is set
+        ]);
+
+        test_vm.run_once().unwrap();
+        assert_eq!(test_vm.pc, 4);
+        test_vm.run_once().unwrap();
+        assert_eq!(test_vm.pc, 8);
+    }
+
+    #[test]
+    fn test_relative_jump_loop() {
+        let mut test_vm = VM::new();
+        test_vm.registers[0] = 3;
+        test_vm.registers[1] = 1;
+        test_vm.set_program(vec![
+            [3, 0, 1, 0],      // Set reg0 to reg0 - reg1
+            [72, 255, 252, 0], // Jump back to the SUB if reg0 is not zero
+            [0, 0, 0, 0],      // Halt
+        ]);
+
+        assert_eq!(test_vm.run(), Ok(ExitStatus::Halted));
+        assert_eq!(test_vm.registers[0], 0);
+    }
+
+    #[test]
+    fn test_arithmetic_sets_zero_and_negative() {
+        let mut test_vm = VM::new();
+        test_vm.registers[0] = 3;
+        test_vm.registers[1] = 1;
+        test_vm.registers[2] = 0;
+        test_vm.set_program(vec![
+            [3, 0, 1, 0],  // Set reg0 to reg0 - reg1
+            [59, 2, 0, 0], // Jump back to the SUB if reg0 is not zero
+            [3, 0, 1, 0],  // Set reg0 to reg0 - reg1
+            [0, 0, 0, 0],  // Halt
+        ]);
+
+        assert_eq!(test_vm.run(), Ok(ExitStatus::Halted));
+        assert_eq!(test_vm.registers[0], -1);
+        assert_eq!(test_vm.flags.to_string(), "-NC--");
+    }
+
+    #[test]
+    fn test_opcode_loadf() {
+        let mut test_vm = VM::new();
+        test_vm.constants = vec![1.0, 2.5];
+        test_vm.set_program(vec![[44, 3, 0, 1]]);
+
+        test_vm.run_once().unwrap();
+        assert_eq!(test_vm.float_registers[3], 2.5);
+    }
+
+    #[test]
+    fn test_opcode_loadf_invalid_constant() {
+        let mut test_vm = VM::new();
+        test_vm.set_program(vec![[44, 0, 0, 1]]);
+
+        assert_eq!(
+            test_vm.run_once(),
+            Err(VmError::InvalidConstant {
+                offset: 0,
+                index: 1
+            })
+        );
+    }
+
+    #[test]
+    fn test_opcode_addf() {
+        let mut test_vm = VM::new();
+        test_vm.float_registers[0] = 1.5;
+        test_vm.float_registers[1] = 2.25;
+        test_vm.set_program(vec![[45, 0, 1, 2]]);
+
+        test_vm.run_once().unwrap();
+        assert_eq!(test_vm.float_registers[2], 3.75);
+    }
+
+    #[test]
+    fn test_opcode_subf() {
+        let mut test_vm = VM::new();
+        test_vm.float_registers[0] = 1.5;
+        test_vm.float_registers[1] = 2.25;
+        test_vm.set_program(vec![[46, 0, 1, 2]]);
+
+        test_vm.run_once().unwrap();
+        assert_eq!(test_vm.float_registers[2], -0.75);
+    }
+
+    #[test]
+    fn test_opcode_mulf() {
+        let mut test_vm = VM::new();
+        test_vm.float_registers[0] = 1.5;
+        test_vm.float_registers[1] = 2.0;
+        test_vm.set_program(vec![[47, 0, 1, 2]]);
+
+        test_vm.run_once().unwrap();
+        assert_eq!(test_vm.float_registers[2], 3.0);
+    }
+
+    #[test]
+    fn test_opcode_divf() {
+        let mut test_vm = VM::new();
+        test_vm.float_registers[0] = 1.0;
+        test_vm.float_registers[1] = 0.0;
+        test_vm.set_program(vec![[48, 0, 1, 2]]);
+
+        test_vm.run_once().unwrap();
+        assert_eq!(test_vm.float_registers[2], f64::INFINITY);
+    }
+
+    #[test]
+    fn test_opcode_eqf() {
+        let mut test_vm = VM::new();
+        test_vm.float_registers[0] = 1.5;
+        test_vm.float_registers[1] = 1.5;
+        test_vm.flags.set(Flags::CONDITION, false);
+        test_vm.set_program(vec![[49, 0, 1, 0]]);
+
+        test_vm.run_once().unwrap();
+        assert!(test_vm.flags.contains(Flags::CONDITION));
+        assert_eq!(test_vm.pc, 4);
+    }
+
+    #[test]
+    fn test_opcode_neqf() {
+        let mut test_vm = VM::new();
+        test_vm.float_registers[0] = 1.5;
+        test_vm.float_registers[1] = 1.5;
+        test_vm.flags.set(Flags::CONDITION, true);
+        test_vm.set_program(vec![[50, 0, 1, 0]]);
+
+        test_vm.run_once().unwrap();
+        assert!(!test_vm.flags.contains(Flags::CONDITION));
+        assert_eq!(test_vm.pc, 4);
+    }
+
+    #[test]
+    fn test_opcode_gtf() {
+        let mut test_vm = VM::new();
+        test_vm.float_registers[0] = 2.0;
+        test_vm.float_registers[1] = 1.5;
+        test_vm.flags.set(Flags::CONDITION, false);
+        test_vm.set_program(vec![[51, 0, 1, 0]]);
+
+        test_vm.run_once().unwrap();
+        assert!(test_vm.flags.contains(Flags::CONDITION));
+        assert_eq!(test_vm.pc, 4);
+    }
+
+    #[test]
+    fn test_opcode_ltf() {
+        let mut test_vm = VM::new();
+        test_vm.float_registers[0] = 2.0;
+        test_vm.float_registers[1] = 1.5;
+        test_vm.flags.set(Flags::CONDITION, true);
+        test_vm.set_program(vec![[52, 0, 1, 0]]);
+
+        test_vm.run_once().unwrap();
+        assert!(!test_vm.flags.contains(Flags::CONDITION));
+        assert_eq!(test_vm.pc, 4);
+    }
+
+    #[test]
+    fn test_opcode_gtqf() {
+        let mut test_vm = VM::new();
+        test_vm.float_registers[0] = 1.5;
+        test_vm.float_registers[1] = 1.5;
+        test_vm.flags.set(Flags::CONDITION, false);
+        test_vm.set_program(vec![[53, 0, 1, 0]]);
+
+        test_vm.run_once().unwrap();
+        assert!(test_vm.flags.contains(Flags::CONDITION));
+        assert_eq!(test_vm.pc, 4);
+    }
+
+    #[test]
+    fn test_opcode_ltqf() {
+        let mut test_vm = VM::new();
+        test_vm.float_registers[0] = f64::NAN;
+        test_vm.float_registers[1] = 1.5;
+        test_vm.flags.set(Flags::CONDITION, true);
+        test_vm.set_program(vec![[54, 0, 1, 0]]);
+
+        test_vm.run_once().unwrap();
+        assert!(!test_vm.flags.contains(Flags::CONDITION));
+        assert_eq!(test_vm.pc, 4);
+    }
+
+    #[test]
+    fn test_opcode_itof() {
+        let mut test_vm = VM::new();
+        test_vm.registers[0] = -7;
+        test_vm.set_program(vec![[55, 0, 1, 0]]);
+
+        test_vm.run_once().unwrap();
+        assert_eq!(test_vm.float_registers[1], -7.0);
+    }
+
+    #[test]
+    fn test_opcode_ftoi() {
+        let mut test_vm = VM::new();
+        test_vm.float_registers[0] = -7.9;
+        test_vm.float_registers[1] = 1e20;
+        test_vm.set_program(vec![[56, 0, 2, 0], [56, 1, 3, 0]]);
+
+        test_vm.run().unwrap_err();
+        assert_eq!(test_vm.registers[2], -7);
+        assert_eq!(test_vm.registers[3], i32::MAX);
+    }
+
+    #[test]
+    fn test_opcode_ret() {
+        let mut test_vm = VM::new();
+        test_vm.set_program(vec![
+            [1, 0, 0, 16], // Set reg0 to 16
+            [26, 0, 0, 0], // Call reg0
+            [1, 1, 0, 7],  // Set reg1 to 7
+            [0, 0, 0, 0],  // Halt
+            [1, 1, 0, 6],  // Set reg1 to 6
+            [27, 0, 0, 0], // Return
+        ]);
+
+        assert_eq!(test_vm.run(), Ok(ExitStatus::Halted));
+        assert_eq!(test_vm.registers[1], 7);
+        assert!(test_vm.stack.is_empty());
+    }
+
+    #[test]
+    fn test_stack_overflow() {
+        let mut test_vm = VM::new();
+        test_vm.stack_limit = 1;
+        test_vm.set_program(vec![
+            [24, 0, 0, 0], // Push reg0
+            [24, 0, 0, 0], // Push reg0
+        ]);
+
+        test_vm.run_once().unwrap();
+        assert_eq!(
+            test_vm.run_once(),
+            Err(VmError::StackOverflow { offset: 4 })
+        );
+    }
+
+    #[test]
+    fn test_stack_underflow() {
+        let mut test_vm = VM::new();
+        test_vm.set_program(vec![
+            [27, 0, 0, 0], // Return
+        ]);
+
+        assert_eq!(
+            test_vm.run_once(),
+            Err(VmError::StackUnderflow { offset: 0 })
+        );
+    }
+
+    #[test]
+    fn test_load_program() {
+        let mut test_vm = VM::new();
+        test_vm.load_program(Program {
+            code: vec![
+                [20, 0, 1, 0], // Set reg0 to the word at reg1
+                [0, 0, 0, 0],  // Halt
+            ],
+            data: vec![0x2A, 0, 0, 0],
+            ..Default::default()
+        });
+
+        assert_eq!(test_vm.run(), Ok(ExitStatus::Halted));
+        assert_eq!(test_vm.registers[0], 42);
+    }
+}
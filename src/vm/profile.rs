@@ -0,0 +1,412 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    fmt::Write,
+    time::Duration,
+};
+
+use super::{decode::decode, VM};
+use crate::{
+    assembler::{disassemble, LineInfo, Section, Symbol},
+    opcode::{JumpTarget, Opcode},
+};
+
+/// How many basic blocks the report lists
+const HOT_BLOCKS: usize = 10;
+
+/// Counts how often each instruction and opcode is executed and how long each
+/// instruction takes, and follows `CALL` and `RET` to build a call tree. Times are
+/// estimates, as they include some of the profiler's own overhead
+#[derive(Debug, Clone)]
+pub struct Profiler {
+    /// Executions of each instruction word, indexed by offset / 4
+    counts: Vec<u64>,
+    /// Nanoseconds spent in each instruction word
+    nanos: Vec<u64>,
+    opcodes: [u64; 256],
+    /// Offsets that execution arrived at other than by falling through
+    targets: BTreeSet<usize>,
+    /// The call tree, where each frame is the address its function was entered at
+    frames: Vec<Frame>,
+    children: HashMap<(usize, usize), usize>,
+    /// The index of the frame being executed, once anything has been recorded
+    current: Option<usize>,
+}
+
+#[derive(Debug, Clone)]
+struct Frame {
+    parent: Option<usize>,
+    address: usize,
+    /// Instructions executed in this frame, not counting its callees
+    samples: u64,
+}
+
+/// A straight run of instructions that is only entered at its first instruction
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Block {
+    pub start: usize,
+    /// The offset just after the last instruction
+    pub end: usize,
+    /// How many times the block was entered
+    pub executions: u64,
+    /// How many of its instructions were executed in total
+    pub instructions: u64,
+    pub time: Duration,
+}
+
+impl Default for Profiler {
+    fn default() -> Self {
+        Profiler::new()
+    }
+}
+
+impl Profiler {
+    pub fn new() -> Profiler {
+        Profiler {
+            counts: vec![],
+            nanos: vec![],
+            opcodes: [0; 256],
+            targets: BTreeSet::new(),
+            frames: vec![],
+            children: HashMap::new(),
+            current: None,
+        }
+    }
+
+    /// How many times the instruction at `offset` was executed
+    pub fn count(&self, offset: usize) -> u64 {
+        self.counts.get(offset / 4).copied().unwrap_or(0)
+    }
+
+    /// How many instructions with `opcode` were executed
+    pub fn opcode_count(&self, opcode: Opcode) -> u64 {
+        self.opcodes[u8::from(opcode) as usize]
+    }
+
+    pub fn total_instructions(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+
+    pub fn total_time(&self) -> Duration {
+        Duration::from_nanos(self.nanos.iter().sum())
+    }
+
+    /// Logs the instruction at `offset`, which has just been executed and left the
+    /// VM at `pc`
+    pub(super) fn record(&mut self, offset: usize, opcode: u8, pc: usize, elapsed: Duration) {
+        let index = offset / 4;
+        if self.counts.len() <= index {
+            self.counts.resize(index + 1, 0);
+            self.nanos.resize(index + 1, 0);
+        }
+        self.counts[index] += 1;
+        self.nanos[index] += elapsed.as_nanos() as u64;
+        self.opcodes[opcode as usize] += 1;
+
+        let current = match self.current {
+            Some(current) => current,
+            None => {
+                self.targets.insert(offset);
+                self.frames.push(Frame {
+                    parent: None,
+                    address: offset,
+                    samples: 0,
+                });
+                0
+            }
+        };
+        self.frames[current].samples += 1;
+
+        if pc != offset + 4 {
+            self.targets.insert(pc);
+        }
+        self.current = Some(match Opcode::try_from(opcode) {
+            Ok(Opcode::CALL) => self.child(current, pc),
+            Ok(Opcode::RET) => self.frames[current].parent.unwrap_or(current),
+            _ => current,
+        });
+    }
+
+    /// The frame for calling `address` from `parent`
+    fn child(&mut self, parent: usize, address: usize) -> usize {
+        let frames = &mut self.frames;
+        *self.children.entry((parent, address)).or_insert_with(|| {
+            frames.push(Frame {
+                parent: Some(parent),
+                address,
+                samples: 0,
+            });
+            frames.len() - 1
+        })
+    }
+
+    /// Splits `vm`'s program into basic blocks. A block starts wherever execution
+    /// arrived other than by falling through, at the target of any immediate jump,
+    /// and after any instruction that can change the pc
+    pub fn blocks(&self, vm: &VM) -> Vec<Block> {
+        let mut leaders = self.targets.clone();
+        leaders.insert(0);
+        for (n, word) in vm.program.chunks_exact(4).enumerate() {
+            let offset = n * 4;
+            let Ok(decoded) = decode(offset, [word[0], word[1], word[2], word[3]]) else {
+                continue;
+            };
+            if ends_block(decoded.opcode) {
+                leaders.insert(offset + 4);
+            }
+            if let Some((_, JumpTarget::Relative | JumpTarget::Absolute)) = decoded.opcode.jump() {
+                leaders.insert(decoded.immediate as usize);
+            }
+        }
+
+        let end = vm.program.len() - vm.program.len() % 4;
+        let mut starts: Vec<usize> = leaders
+            .into_iter()
+            .filter(|&offset| offset < end && offset.is_multiple_of(4))
+            .collect();
+        starts.push(end);
+
+        starts
+            .windows(2)
+            .map(|pair| {
+                let (start, end) = (pair[0], pair[1]);
+                let words = start / 4..end / 4;
+                let sum =
+                    |values: &[u64]| -> u64 { words.clone().filter_map(|n| values.get(n)).sum() };
+                Block {
+                    start,
+                    end,
+                    executions: self.count(start),
+                    instructions: sum(&self.counts),
+                    time: Duration::from_nanos(sum(&self.nanos)),
+                }
+            })
+            .collect()
+    }
+
+    /// A report of the opcodes executed, the hottest basic blocks and the program's
+    /// disassembly annotated with counts. Each instruction is followed by its source
+    /// line when there is debug info, using `source` if it is available
+    pub fn report(
+        &self,
+        vm: &VM,
+        symbols: &[Symbol],
+        debug: &[LineInfo],
+        source: Option<&str>,
+    ) -> String {
+        let total = self.total_instructions();
+        let total_time = self.total_time();
+        let percent = |n: u64| 100.0 * n as f64 / total.max(1) as f64;
+        let mut report = String::new();
+
+        let _ = writeln!(report, "Executed {total} instructions in {total_time:?}");
+
+        let _ = writeln!(report, "\nInstructions by opcode:");
+        let mut opcodes: Vec<(Opcode, u64)> = (0..=u8::MAX)
+            .filter_map(|byte| Some((Opcode::try_from(byte).ok()?, self.opcodes[byte as usize])))
+            .filter(|&(_, count)| count > 0)
+            .collect();
+        opcodes.sort_by_key(|&(_, count)| std::cmp::Reverse(count));
+        for (opcode, count) in opcodes {
+            let _ = writeln!(
+                report,
+                "  {:<8} {count:>10} {:>6.1}%",
+                format!("{opcode:?}"),
+                percent(count)
+            );
+        }
+
+        let _ = writeln!(report, "\nHottest basic blocks:");
+        let blocks = self.blocks(vm);
+        let mut hot: Vec<&Block> = blocks.iter().filter(|block| block.executions > 0).collect();
+        hot.sort_by_key(|block| std::cmp::Reverse((block.time, block.instructions)));
+        for block in hot.into_iter().take(HOT_BLOCKS) {
+            let share = block.time.as_secs_f64() / total_time.as_secs_f64().max(f64::EPSILON);
+            let label = label_at(symbols, block.start).unwrap_or("");
+            let _ = writeln!(
+                report,
+                "  {:04}-{:04} {label:<12} entered {:>8} times {:>10} instructions {:>12} {:>6.1}%",
+                block.start,
+                block.end - 4,
+                block.executions,
+                block.instructions,
+                format!("{:?}", block.time),
+                100.0 * share
+            );
+        }
+
+        let _ = writeln!(report, "\nAnnotated disassembly:");
+        let lines: Vec<&str> = source.map_or(vec![], |source| source.lines().collect());
+        let starts: BTreeSet<usize> = blocks.iter().map(|block| block.start).collect();
+        for (n, text) in disassemble(&vm.program, &vm.constants)
+            .into_iter()
+            .enumerate()
+        {
+            let offset = n * 4;
+            if offset > 0 && starts.contains(&offset) {
+                let _ = writeln!(report);
+            }
+            if let Some(label) = label_at(symbols, offset) {
+                let _ = writeln!(report, "{:26}{label}:", "");
+            }
+
+            let count = self.count(offset);
+            let line = format!("{count:>10} {:>6.1}%  {offset:04}: {text}", percent(count));
+            let _ = match debug.iter().find(|info| info.offset as usize == offset) {
+                Some(info) => match lines.get((info.line as usize).wrapping_sub(1)) {
+                    Some(source) => {
+                        writeln!(report, "{line:<40}; {}: {}", info.line, source.trim())
+                    }
+                    None => writeln!(report, "{line:<40}; line {}", info.line),
+                },
+                None => writeln!(report, "{line}"),
+            };
+        }
+
+        report
+    }
+
+    /// The call tree in the folded stack format that flamegraph tools read, with one
+    /// line per call stack giving the instructions executed in it. Functions are named
+    /// by their code label, or by their offset if they have none
+    pub fn folded(&self, symbols: &[Symbol]) -> String {
+        let mut lines: Vec<String> = self
+            .frames
+            .iter()
+            .enumerate()
+            .filter(|(_, frame)| frame.samples > 0)
+            .map(|(mut index, frame)| {
+                let mut names = vec![];
+                loop {
+                    let address = self.frames[index].address;
+                    names.push(
+                        label_at(symbols, address).map_or(format!("{address:04}"), str::to_owned),
+                    );
+                    match self.frames[index].parent {
+                        Some(parent) => index = parent,
+                        None => break,
+                    }
+                }
+                names.reverse();
+                format!("{} {}", names.join(";"), frame.samples)
+            })
+            .collect();
+        lines.sort();
+
+        lines.into_iter().map(|line| line + "\n").collect()
+    }
+}
+
+/// Whether `opcode` can send execution somewhere other than the next instruction
+fn ends_block(opcode: Opcode) -> bool {
+    opcode.jump().is_some()
+        || matches!(
+            opcode,
+            Opcode::JMPF
+                | Opcode::JMPB
+                | Opcode::CALL
+                | Opcode::RET
+                | Opcode::HLT
+                | Opcode::SYSCALL
+        )
+}
+
+fn label_at(symbols: &[Symbol], offset: usize) -> Option<&str> {
+    symbols
+        .iter()
+        .find(|symbol| symbol.section == Section::Code && symbol.offset as usize == offset)
+        .map(|symbol| symbol.name.as_str())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{assembler::assemble_program, vm::ExitStatus};
+
+    const SOURCE: &str = "
+        LOAD $0 #3
+        LOAD $1 #1
+        LOAD $3 @square
+    loop:
+        CALL $3
+        SUB $0 $1 $0
+        JNZ @loop
+        HLT
+    square:
+        MUL $0 $0 $2
+        RET
+    ";
+
+    fn profile(source: &str) -> (VM, Vec<Symbol>, Vec<LineInfo>) {
+        let program = assemble_program(source).unwrap();
+        let (symbols, debug) = (program.symbols.clone(), program.debug.clone());
+        let mut vm = VM::new();
+        vm.profiler = Some(Profiler::new());
+        vm.load_program(program);
+        assert_eq!(vm.run(), Ok(ExitStatus::Halted));
+        (vm, symbols, debug)
+    }
+
+    #[test]
+    fn test_counts() {
+        let (vm, _, _) = profile(SOURCE);
+        let profiler = vm.profiler.as_ref().unwrap();
+
+        assert_eq!(profiler.count(0), 1);
+        assert_eq!(profiler.count(12), 3);
+        assert_eq!(profiler.count(28), 3);
+        assert_eq!(profiler.count(24), 1);
+        assert_eq!(profiler.count(100), 0);
+        assert_eq!(profiler.opcode_count(Opcode::CALL), 3);
+        assert_eq!(profiler.opcode_count(Opcode::LOAD), 3);
+        assert_eq!(profiler.total_instructions(), 4 + 3 * 5);
+    }
+
+    #[test]
+    fn test_blocks() {
+        let (vm, _, _) = profile(SOURCE);
+        let blocks: Vec<(usize, usize, u64, u64)> = vm
+            .profiler
+            .as_ref()
+            .unwrap()
+            .blocks(&vm)
+            .into_iter()
+            .map(|block| (block.start, block.end, block.executions, block.instructions))
+            .collect();
+
+        assert_eq!(
+            blocks,
+            vec![
+                (0, 12, 1, 3),
+                (12, 16, 3, 3),
+                (16, 24, 3, 6),
+                (24, 28, 1, 1),
+                (28, 36, 3, 6),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_folded() {
+        let (vm, symbols, _) = profile(SOURCE);
+
+        assert_eq!(
+            vm.profiler.as_ref().unwrap().folded(&symbols),
+            "0000 13\n0000;square 6\n"
+        );
+    }
+
+    #[test]
+    fn test_report() {
+        let (vm, symbols, debug) = profile(SOURCE);
+        let profiler = vm.profiler.as_ref().unwrap();
+
+        let report = profiler.report(&vm, &symbols, &debug, Some(SOURCE));
+        assert!(report.starts_with("Executed 19 instructions in "));
+        assert!(report.contains("  CALL              3   15.8%\n"));
+        assert!(report.contains("\n                          loop:\n"));
+        assert!(report.contains("         3   15.8%  0012: CALL $3       ; 6: CALL $3\n"));
+
+        let report = profiler.report(&vm, &[], &debug, None);
+        assert!(report.contains("         3   15.8%  0028: MUL $0 $0 $2  ; line 11\n"));
+    }
+}
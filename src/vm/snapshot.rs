@@ -0,0 +1,397 @@
+//! Snapshots of the VM's state, and the snapshot file format.
+//!
+//! All integers are little-endian. A file starts with a 12 byte header:
+//!
+//! | offset | size | field                                      |
+//! |--------|------|--------------------------------------------|
+//! | 0      | 4    | [`SNAPSHOT_MAGIC`]                         |
+//! | 4      | 2    | format [`SNAPSHOT_VERSION`]                |
+//! | 6      | 2    | reserved                                   |
+//! | 8      | 4    | CRC-32 of every byte after the header      |
+//!
+//! The header is followed by the pc and remainder as `u32`s, the flags byte, the
+//! integer registers as `i32`s and the float registers as `f64`s. Next are the VM's
+//! settings: the arithmetic mode as a byte (0 wrapping, 1 trapping, 2 saturating), a
+//! byte that is 1 if the fuel is limited followed by the fuel left as a `u64`, and the
+//! heap limit as a `u64`. Then come the program bytes, the constant pool as `f64`s,
+//! the heap bytes and the stack as `i32`s, each preceded by its number of elements
+//! as a `u32`.
+//!
+//! Fuel costs, breakpoints, host functions and any history, tracer or profiler are
+//! not saved, so they must be set up again after a snapshot is restored.
+
+use std::{error::Error, fmt::Display};
+
+use super::{ArithmeticMode, Flags, REGISTER_COUNT, VM};
+use crate::assembler::bytecode::checksum;
+
+pub const SNAPSHOT_MAGIC: [u8; 4] = *b"\x7FPSN";
+pub const SNAPSHOT_VERSION: u16 = 2;
+const SNAPSHOT_HEADER_SIZE: usize = 12;
+
+/// A copy of everything a program can change: its registers, pc, flags and memory.
+/// The program itself, the constant pool and the VM's settings are not included
+#[derive(Debug, Clone, PartialEq)]
+pub struct Snapshot {
+    pub registers: [i32; REGISTER_COUNT],
+    pub float_registers: [f64; REGISTER_COUNT],
+    pub pc: usize,
+    pub remainder: u32,
+    pub flags: Flags,
+    pub heap: Vec<u8>,
+    pub stack: Vec<i32>,
+}
+
+/// An error raised while reading a snapshot file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SnapshotError {
+    Truncated,
+    BadMagic([u8; 4]),
+    UnsupportedVersion(u16),
+    ChecksumMismatch { expected: u32, actual: u32 },
+    UnknownArithmeticMode(u8),
+    InvalidFuelMarker(u8),
+    MisalignedCode(usize),
+    TrailingBytes(usize),
+}
+
+impl Display for SnapshotError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        use SnapshotError as SE;
+        match self {
+            SE::Truncated => write!(f, "The snapshot ended unexpectedly"),
+            SE::BadMagic(magic) => write!(f, "{magic:02X?} is not a potassium snapshot header"),
+            SE::UnsupportedVersion(v) => write!(f, "Snapshot version {v} is not supported"),
+            SE::ChecksumMismatch { expected, actual } => write!(
+                f,
+                "Checksum mismatch: the header says {expected:08X} but the snapshot hashes to {actual:08X}"
+            ),
+            SE::UnknownArithmeticMode(mode) => write!(f, "{mode} is not an arithmetic mode"),
+            SE::InvalidFuelMarker(marker) => {
+                write!(f, "The fuel marker is {marker}, but it should be 0 or 1")
+            }
+            SE::MisalignedCode(len) => {
+                write!(f, "The program is {len} bytes, which is not a multiple of 4")
+            }
+            SE::TrailingBytes(len) => write!(f, "The snapshot has {len} unexpected trailing bytes"),
+        }
+    }
+}
+
+impl Error for SnapshotError {}
+
+impl VM {
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            registers: self.registers,
+            float_registers: self.float_registers,
+            pc: self.pc,
+            remainder: self.remainder,
+            flags: self.flags,
+            heap: self.heap.clone(),
+            stack: self.stack.clone(),
+        }
+    }
+
+    /// Puts the VM back into the state `snapshot` was taken in
+    pub fn restore(&mut self, snapshot: &Snapshot) {
+        self.registers = snapshot.registers;
+        self.float_registers = snapshot.float_registers;
+        self.pc = snapshot.pc;
+        self.remainder = snapshot.remainder;
+        self.flags = snapshot.flags;
+        self.heap.clone_from(&snapshot.heap);
+        self.stack.clone_from(&snapshot.stack);
+    }
+
+    /// Writes the VM's state, settings, program and constant pool in the snapshot
+    /// file format
+    pub fn save_snapshot(&self) -> Vec<u8> {
+        let mut body = vec![];
+        body.extend_from_slice(&(self.pc as u32).to_le_bytes());
+        body.extend_from_slice(&self.remainder.to_le_bytes());
+        body.push(self.flags.bits());
+        for register in self.registers {
+            body.extend_from_slice(&register.to_le_bytes());
+        }
+        for register in self.float_registers {
+            body.extend_from_slice(&register.to_le_bytes());
+        }
+
+        body.push(match self.arithmetic_mode {
+            ArithmeticMode::Wrapping => 0,
+            ArithmeticMode::Trapping => 1,
+            ArithmeticMode::Saturating => 2,
+        });
+        body.push(self.fuel.is_some() as u8);
+        body.extend_from_slice(&self.fuel.unwrap_or(0).to_le_bytes());
+        body.extend_from_slice(&(self.heap_limit as u64).to_le_bytes());
+
+        body.extend_from_slice(&(self.program.len() as u32).to_le_bytes());
+        body.extend_from_slice(&self.program);
+        body.extend_from_slice(&(self.constants.len() as u32).to_le_bytes());
+        for constant in &self.constants {
+            body.extend_from_slice(&constant.to_le_bytes());
+        }
+        body.extend_from_slice(&(self.heap.len() as u32).to_le_bytes());
+        body.extend_from_slice(&self.heap);
+        body.extend_from_slice(&(self.stack.len() as u32).to_le_bytes());
+        for value in &self.stack {
+            body.extend_from_slice(&value.to_le_bytes());
+        }
+
+        let mut output = Vec::with_capacity(SNAPSHOT_HEADER_SIZE + body.len());
+        output.extend_from_slice(&SNAPSHOT_MAGIC);
+        output.extend_from_slice(&SNAPSHOT_VERSION.to_le_bytes());
+        output.extend_from_slice(&[0, 0]);
+        output.extend_from_slice(&checksum(&body).to_le_bytes());
+        output.extend_from_slice(&body);
+        output
+    }
+
+    /// Replaces the VM's state, settings, program and constant pool with those in a
+    /// snapshot file. The VM is left unchanged if the file is invalid
+    pub fn restore_snapshot(&mut self, bytes: &[u8]) -> Result<(), SnapshotError> {
+        let header = bytes
+            .get(..SNAPSHOT_HEADER_SIZE)
+            .ok_or(SnapshotError::Truncated)?;
+
+        let magic = [header[0], header[1], header[2], header[3]];
+        if magic != SNAPSHOT_MAGIC {
+            return Err(SnapshotError::BadMagic(magic));
+        }
+
+        let version = u16::from_le_bytes([header[4], header[5]]);
+        if version != SNAPSHOT_VERSION {
+            return Err(SnapshotError::UnsupportedVersion(version));
+        }
+
+        let expected = u32::from_le_bytes([header[8], header[9], header[10], header[11]]);
+        let body = &bytes[SNAPSHOT_HEADER_SIZE..];
+        let actual = checksum(body);
+        if expected != actual {
+            return Err(SnapshotError::ChecksumMismatch { expected, actual });
+        }
+
+        let mut reader = Reader { bytes: body };
+        let pc = reader.u32()? as usize;
+        let remainder = reader.u32()?;
+        let flags = Flags::from_bits(reader.take(1)?[0]);
+        let mut registers = [0; REGISTER_COUNT];
+        for register in &mut registers {
+            *register = i32::from_le_bytes(reader.array()?);
+        }
+        let mut float_registers = [0.0; REGISTER_COUNT];
+        for register in &mut float_registers {
+            *register = f64::from_le_bytes(reader.array()?);
+        }
+
+        let arithmetic_mode = match reader.take(1)?[0] {
+            0 => ArithmeticMode::Wrapping,
+            1 => ArithmeticMode::Trapping,
+            2 => ArithmeticMode::Saturating,
+            mode => return Err(SnapshotError::UnknownArithmeticMode(mode)),
+        };
+        let limited = reader.take(1)?[0];
+        let fuel = u64::from_le_bytes(reader.array()?);
+        let fuel = match limited {
+            0 => None,
+            1 => Some(fuel),
+            marker => return Err(SnapshotError::InvalidFuelMarker(marker)),
+        };
+        let heap_limit = usize::try_from(u64::from_le_bytes(reader.array()?)).unwrap_or(usize::MAX);
+
+        let len = reader.u32()? as usize;
+        let code = reader.take(len)?;
+        if !len.is_multiple_of(4) {
+            return Err(SnapshotError::MisalignedCode(len));
+        }
+        let code = code
+            .chunks_exact(4)
+            .map(|word| [word[0], word[1], word[2], word[3]])
+            .collect();
+        let constants =
+            reader.elements(8, |bytes| f64::from_le_bytes(bytes.try_into().unwrap()))?;
+        let len = reader.u32()? as usize;
+        let heap = reader.take(len)?.to_vec();
+        let stack = reader.elements(4, |bytes| i32::from_le_bytes(bytes.try_into().unwrap()))?;
+
+        if !reader.bytes.is_empty() {
+            return Err(SnapshotError::TrailingBytes(reader.bytes.len()));
+        }
+
+        self.set_program(code);
+        self.constants = constants;
+        self.arithmetic_mode = arithmetic_mode;
+        self.fuel = fuel;
+        self.heap_limit = heap_limit;
+        self.restore(&Snapshot {
+            registers,
+            float_registers,
+            pc,
+            remainder,
+            flags,
+            heap,
+            stack,
+        });
+        Ok(())
+    }
+}
+
+/// Reads fields from the front of a snapshot's body
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], SnapshotError> {
+        if self.bytes.len() < len {
+            return Err(SnapshotError::Truncated);
+        }
+        let (taken, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], SnapshotError> {
+        Ok(self.take(N)?.try_into().unwrap())
+    }
+
+    fn u32(&mut self) -> Result<u32, SnapshotError> {
+        Ok(u32::from_le_bytes(self.array()?))
+    }
+
+    /// Reads a count followed by that many `size` byte elements
+    fn elements<T>(
+        &mut self,
+        size: usize,
+        read: impl Fn(&[u8]) -> T,
+    ) -> Result<Vec<T>, SnapshotError> {
+        let len = self.u32()? as usize;
+        let bytes = self.take(len.checked_mul(size).ok_or(SnapshotError::Truncated)?)?;
+        Ok(bytes.chunks_exact(size).map(read).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{assembler::assemble_program, vm::ExitStatus};
+
+    #[test]
+    fn test_snapshot_restore() {
+        let mut vm = VM::new();
+        vm.registers[3] = 7;
+        vm.float_registers[1] = 2.5;
+        vm.pc = 12;
+        vm.heap = vec![1, 2, 3];
+        vm.stack = vec![4];
+        let snapshot = vm.snapshot();
+
+        vm.registers[3] = 0;
+        vm.float_registers[1] = 0.0;
+        vm.pc = 0;
+        vm.flags = Flags::from_bits(Flags::ZERO);
+        vm.heap.clear();
+        vm.stack.push(5);
+
+        vm.restore(&snapshot);
+        assert_eq!(vm.snapshot(), snapshot);
+    }
+
+    #[test]
+    fn test_save_and_resume() {
+        let source = "
+            .data
+            total: .word #7
+            .code
+            LOAD $0 #10
+            LOAD $1 #1
+            LOADF $f0 #1.5
+        loop:
+            PUSH $0
+            ADDF $f1 $f0 $f1
+            SUB $0 $1 $0
+            JNZ @loop
+            HLT
+        ";
+        let mut expected = VM::new();
+        expected.load_program(assemble_program(source).unwrap());
+        assert_eq!(expected.run(), Ok(ExitStatus::Halted));
+
+        let mut vm = VM::new();
+        vm.load_program(assemble_program(source).unwrap());
+        vm.arithmetic_mode = ArithmeticMode::Saturating;
+        vm.fuel = Some(1000);
+        vm.heap_limit = 64;
+        assert_eq!(vm.run_for(13), Ok(ExitStatus::Running));
+        let file = vm.save_snapshot();
+
+        let mut resumed = VM::new();
+        assert_eq!(resumed.restore_snapshot(&file), Ok(()));
+        assert_eq!(resumed.snapshot(), vm.snapshot());
+        assert_eq!(resumed.program, vm.program);
+        assert_eq!(resumed.constants, vm.constants);
+        assert_eq!(resumed.arithmetic_mode, ArithmeticMode::Saturating);
+        assert_eq!(resumed.fuel, vm.fuel);
+        assert_eq!(resumed.heap_limit, 64);
+
+        assert_eq!(resumed.run(), Ok(ExitStatus::Halted));
+        assert_eq!(resumed.snapshot(), expected.snapshot());
+    }
+
+    #[test]
+    fn test_invalid_snapshots() {
+        let mut vm = VM::new();
+        vm.set_program(vec![[1, 0, 0, 5], [0, 0, 0, 0]]);
+        vm.stack = vec![1, 2];
+        let file = vm.save_snapshot();
+
+        let mut restored = VM::new();
+        assert_eq!(
+            restored.restore_snapshot(&file[..8]),
+            Err(SnapshotError::Truncated)
+        );
+        assert_eq!(
+            restored.restore_snapshot(b"\x7FPBC\x02\x00\x00\x00\x00\x00\x00\x00"),
+            Err(SnapshotError::BadMagic(*b"\x7FPBC"))
+        );
+
+        let mut newer = file.clone();
+        newer[4] = 3;
+        assert_eq!(
+            restored.restore_snapshot(&newer),
+            Err(SnapshotError::UnsupportedVersion(3))
+        );
+
+        let mut corrupt = file.clone();
+        *corrupt.last_mut().unwrap() ^= 1;
+        assert!(matches!(
+            restored.restore_snapshot(&corrupt),
+            Err(SnapshotError::ChecksumMismatch { .. })
+        ));
+
+        // A body cut short with a checksum that matches what is left
+        let mut short = file[..file.len() - 4].to_vec();
+        let sum = checksum(&short[SNAPSHOT_HEADER_SIZE..]);
+        short[8..12].copy_from_slice(&sum.to_le_bytes());
+        assert_eq!(
+            restored.restore_snapshot(&short),
+            Err(SnapshotError::Truncated)
+        );
+
+        // The arithmetic mode byte follows the pc, remainder, flags and registers
+        let mut mode = file.clone();
+        mode[SNAPSHOT_HEADER_SIZE + 9 + REGISTER_COUNT * 12] = 3;
+        let sum = checksum(&mode[SNAPSHOT_HEADER_SIZE..]);
+        mode[8..12].copy_from_slice(&sum.to_le_bytes());
+        assert_eq!(
+            restored.restore_snapshot(&mode),
+            Err(SnapshotError::UnknownArithmeticMode(3))
+        );
+
+        assert!(restored.program.is_empty());
+        assert_eq!(restored.restore_snapshot(&file), Ok(()));
+        assert_eq!(restored.stack, vec![1, 2]);
+    }
+}
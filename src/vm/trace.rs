@@ -0,0 +1,314 @@
+use std::{collections::VecDeque, fmt::Display, io::Write, str::FromStr};
+
+use super::{Flags, REGISTER_COUNT, VM};
+use crate::assembler::Instruction;
+
+/// How trace entries are written
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum TraceFormat {
+    /// One line per instruction, such as `0008: ADD $0 $1 $2  $2: 0 -> 5`
+    #[default]
+    Human,
+    /// One JSON object per instruction
+    JsonLines,
+}
+
+impl FromStr for TraceFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "human" => Ok(TraceFormat::Human),
+            "json" => Ok(TraceFormat::JsonLines),
+            _ => Err(format!("'{s}' is not a trace format")),
+        }
+    }
+}
+
+/// A value that an instruction changed
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Change {
+    Register { register: usize, old: i32, new: i32 },
+    FloatRegister { register: usize, old: f64, new: f64 },
+    Flags { old: Flags, new: Flags },
+}
+
+impl Display for Change {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Change::Register { register, old, new } => write!(f, "${register}: {old} -> {new}"),
+            Change::FloatRegister { register, old, new } => {
+                write!(f, "$f{register}: {old:?} -> {new:?}")
+            }
+            Change::Flags { old, new } => write!(f, "flags: {old} -> {new}"),
+        }
+    }
+}
+
+/// One executed instruction and what it changed
+#[derive(Debug, Clone, PartialEq)]
+pub struct TraceEntry {
+    /// The offset the instruction was executed from
+    pub pc: usize,
+    pub word: [u8; 4],
+    /// The instruction, if `word` is one the assembler could have produced
+    pub instruction: Option<Instruction>,
+    pub changes: Vec<Change>,
+}
+
+impl TraceEntry {
+    fn instruction_text(&self) -> String {
+        match self.instruction {
+            Some(instruction) => instruction.to_string(),
+            None => format!("{:?}", self.word),
+        }
+    }
+
+    fn write(&self, format: TraceFormat, output: &mut dyn Write) -> std::io::Result<()> {
+        match format {
+            TraceFormat::Human => {
+                write!(output, "{:04}: {}", self.pc, self.instruction_text())?;
+                for (n, change) in self.changes.iter().enumerate() {
+                    let separator = if n == 0 { "  " } else { ", " };
+                    write!(output, "{separator}{change}")?;
+                }
+                writeln!(output)
+            }
+            TraceFormat::JsonLines => {
+                let changes: Vec<String> = self
+                    .changes
+                    .iter()
+                    .map(|change| match change {
+                        Change::Register { register, old, new } => {
+                            format!("\"${register}\":[{old},{new}]")
+                        }
+                        Change::FloatRegister { register, old, new } => {
+                            format!(
+                                "\"$f{register}\":[{},{}]",
+                                json_float(*old),
+                                json_float(*new)
+                            )
+                        }
+                        Change::Flags { old, new } => format!("\"flags\":[\"{old}\",\"{new}\"]"),
+                    })
+                    .collect();
+                writeln!(
+                    output,
+                    "{{\"pc\":{},\"instruction\":\"{}\",\"changes\":{{{}}}}}",
+                    self.pc,
+                    self.instruction_text()
+                        .replace('\\', "\\\\")
+                        .replace('"', "\\\""),
+                    changes.join(",")
+                )
+            }
+        }
+    }
+}
+
+/// JSON has no infinities or NaN, so those are written as `null`
+fn json_float(value: f64) -> String {
+    if value.is_finite() {
+        format!("{value:?}")
+    } else {
+        "null".to_owned()
+    }
+}
+
+/// The values a trace entry compares before and after an instruction
+pub(super) struct TraceState {
+    registers: [i32; REGISTER_COUNT],
+    float_registers: [f64; REGISTER_COUNT],
+    flags: Flags,
+}
+
+impl TraceState {
+    pub(super) fn of(vm: &VM) -> TraceState {
+        TraceState {
+            registers: vm.registers,
+            float_registers: vm.float_registers,
+            flags: vm.flags,
+        }
+    }
+
+    /// The entry for the instruction at `pc`, which took the VM from this state to
+    /// its current one
+    pub(super) fn entry(&self, vm: &VM, pc: usize) -> TraceEntry {
+        let mut word = [0; 4];
+        if let Some(bytes) = vm.program.get(pc..pc + 4) {
+            word.copy_from_slice(bytes);
+        }
+
+        let mut changes = vec![];
+        for (register, (&old, &new)) in self.registers.iter().zip(&vm.registers).enumerate() {
+            if old != new {
+                changes.push(Change::Register { register, old, new });
+            }
+        }
+        for (register, (&old, &new)) in self
+            .float_registers
+            .iter()
+            .zip(&vm.float_registers)
+            .enumerate()
+        {
+            if old.to_bits() != new.to_bits() {
+                changes.push(Change::FloatRegister { register, old, new });
+            }
+        }
+        if self.flags != vm.flags {
+            changes.push(Change::Flags {
+                old: self.flags,
+                new: vm.flags,
+            });
+        }
+
+        TraceEntry {
+            pc,
+            word,
+            instruction: Instruction::try_from(word).ok(),
+            changes,
+        }
+    }
+}
+
+/// Records every instruction the VM executes. A log writes each entry as it happens,
+/// while a ring buffer keeps only the most recent entries and writes them out when
+/// the program fails
+pub struct Tracer {
+    format: TraceFormat,
+    output: Box<dyn Write>,
+    /// The number of entries a ring buffer keeps, or `None` for a log
+    capacity: Option<usize>,
+    entries: VecDeque<TraceEntry>,
+}
+
+impl Tracer {
+    /// A tracer that writes every entry to `output`
+    pub fn log(format: TraceFormat, output: impl Write + 'static) -> Tracer {
+        Tracer {
+            format,
+            output: Box::new(output),
+            capacity: None,
+            entries: VecDeque::new(),
+        }
+    }
+
+    /// A tracer that keeps the last `capacity` entries, writing them to `output` if
+    /// the program fails
+    pub fn ring_buffer(
+        format: TraceFormat,
+        output: impl Write + 'static,
+        capacity: usize,
+    ) -> Tracer {
+        Tracer {
+            format,
+            output: Box::new(output),
+            capacity: Some(capacity),
+            entries: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    /// The entries a ring buffer is holding
+    pub fn entries(&self) -> impl Iterator<Item = &TraceEntry> {
+        self.entries.iter()
+    }
+
+    /// Writes or buffers `entry`. Tracing is only a diagnostic, so a failed write
+    /// does not stop the program
+    pub(super) fn record(&mut self, entry: TraceEntry) {
+        match self.capacity {
+            None => {
+                let _ = entry.write(self.format, &mut self.output);
+            }
+            Some(0) => {}
+            Some(capacity) => {
+                if self.entries.len() == capacity {
+                    self.entries.pop_front();
+                }
+                self.entries.push_back(entry);
+            }
+        }
+    }
+
+    /// Writes out and clears a ring buffer's entries
+    pub(super) fn dump(&mut self) {
+        for entry in self.entries.drain(..) {
+            let _ = entry.write(self.format, &mut self.output);
+        }
+        let _ = self.output.flush();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vm::{test_util::SharedOutput, ExitStatus, VmError};
+
+    fn program() -> Vec<[u8; 4]> {
+        vec![
+            [1, 0, 0, 5], // Set reg0 to 5
+            [1, 1, 0, 0], // Set reg1 to 0
+            [3, 1, 0, 2], // Set reg2 to reg1 - reg0
+            [5, 2, 1, 3], // Set reg3 to reg2 / reg1
+        ]
+    }
+
+    #[test]
+    fn test_human_log() {
+        let output = SharedOutput::default();
+        let mut vm = VM::new();
+        vm.tracer = Some(Tracer::log(TraceFormat::Human, output.clone()));
+        vm.set_program(vec![[1, 0, 0, 5], [3, 1, 0, 2], [0, 0, 0, 0]]);
+
+        assert_eq!(vm.run(), Ok(ExitStatus::Halted));
+        assert_eq!(
+            output.text(),
+            "0000: LOAD $0 #5  $0: 0 -> 5\n\
+             0004: SUB $1 $0 $2  $2: 0 -> -5, flags: ----- -> -NC--\n\
+             0008: HLT\n"
+        );
+    }
+
+    #[test]
+    fn test_json_lines() {
+        let output = SharedOutput::default();
+        let mut vm = VM::new();
+        vm.tracer = Some(Tracer::log(TraceFormat::JsonLines, output.clone()));
+        vm.constants = vec![1.5];
+        vm.set_program(vec![[44, 0, 0, 0], [2, 0, 0, 1]]);
+
+        vm.run_for(2).unwrap();
+        assert_eq!(
+            output.text(),
+            "{\"pc\":0,\"instruction\":\"LOADF $f0 [0]\",\"changes\":{\"$f0\":[0.0,1.5]}}\n\
+             {\"pc\":4,\"instruction\":\"ADD $0 $0 $1\",\"changes\":{\"flags\":[\"-----\",\"Z---T\"]}}\n"
+        );
+    }
+
+    #[test]
+    fn test_ring_buffer() {
+        let output = SharedOutput::default();
+        let mut vm = VM::new();
+        vm.tracer = Some(Tracer::ring_buffer(TraceFormat::Human, output.clone(), 2));
+        vm.set_program(program());
+
+        assert_eq!(vm.run_for(3), Ok(ExitStatus::Running));
+        assert_eq!(output.text(), "");
+        let pcs: Vec<usize> = vm
+            .tracer
+            .as_ref()
+            .unwrap()
+            .entries()
+            .map(|e| e.pc)
+            .collect();
+        assert_eq!(pcs, vec![4, 8]);
+
+        assert_eq!(vm.run(), Err(VmError::DivideByZero { offset: 12 }));
+        assert_eq!(
+            output.text(),
+            "0004: LOAD $1 #0\n\
+             0008: SUB $1 $0 $2  $2: 0 -> -5, flags: ----- -> -NC--\n"
+        );
+        assert_eq!(vm.tracer.as_ref().unwrap().entries().count(), 0);
+    }
+}